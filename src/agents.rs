@@ -0,0 +1,228 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::collector::{contains_exec_token, AGENT_PATTERNS, IDE_AGENT_PATTERNS};
+use crate::model::AgentKind;
+use crate::renderer::{AgentStyle, Theme};
+
+/// Operator-defined agent kinds from an `agents.toml` file, e.g.
+///
+/// ```toml
+/// [agents.aider]
+/// tokens = ["aider"]
+/// icon = "✎"
+/// color = 34   # 256-color background index
+/// ```
+///
+/// A built-in label (`claude`, `codex`, ...) adds detection tokens to that kind; any
+/// other label becomes an `AgentKind::Custom`. A top-level `show_unknown = true` also
+/// reports unmatched interactive long-running processes as `AgentKind::Unknown`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AgentConfig {
+    #[serde(default)]
+    agents: BTreeMap<String, AgentSpec>,
+    #[serde(default)]
+    show_unknown: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AgentSpec {
+    /// Executable names that identify the agent in a process command line.
+    #[serde(default)]
+    pub tokens: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<u8>,
+}
+
+impl AgentConfig {
+    pub fn parse(text: &str) -> Result<Self> {
+        let config: Self = toml::from_str(text).map_err(|e| anyhow!("invalid agents file: {e}"))?;
+        for label in config.agents.keys() {
+            label
+                .parse::<AgentKind>()
+                .map_err(|_| anyhow!("invalid agent label in agents file: {label:?}"))?;
+        }
+        Ok(config)
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| anyhow!("cannot read {}: {e}", path.display()))?;
+        Self::parse(&text)
+    }
+
+    /// `$XDG_CONFIG_HOME/agent-box/agents.toml`, else `~/.config/agent-box/agents.toml`.
+    pub fn default_path() -> Option<PathBuf> {
+        let base = std::env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+        Some(base.join("agent-box").join("agents.toml"))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.agents.is_empty()
+    }
+
+    /// Also report processes no pattern matches, when they look like an interactive
+    /// long-running CLI (stdin on a terminal, not a shell, up for a minute or more).
+    pub fn with_show_unknown(mut self, show_unknown: bool) -> Self {
+        self.show_unknown = show_unknown;
+        self
+    }
+
+    pub fn shows_unknown(&self) -> bool {
+        self.show_unknown
+    }
+
+    /// Agent kind whose configured tokens appear as an executable in `command`.
+    pub fn detect(&self, command: &str) -> Option<AgentKind> {
+        let lower = command.to_lowercase();
+        self.agents.iter().find_map(|(label, spec)| {
+            spec.tokens
+                .iter()
+                .any(|token| contains_exec_token(&lower, &token.to_lowercase()))
+                .then(|| label.parse().ok())
+                .flatten()
+        })
+    }
+
+    /// Every agent label with the command patterns that detect it: built-ins first, in
+    /// detection order, with configured tokens appended, then custom labels by name.
+    pub fn detection_table(&self) -> Vec<(String, Vec<String>)> {
+        let builtins = AGENT_PATTERNS
+            .iter()
+            .map(|(kind, patterns)| (kind.as_label(), *patterns))
+            .chain(IDE_AGENT_PATTERNS.iter().copied());
+        let mut table = builtins
+            .map(|(label, patterns)| {
+                let mut patterns = patterns.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+                if let Some(spec) = self.agents.get(label) {
+                    patterns.extend(spec.tokens.iter().cloned());
+                }
+                (label.to_string(), patterns)
+            })
+            .collect::<Vec<_>>();
+        for (label, spec) in &self.agents {
+            if table.iter().all(|(known, _)| known != label) {
+                table.push((label.clone(), spec.tokens.clone()));
+            }
+        }
+        table
+    }
+
+    /// `--list-agents` output: one `label  pattern, pattern` line per agent.
+    pub fn render_detection_table(&self) -> String {
+        let table = self.detection_table();
+        let width = table.iter().map(|(label, _)| label.len()).max().unwrap_or(0);
+        table
+            .iter()
+            .map(|(label, patterns)| format!("{label:<width$}  {}\n", patterns.join(", ")))
+            .collect()
+    }
+
+    /// Registers icon/color hints for custom labels on `theme`. Colors are dropped for
+    /// themes without backgrounds (`mono`).
+    pub fn apply_to_theme(&self, mut theme: Theme) -> Theme {
+        for (label, spec) in &self.agents {
+            let kind = match label.parse::<AgentKind>() {
+                Ok(kind @ AgentKind::Custom(_)) => kind,
+                _ => continue,
+            };
+            if spec.icon.is_none() && spec.color.is_none() {
+                continue;
+            }
+            let bg = match spec.color {
+                Some(color) if !theme.custom_palette.is_empty() => {
+                    format!("\x1b[48;5;{color}m")
+                }
+                _ => theme.bg_for_agent(&kind).to_string(),
+            };
+            let icon = spec
+                .icon
+                .clone()
+                .unwrap_or_else(|| theme.icon_for_agent(&kind).to_string());
+            theme = theme.with_custom_agent(label, AgentStyle { icon, bg });
+        }
+        theme
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use crate::collector::{Collector, CommandRunner, LocalProcessCollector};
+    use crate::model::AgentKind;
+    use crate::renderer::Theme;
+
+    use super::AgentConfig;
+
+    struct StaticRunner(&'static str);
+
+    impl CommandRunner for StaticRunner {
+        fn run(&self, _program: &str, _args: &[&str]) -> anyhow::Result<String> {
+            Ok(self.0.to_string())
+        }
+    }
+
+    #[test]
+    fn agents_file_adds_detection_and_theme_color() {
+        let unique = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("clock")
+            .as_nanos();
+        let path = std::env::temp_dir().join(format!("agent-box-agents-{unique}.toml"));
+        let text = "[agents.aider]\ntokens = [\"aider\"]\nicon = \"A\"\ncolor = 34\n\n\
+                    [agents.codex]\ntokens = [\"my-codex-wrapper\"]\n";
+        std::fs::write(&path, text).expect("write agents.toml");
+        let config = AgentConfig::load(&path).expect("valid agents file");
+        let _ = std::fs::remove_file(&path);
+
+        let aider = AgentKind::Custom("aider".to_string());
+        assert_eq!(config.detect("/usr/local/bin/aider --model x"), Some(aider.clone()));
+        assert_eq!(config.detect("my-codex-wrapper run"), Some(AgentKind::Codex));
+        assert_eq!(config.detect("vim notes.md"), None);
+
+        let collector = LocalProcessCollector::new()
+            .with_runner(std::sync::Arc::new(StaticRunner("4242 1 aider --yes\n")))
+            .with_agents(config.clone());
+        let sessions = collector.try_collect().expect("listing parses");
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].agent, aider);
+
+        let theme = config.apply_to_theme(Theme::dark());
+        assert_eq!(theme.bg_for_agent(&aider), "\x1b[48;5;34m");
+        assert_eq!(theme.icon_for_agent(&aider), "A");
+        assert_eq!(config.apply_to_theme(Theme::mono()).bg_for_agent(&aider), "");
+    }
+
+    #[test]
+    fn detection_table_lists_builtins_and_loaded_entries() {
+        let config = AgentConfig::parse(
+            "[agents.aider]\ntokens = [\"aider\"]\n\n[agents.codex]\ntokens = [\"cx\"]\n",
+        )
+        .expect("valid agents file");
+        let listing = config.render_detection_table();
+        let lines = listing.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], "claude        claude");
+        assert_eq!(lines[1], "codex         codex, openai, cx");
+        assert_eq!(lines[2], "gemini        gemini");
+        assert!(lines.contains(&"copilot       github-copilot-cli, gh copilot"));
+        assert!(lines.contains(&"windsurf      windsurf, codeium, /windsurf/, /codeium/"));
+        assert_eq!(lines.last(), Some(&"aider         aider"));
+    }
+
+    #[test]
+    fn rejects_bad_labels_and_unknown_keys() {
+        assert!(AgentConfig::parse("[agents.\"Bad Label\"]\ntokens = [\"x\"]\n").is_err());
+        assert!(AgentConfig::parse("[agents.aider]\ntoken = [\"aider\"]\n").is_err());
+        assert!(AgentConfig::parse("").expect("empty file is fine").is_empty());
+    }
+}
@@ -1,22 +1,153 @@
+use std::fmt;
 use std::io::{Read, Write};
-use std::net::IpAddr;
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::PathBuf;
 use std::net::TcpStream;
 use std::process;
 use std::str::FromStr;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use anyhow::{anyhow, Result};
 use clap::{Parser, ValueEnum};
+use serde::{Deserialize, Serialize};
 
-use crate::security::generate_passkey_sha1;
+use crate::agents::AgentConfig;
+use crate::security::{generate_passkey_sha1, passkey_strength, RedactionPolicy, Strength};
+use crate::sync::{
+    validate_auth_key, JitterMode, RetryPolicy, TransportProtocol, DEFAULT_MAX_CLOCK_SKEW_MS,
+};
 
-#[derive(Debug, Clone, Copy, ValueEnum, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, ValueEnum, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Protocol {
     Http,
     Https,
     Quic,
 }
 
+/// Animation next to running sessions.
+#[derive(Debug, Clone, Copy, ValueEnum, PartialEq, Eq)]
+pub enum Spinner {
+    /// `◴◷◶◵`
+    Quadrant,
+    Braille,
+    /// `|/-\`, for limited fonts.
+    Ascii,
+    Dots,
+}
+
+/// How `REMOTE <peer>` sections are ordered.
+#[derive(Debug, Clone, Copy, ValueEnum, PartialEq, Eq)]
+pub enum PeerOrder {
+    /// Alphabetically by peer name.
+    Name,
+    /// By when each peer's first session appeared, so new peers go last.
+    FirstSeen,
+}
+
+/// How much of each session is shared with peers.
+#[derive(Debug, Clone, Copy, ValueEnum, PartialEq, Eq)]
+pub enum Share {
+    /// Every field, after redaction.
+    Full,
+    /// Agent, status, user and timestamps; no titles, dirs or transcript.
+    StatusOnly,
+    /// Titles and dirs replaced by stable pseudonyms; no transcript.
+    Redacted,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Live dashboard of session cards.
+    Text,
+    /// One JSON object per session per tick, newline-delimited.
+    Jsonl,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ThemeName {
+    Dark,
+    Light,
+    Mono,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum, PartialEq, Eq)]
+pub enum ErrorFormat {
+    /// Human-readable `Error: ...` text.
+    Text,
+    /// A single `{"error": ..., "kind": ...}` JSON object.
+    Json,
+}
+
+/// Broad category of a fatal startup error, reported as `kind` by `--error-format json`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    InvalidPeer,
+    InvalidBind,
+    InvalidKey,
+    Interface,
+    Io,
+    Other,
+}
+
+impl ErrorKind {
+    pub fn as_label(&self) -> &'static str {
+        match self {
+            ErrorKind::InvalidPeer => "invalid_peer",
+            ErrorKind::InvalidBind => "invalid_bind",
+            ErrorKind::InvalidKey => "invalid_key",
+            ErrorKind::Interface => "interface",
+            ErrorKind::Io => "io",
+            ErrorKind::Other => "other",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct CliError {
+    pub kind: ErrorKind,
+    pub message: String,
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for CliError {}
+
+pub fn cli_error(kind: ErrorKind, message: impl Into<String>) -> anyhow::Error {
+    CliError {
+        kind,
+        message: message.into(),
+    }
+    .into()
+}
+
+/// Kind of the outermost typed error in `err`'s chain; `Other` when none is typed.
+pub fn error_kind(err: &anyhow::Error) -> ErrorKind {
+    for cause in err.chain() {
+        if let Some(cli) = cause.downcast_ref::<CliError>() {
+            return cli.kind;
+        }
+        if cause.is::<std::io::Error>() {
+            return ErrorKind::Io;
+        }
+    }
+    ErrorKind::Other
+}
+
+pub fn error_json(err: &anyhow::Error) -> String {
+    serde_json::json!({
+        "error": format!("{err:#}"),
+        "kind": error_kind(err).as_label(),
+    })
+    .to_string()
+}
+
 #[derive(Debug, Parser)]
 #[command(name = "agent-box")]
 #[command(about = "Terminal monitor for local and remote agent sessions")]
@@ -30,20 +161,347 @@ pub struct CliArgs {
     #[arg(short = 'i', long, default_value = "127.0.0.1")]
     pub ip: String,
 
+    #[arg(
+        long,
+        conflicts_with = "ip",
+        help = "Bind to this network interface's primary IPv4 address (e.g. eth0)"
+    )]
+    pub iface: Option<String>,
+
     #[arg(long, help = "Use detected public IP as bind/join IP")]
     pub public: bool,
 
+    #[arg(long, help = "With --public, use the public IPv6 address when the host has both")]
+    pub prefer_ipv6: bool,
+
     #[arg(long, help = "Explicit session passkey for join/sync")]
     pub key: Option<String>,
 
-    #[arg(short = 'p', long, default_value_t = 8346)]
+    #[arg(short = 'p', long, default_value_t = DEFAULT_PORT)]
     pub port: u16,
 
-    #[arg(short = 't', long, default_value_t = 3)]
-    pub interval: u64,
+    #[arg(
+        short = 't',
+        long,
+        default_value = "3s",
+        value_parser = parse_interval,
+        help = "Refresh interval such as 500ms, 2s or 1m (bare numbers are seconds)"
+    )]
+    pub interval: Duration,
 
     #[arg(short = 'r', long = "protocol", value_enum, default_value_t = Protocol::Http)]
     pub protocol: Protocol,
+
+    #[arg(long, help = "Show per-user totals across hosts instead of session cards")]
+    pub by_user: bool,
+
+    #[arg(long, help = "Group session cards by git project instead of by host")]
+    pub by_project: bool,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = PeerOrder::Name,
+        help = "Order of remote peer sections"
+    )]
+    pub peer_order: PeerOrder,
+
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+
+    #[arg(long, help = "Append output to this file instead of stdout")]
+    pub output: Option<PathBuf>,
+
+    #[arg(long, help = "Draw on the terminal's alternate screen to keep scrollback intact")]
+    pub alt_screen: bool,
+
+    #[arg(long, help = "Redraw only the session cards that changed instead of the whole screen")]
+    pub incremental: bool,
+
+    #[arg(long, help = "Never clear the screen; append each refresh")]
+    pub no_clear: bool,
+
+    #[arg(long, help = "Hide finished (success/failed/stopped) sessions from the view")]
+    pub hide_done: bool,
+
+    #[arg(
+        long,
+        value_name = "SECS",
+        default_value_t = 0,
+        help = "Keep finished sessions on screen for SECS seconds after they disappear"
+    )]
+    pub linger_secs: u64,
+
+    #[arg(
+        long,
+        value_name = "URL",
+        help = "POST session start/status-change/end events as JSON to this http:// URL"
+    )]
+    pub webhook: Option<String>,
+
+    #[arg(long, help = "Also log session start/status-change/end events to the local syslog")]
+    pub syslog: bool,
+
+    #[arg(long, help = "Share transcript lines and pending actions with peers unredacted")]
+    pub no_redact: bool,
+
+    #[arg(
+        long,
+        conflicts_with = "no_redact",
+        help = "Also redact secrets in session titles before sharing them with peers"
+    )]
+    pub redact_titles: bool,
+
+    #[arg(
+        long,
+        value_name = "MARKER",
+        conflicts_with = "no_redact",
+        help = "Mask text after this case-insensitive marker too, e.g. ghp_ (repeatable)"
+    )]
+    pub redact_pattern: Vec<String>,
+
+    #[arg(long, help = "Drop pulls whose reply names a host other than the one dialed")]
+    pub strict_peer: bool,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = Share::Full,
+        help = "How much of each session peers see"
+    )]
+    pub share: Share,
+
+    #[arg(long, help = "Show users and peer hosts as stable pseudonyms (e.g. user-a3f)")]
+    pub anonymize: bool,
+
+    #[arg(
+        long,
+        value_name = "N",
+        help = "Show at most N newest sessions per agent kind; the rest are summarized"
+    )]
+    pub max_per_agent: Option<usize>,
+
+    #[arg(
+        long,
+        value_name = "N",
+        help = "Collect at most N of the most recently updated local sessions"
+    )]
+    pub max_sessions: Option<usize>,
+
+    #[arg(
+        long,
+        value_name = "MS",
+        default_value_t = 0,
+        help = "Send idle peers a heartbeat instead of a full pull for up to MS ms (0 = off)"
+    )]
+    pub heartbeat_ms: u64,
+
+    #[arg(
+        long,
+        value_name = "MS",
+        default_value_t = DEFAULT_MAX_CLOCK_SKEW_MS,
+        help = "Clamp peer timestamps more than MS ms ahead of the local clock to now"
+    )]
+    pub max_clock_skew_ms: u64,
+
+    #[arg(
+        long,
+        value_name = "N",
+        value_parser = clap::value_parser!(u16).range(1..),
+        help = "Never draw lines wider than N columns, even on wider terminals"
+    )]
+    pub max_width: Option<u16>,
+
+    #[arg(
+        long,
+        value_name = "N",
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Show page N of the sessions (page size from --max-rows or the terminal height)"
+    )]
+    pub page: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "N",
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Show at most N session cards per page"
+    )]
+    pub max_rows: Option<u64>,
+
+    #[arg(long, value_enum, default_value_t = ThemeName::Dark, help = "Color palette")]
+    pub theme: ThemeName,
+
+    #[arg(
+        long,
+        value_name = "GLOB",
+        help = "Hide local sessions owned by users matching this glob (repeatable)"
+    )]
+    pub exclude_user: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "NAME",
+        help = "Executable name of this monitor to skip in listings (repeatable; default: own binary)"
+    )]
+    pub self_name: Vec<String>,
+
+    #[arg(long, value_name = "ID", help = "Show this session id first (repeatable, in order)")]
+    pub pin: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "GLOB",
+        help = "Hide local sessions in directories matching this glob or below it (repeatable)"
+    )]
+    pub exclude_dir: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Extra agent kinds and detection tokens (default: ~/.config/agent-box/agents.toml)"
+    )]
+    pub agents_file: Option<PathBuf>,
+
+    #[arg(
+        long,
+        help = "Also show unmatched interactive CLIs running for a minute or more, as unknown"
+    )]
+    pub show_unknown: bool,
+
+    #[arg(long, help = "Print a single status-bar line for local sessions and exit")]
+    pub oneline: bool,
+
+    #[arg(long, help = "Print each agent label with the command patterns that detect it and exit")]
+    pub list_agents: bool,
+
+    #[arg(long, help = "Print the effective configuration as TOML and exit")]
+    pub dump_config: bool,
+
+    #[arg(long, help = "Show sample sessions instead of scanning processes; no networking")]
+    pub demo: bool,
+
+    #[arg(long, help = "Render a single refresh and exit")]
+    pub once: bool,
+
+    #[arg(
+        long,
+        help = "Exit nonzero, listing them, if any session is waiting for input (implies --once)"
+    )]
+    pub fail_on_waiting: bool,
+
+    #[arg(short = 'v', long, help = "Show extra detail such as raw millisecond timestamps")]
+    pub verbose: bool,
+
+    #[arg(
+        short = 'q',
+        long,
+        help = "Print only session output: no banner, join line or warnings (errors still show)"
+    )]
+    pub quiet: bool,
+
+    #[arg(
+        long,
+        value_name = "TEXT",
+        help = "Name this monitor: shown as a header and included in JSONL and webhook output"
+    )]
+    pub label: Option<String>,
+
+    #[arg(long, help = "Freeze the running spinner and age fading, for reproducible output")]
+    pub no_spinner: bool,
+
+    #[arg(long, value_enum, default_value_t = Spinner::Quadrant, help = "Running spinner style")]
+    pub spinner: Spinner,
+
+    #[arg(long, help = "List session files found for the current directory and exit")]
+    pub debug_sessions: bool,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = ErrorFormat::Text,
+        help = "How fatal errors are printed"
+    )]
+    pub error_format: ErrorFormat,
+}
+
+/// What `--dump-config` prints: the flags that shape a run once merged with the agents
+/// file and environment. The session key is deliberately left out.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EffectiveConfig {
+    pub interval_ms: u64,
+    pub port: u16,
+    pub protocol: Protocol,
+    pub theme: ThemeName,
+    pub heartbeat_ms: u64,
+    pub max_clock_skew_ms: u64,
+    pub linger_secs: u64,
+    pub redaction: RedactionPolicy,
+    /// Flattened so the dump's `[agents.*]` tables read like an agents file.
+    #[serde(flatten)]
+    pub agents: AgentConfig,
+}
+
+impl EffectiveConfig {
+    pub fn new(args: &CliArgs, redaction: RedactionPolicy, agents: AgentConfig) -> Self {
+        Self {
+            interval_ms: args.interval.as_millis() as u64,
+            port: args.port,
+            protocol: args.protocol,
+            theme: args.theme,
+            heartbeat_ms: args.heartbeat_ms,
+            max_clock_skew_ms: args.max_clock_skew_ms,
+            linger_secs: args.linger_secs,
+            redaction,
+            agents,
+        }
+    }
+
+    pub fn to_toml(&self) -> Result<String> {
+        toml::to_string(self).map_err(|e| anyhow!("cannot serialize config: {e}"))
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        toml::from_str(text).map_err(|e| anyhow!("invalid config: {e}"))
+    }
+}
+
+/// Fastest refresh the monitor will run at, regardless of `--interval`.
+pub const MIN_INTERVAL: Duration = Duration::from_millis(100);
+
+pub fn parse_interval(value: &str) -> Result<Duration> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (digits, unit) = value.split_at(split);
+    let amount = digits
+        .parse::<u64>()
+        .map_err(|_| anyhow!("invalid interval: {value}"))?;
+    let parsed = match unit {
+        "ms" => Duration::from_millis(amount),
+        "" | "s" => Duration::from_secs(amount),
+        "m" => Duration::from_secs(amount.saturating_mul(60)),
+        _ => return Err(anyhow!("invalid interval unit in {value} (use ms, s or m)")),
+    };
+    Ok(parsed.max(MIN_INTERVAL))
+}
+
+/// Sync port used when `--port` is not given.
+pub const DEFAULT_PORT: u16 = 8346;
+
+/// Command another host runs to join this one, e.g. `agent-box 10.0.0.5:key`; `--port`
+/// is appended only when it differs from `DEFAULT_PORT`.
+pub fn join_string(ip: &str, port: u16, key: &str) -> String {
+    // `host:key` splits at the first colon, so IPv6 literals go in brackets.
+    let ip = match IpAddr::from_str(ip) {
+        Ok(IpAddr::V6(v6)) => format!("[{v6}]"),
+        _ => ip.to_string(),
+    };
+    if port == DEFAULT_PORT {
+        format!("agent-box {ip}:{key}")
+    } else {
+        format!("agent-box {ip}:{key} --port {port}")
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -62,12 +520,29 @@ where
 }
 
 pub fn parse_peer(peer: &str, session_unix_ms: u64) -> Result<ParsedPeer> {
-    if let Some((host, auth_key)) = peer.split_once(':') {
+    let host_and_key = match peer.trim().strip_prefix('[') {
+        // `[2001:db8::1]:key`, or just `[2001:db8::1]` to have a key generated.
+        Some(bracketed) => match bracketed.split_once(']') {
+            Some((_, "")) => None,
+            Some((host, rest)) => match rest.strip_prefix(':') {
+                Some(key) => Some((host, key)),
+                None => {
+                    return Err(cli_error(ErrorKind::InvalidPeer, format!("invalid peer: {peer}")))
+                }
+            },
+            None => return Err(cli_error(ErrorKind::InvalidPeer, "unclosed '[' in peer host")),
+        },
+        None => peer.split_once(':'),
+    };
+    if let Some((host, auth_key)) = host_and_key {
         if host.trim().is_empty() || auth_key.trim().is_empty() {
-            return Err(anyhow!("peer host and auth key must be non-empty"));
+            return Err(cli_error(
+                ErrorKind::InvalidPeer,
+                "peer host and auth key must be non-empty",
+            ));
         }
         return Ok(ParsedPeer {
-            host: host.to_string(),
+            host: canonicalize_host(host)?,
             auth_key: auth_key.to_string(),
             generated_auth_key: false,
         });
@@ -75,8 +550,9 @@ pub fn parse_peer(peer: &str, session_unix_ms: u64) -> Result<ParsedPeer> {
 
     let host = peer.trim();
     if host.is_empty() {
-        return Err(anyhow!("peer host must be non-empty"));
+        return Err(cli_error(ErrorKind::InvalidPeer, "peer host must be non-empty"));
     }
+    let host = canonicalize_host(host)?;
 
     let local_host = detect_hostname();
     let random_seed = runtime_random_seed();
@@ -87,13 +563,47 @@ pub fn parse_peer(peer: &str, session_unix_ms: u64) -> Result<ParsedPeer> {
     );
 
     Ok(ParsedPeer {
-        host: host.to_string(),
+        host,
         auth_key: generated,
         generated_auth_key: true,
     })
 }
 
-fn detect_hostname() -> String {
+/// Normalizes a peer host so typos fail at parse time rather than on first connect.
+/// IP literals are validated and re-printed canonically; hostnames are lowercased
+/// and checked against RFC 1123 label rules.
+pub fn canonicalize_host(host: &str) -> Result<String> {
+    let host = host.trim();
+    if host.is_empty() {
+        return Err(cli_error(ErrorKind::InvalidPeer, "peer host must be non-empty"));
+    }
+    let unbracketed = host
+        .strip_prefix('[')
+        .and_then(|h| h.strip_suffix(']'))
+        .unwrap_or(host);
+    if let Ok(ip) = IpAddr::from_str(unbracketed) {
+        return Ok(ip.to_string());
+    }
+    if host.chars().all(|c| c.is_ascii_digit() || c == '.') {
+        return Err(cli_error(ErrorKind::InvalidPeer, format!("invalid IP address: {host}")));
+    }
+
+    let lower = host.trim_end_matches('.').to_lowercase();
+    let valid_label = |label: &str| {
+        !label.is_empty()
+            && label.len() <= 63
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+            && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+    };
+    if lower.len() > 253 || !lower.split('.').all(valid_label) {
+        return Err(cli_error(ErrorKind::InvalidPeer, format!("invalid peer host: {host}")));
+    }
+    Ok(lower)
+}
+
+/// Best-effort local host name from the environment; `localhost` when unset.
+pub fn detect_hostname() -> String {
     std::env::var("HOSTNAME")
         .or_else(|_| std::env::var("COMPUTERNAME"))
         .unwrap_or_else(|_| "localhost".to_string())
@@ -108,37 +618,375 @@ fn runtime_random_seed() -> u64 {
 }
 
 pub fn validate_bind(ip: &str, port: u16) -> Result<()> {
-    let _ = IpAddr::from_str(ip).map_err(|_| anyhow!("invalid IP address: {ip}"))?;
+    let _ = IpAddr::from_str(ip)
+        .map_err(|_| cli_error(ErrorKind::InvalidBind, format!("invalid IP address: {ip}")))?;
     if port == 0 {
-        return Err(anyhow!("port 0 is not allowed"));
+        return Err(cli_error(ErrorKind::InvalidBind, "port 0 is not allowed"));
     }
     Ok(())
 }
 
-pub fn detect_public_ip() -> Result<String> {
-    let mut stream = TcpStream::connect("api.ipify.org:80")
-        .map_err(|e| anyhow!("failed to contact public IP service: {e}"))?;
-    let request = "GET / HTTP/1.1\r\nHost: api.ipify.org\r\nConnection: close\r\n\r\n";
-    stream
-        .write_all(request.as_bytes())
-        .map_err(|e| anyhow!("failed to request public IP: {e}"))?;
-    let mut response = String::new();
-    stream
-        .read_to_string(&mut response)
-        .map_err(|e| anyhow!("failed to read public IP response: {e}"))?;
-    let body = response
-        .split("\r\n\r\n")
-        .last()
-        .ok_or_else(|| anyhow!("invalid public IP response"))?
-        .trim();
-    let _ = IpAddr::from_str(body).map_err(|_| anyhow!("invalid public IP: {body}"))?;
-    Ok(body.to_string())
+/// One address assigned to a network interface.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InterfaceAddr {
+    pub name: String,
+    pub ip: IpAddr,
+}
+
+/// Picks the first IPv4 address of interface `name` from `table`.
+pub fn resolve_iface_ipv4(name: &str, table: &[InterfaceAddr]) -> Result<Ipv4Addr> {
+    let mut known = false;
+    for entry in table.iter().filter(|e| e.name == name) {
+        known = true;
+        if let IpAddr::V4(ip) = entry.ip {
+            return Ok(ip);
+        }
+    }
+    if known {
+        Err(cli_error(
+            ErrorKind::Interface,
+            format!("interface {name} has no IPv4 address"),
+        ))
+    } else {
+        Err(cli_error(ErrorKind::Interface, format!("no such network interface: {name}")))
+    }
+}
+
+/// Addresses of all local interfaces, in the order `getifaddrs` reports them.
+#[cfg(unix)]
+pub fn system_interfaces() -> Result<Vec<InterfaceAddr>> {
+    use std::ffi::CStr;
+    use std::net::Ipv6Addr;
+
+    let mut head: *mut libc::ifaddrs = std::ptr::null_mut();
+    // SAFETY: getifaddrs fills `head` with a list we walk read-only and free once below.
+    if unsafe { libc::getifaddrs(&mut head) } != 0 {
+        return Err(cli_error(
+            ErrorKind::Interface,
+            format!("getifaddrs failed: {}", std::io::Error::last_os_error()),
+        ));
+    }
+    let mut out = Vec::new();
+    let mut cursor = head;
+    while !cursor.is_null() {
+        // SAFETY: every node and its name/addr pointers stay valid until freeifaddrs.
+        let entry = unsafe { &*cursor };
+        cursor = entry.ifa_next;
+        if entry.ifa_addr.is_null() || entry.ifa_name.is_null() {
+            continue;
+        }
+        let name = unsafe { CStr::from_ptr(entry.ifa_name) }
+            .to_string_lossy()
+            .into_owned();
+        let ip = match i32::from(unsafe { (*entry.ifa_addr).sa_family }) {
+            libc::AF_INET => {
+                let addr = unsafe { &*(entry.ifa_addr as *const libc::sockaddr_in) };
+                IpAddr::V4(Ipv4Addr::from(u32::from_be(addr.sin_addr.s_addr)))
+            }
+            libc::AF_INET6 => {
+                let addr = unsafe { &*(entry.ifa_addr as *const libc::sockaddr_in6) };
+                IpAddr::V6(Ipv6Addr::from(addr.sin6_addr.s6_addr))
+            }
+            _ => continue,
+        };
+        out.push(InterfaceAddr { name, ip });
+    }
+    unsafe { libc::freeifaddrs(head) };
+    Ok(out)
+}
+
+#[cfg(not(unix))]
+pub fn system_interfaces() -> Result<Vec<InterfaceAddr>> {
+    Err(cli_error(ErrorKind::Interface, "--iface is only supported on Unix"))
+}
+
+/// Asks an external service for this host's public address over one IP family.
+pub trait PublicIpLookup: Sync {
+    fn lookup(&self, ipv6: bool) -> Result<String>;
+}
+
+/// ipify over plain HTTP: `api.ipify.org` answers over IPv4, `api6.ipify.org` over IPv6.
+#[derive(Debug, Default)]
+pub struct IpifyLookup;
+
+impl PublicIpLookup for IpifyLookup {
+    fn lookup(&self, ipv6: bool) -> Result<String> {
+        let host = if ipv6 { "api6.ipify.org" } else { "api.ipify.org" };
+        let mut stream = TcpStream::connect((host, 80))
+            .map_err(|e| anyhow!("failed to contact public IP service: {e}"))?;
+        let request = format!("GET / HTTP/1.1\r\nHost: {host}\r\nConnection: close\r\n\r\n");
+        stream
+            .write_all(request.as_bytes())
+            .map_err(|e| anyhow!("failed to request public IP: {e}"))?;
+        let mut response = String::new();
+        stream
+            .read_to_string(&mut response)
+            .map_err(|e| anyhow!("failed to read public IP response: {e}"))?;
+        let body = response
+            .split("\r\n\r\n")
+            .last()
+            .ok_or_else(|| anyhow!("invalid public IP response"))?;
+        Ok(body.trim().to_string())
+    }
+}
+
+pub fn detect_public_ip(prefer_ipv6: bool) -> Result<String> {
+    detect_public_ip_with(&IpifyLookup, prefer_ipv6)
+}
+
+/// Startup retry budget for the public IP lookup: three tries within about a second.
+pub fn public_ip_retry_policy() -> RetryPolicy {
+    RetryPolicy {
+        max_attempts: 3,
+        base_delay_ms: 250,
+        max_delay_ms: 1_000,
+        jitter: JitterMode::None,
+    }
+}
+
+/// `detect_public_ip_with`, retried per `policy` so a brief network blip at startup does
+/// not lose the address. `on_retry` sees each failed attempt that will be retried.
+pub fn detect_public_ip_retrying(
+    lookup: &dyn PublicIpLookup,
+    prefer_ipv6: bool,
+    policy: &RetryPolicy,
+    on_retry: &mut dyn FnMut(u32, &anyhow::Error),
+) -> Result<String> {
+    let mut attempt = 1;
+    loop {
+        match detect_public_ip_with(lookup, prefer_ipv6) {
+            Ok(ip) => return Ok(ip),
+            Err(err) if attempt < policy.max_attempts => {
+                on_retry(attempt, &err);
+                thread::sleep(policy.delay_for_attempt(attempt));
+                attempt += 1;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Queries both families at once and returns the preferred one (IPv4 unless
+/// `prefer_ipv6`), falling back to the other, so IPv6-only hosts still get an address.
+pub fn detect_public_ip_with(lookup: &dyn PublicIpLookup, prefer_ipv6: bool) -> Result<String> {
+    let (v4, v6) = thread::scope(|scope| {
+        let v6 = scope.spawn(|| public_ip_of_family(lookup, true));
+        let v4 = public_ip_of_family(lookup, false);
+        let v6 = v6
+            .join()
+            .unwrap_or_else(|_| Err(anyhow!("IPv6 public IP lookup panicked")));
+        (v4, v6)
+    });
+    let (preferred, fallback) = if prefer_ipv6 { (v6, v4) } else { (v4, v6) };
+    match (preferred, fallback) {
+        (Ok(ip), _) | (Err(_), Ok(ip)) => Ok(ip.to_string()),
+        (Err(err), Err(other)) => Err(anyhow!("{err}; {other}")),
+    }
+}
+
+fn public_ip_of_family(lookup: &dyn PublicIpLookup, ipv6: bool) -> Result<IpAddr> {
+    let body = lookup.lookup(ipv6)?;
+    let ip = IpAddr::from_str(body.trim()).map_err(|_| anyhow!("invalid public IP: {body}"))?;
+    if ip.is_ipv6() != ipv6 {
+        return Err(anyhow!("public IP service returned {ip} for the wrong family"));
+    }
+    Ok(ip)
+}
+
+/// Network and key settings derived from the flags, validated once at startup.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuntimeConfig {
+    /// Address advertised in the join string.
+    pub listen_ip: String,
+    /// Address the sync server binds; the wildcard of the listen family when public.
+    pub bind_ip: String,
+    pub local_host: String,
+    /// Key sessions are shared under; `None` only with `--no-expose` and no key or peer.
+    pub session_key: Option<String>,
+    pub peer: Option<ParsedPeer>,
+    pub protocol: TransportProtocol,
+    /// Non-fatal problems for the caller to report.
+    pub warnings: Vec<String>,
+}
+
+impl RuntimeConfig {
+    pub fn peer_host(&self) -> Option<&str> {
+        self.peer.as_ref().map(|peer| peer.host.as_str())
+    }
+}
+
+pub fn resolve_runtime_config(args: &CliArgs) -> Result<RuntimeConfig> {
+    let session_unix_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0);
+    resolve_runtime_config_with(args, &IpifyLookup, session_unix_ms)
+}
+
+/// `resolve_runtime_config` with the public IP lookup and key timestamp supplied.
+pub fn resolve_runtime_config_with(
+    args: &CliArgs,
+    lookup: &dyn PublicIpLookup,
+    session_unix_ms: u64,
+) -> Result<RuntimeConfig> {
+    let mut warnings = Vec::new();
+    let iface_ip = match args.iface.as_deref() {
+        Some(name) => Some(resolve_iface_ipv4(name, &system_interfaces()?)?.to_string()),
+        None => None,
+    };
+    let prefer_public_ip = iface_ip.is_none() && (args.public || args.peer.is_some());
+    let listen_ip = if let Some(ip) = iface_ip {
+        ip
+    } else if prefer_public_ip {
+        let detected = detect_public_ip_retrying(
+            lookup,
+            args.prefer_ipv6,
+            &public_ip_retry_policy(),
+            &mut |attempt, err| {
+                if args.verbose {
+                    warnings.push(format!(
+                        "public IP lookup attempt {attempt} failed ({err}); retrying"
+                    ));
+                }
+            },
+        );
+        match detected {
+            Ok(ip) => ip,
+            Err(err) => {
+                warnings.push(format!(
+                    "public IP resolution failed ({err}); using configured bind IP {}",
+                    args.ip
+                ));
+                args.ip.clone()
+            }
+        }
+    } else {
+        args.ip.clone()
+    };
+    validate_bind(&listen_ip, args.port)?;
+    let local_host = detect_hostname();
+
+    if let Some(key) = args.key.as_deref() {
+        if passkey_strength(key) == Strength::Weak {
+            warnings.push(
+                "--key is weak (short or repetitive); omit --key to get a generated passkey, \
+                 or use a longer random one."
+                    .to_string(),
+            );
+        }
+    }
+
+    let mut peer = None;
+    let session_key = if let Some(target) = args.peer.as_deref() {
+        let mut parsed = parse_peer(target, session_unix_ms)?;
+        if let Some(explicit) = args.key.as_deref() {
+            parsed.auth_key = explicit.to_string();
+            parsed.generated_auth_key = false;
+        }
+        validate_auth_key(&parsed.auth_key).map_err(invalid_key)?;
+        let key = parsed.auth_key.clone();
+        peer = Some(parsed);
+        Some(key)
+    } else if let Some(explicit) = args.key.as_deref() {
+        // Explicit key also defines local session sharing key without a join target.
+        validate_auth_key(explicit).map_err(invalid_key)?;
+        Some(explicit.to_string())
+    } else if !args.no_expose {
+        // No passkey supplied at all in CLI input: generate one for join instructions.
+        Some(generate_passkey_sha1(
+            &format!("{local_host}:{listen_ip}"),
+            session_unix_ms,
+            runtime_random_seed(),
+        ))
+    } else {
+        None
+    };
+
+    let bind_ip = if prefer_public_ip && listen_ip.contains(':') {
+        "::".to_string()
+    } else if prefer_public_ip {
+        "0.0.0.0".to_string()
+    } else {
+        listen_ip.clone()
+    };
+    if prefer_public_ip && session_key.is_none() {
+        return Err(cli_error(
+            ErrorKind::InvalidBind,
+            "--public without a session key; pass --key or drop --no-expose",
+        ));
+    }
+
+    Ok(RuntimeConfig {
+        listen_ip,
+        bind_ip,
+        local_host,
+        session_key,
+        peer,
+        protocol: transport_from_args(args.protocol),
+        warnings,
+    })
+}
+
+fn invalid_key(err: anyhow::Error) -> anyhow::Error {
+    cli_error(ErrorKind::InvalidKey, err.to_string())
+}
+
+fn transport_from_args(protocol: Protocol) -> TransportProtocol {
+    match protocol {
+        Protocol::Http => TransportProtocol::Http,
+        Protocol::Https => TransportProtocol::Https,
+        Protocol::Quic => TransportProtocol::Quic,
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn dumped_config_parses_back_to_the_same_config() {
+        let args = CliArgs::try_parse_from([
+            "agent-box",
+            "--interval",
+            "750ms",
+            "--theme",
+            "mono",
+            "--protocol",
+            "quic",
+            "--heartbeat-ms",
+            "9000",
+            "--linger-secs",
+            "4",
+        ])
+        .expect("args parse");
+        let agents = AgentConfig::parse(
+            "show_unknown = true\n[agents.aider]\ntokens = [\"aider\"]\ncolor = 34\n\n\
+             [agents.codex]\ntokens = [\"my-codex\"]\n",
+        )
+        .expect("agents parse");
+        let redaction = RedactionPolicy::default()
+            .with_titles(true)
+            .with_extra_patterns(vec!["ghp_".to_string()]);
+        let config = EffectiveConfig::new(&args, redaction, agents);
+
+        let text = config.to_toml().expect("serializes");
+        assert!(text.contains("interval_ms = 750"), "{text}");
+        assert!(text.contains("theme = \"mono\""), "{text}");
+        assert_eq!(EffectiveConfig::parse(&text).expect("re-parses"), config);
+    }
+
+    #[test]
+    fn join_string_mentions_port_only_when_non_default() {
+        assert_eq!(
+            join_string("10.0.0.5", DEFAULT_PORT, "abc123"),
+            "agent-box 10.0.0.5:abc123"
+        );
+        assert_eq!(
+            join_string("10.0.0.5", 9000, "abc123"),
+            "agent-box 10.0.0.5:abc123 --port 9000"
+        );
+    }
+
     #[test]
     fn parses_defaults() {
         let args = parse_args_from(["agent-box"]);
@@ -146,8 +994,62 @@ mod tests {
         assert!(!args.public);
         assert!(args.key.is_none());
         assert_eq!(args.port, 8346);
-        assert_eq!(args.interval, 3);
+        assert_eq!(args.interval, Duration::from_secs(3));
         assert_eq!(args.protocol, Protocol::Http);
+        assert_eq!(args.format, OutputFormat::Text);
+        assert_eq!(args.theme, ThemeName::Dark);
+        assert!(args.output.is_none());
+    }
+
+    #[test]
+    fn parses_jsonl_format_with_output() {
+        let args = parse_args_from([
+            "agent-box",
+            "--format",
+            "jsonl",
+            "--output",
+            "/tmp/a.jsonl",
+        ]);
+        assert_eq!(args.format, OutputFormat::Jsonl);
+        assert_eq!(args.output, Some(PathBuf::from("/tmp/a.jsonl")));
+    }
+
+    #[test]
+    fn resolves_iface_from_table() {
+        let table = vec![
+            InterfaceAddr {
+                name: "lo".to_string(),
+                ip: "127.0.0.1".parse().unwrap(),
+            },
+            InterfaceAddr {
+                name: "eth0".to_string(),
+                ip: "fe80::1".parse().unwrap(),
+            },
+            InterfaceAddr {
+                name: "eth0".to_string(),
+                ip: "10.0.0.7".parse().unwrap(),
+            },
+            InterfaceAddr {
+                name: "wg0".to_string(),
+                ip: "fd00::2".parse().unwrap(),
+            },
+        ];
+        assert_eq!(
+            resolve_iface_ipv4("eth0", &table).unwrap(),
+            Ipv4Addr::new(10, 0, 0, 7)
+        );
+        let no_v4 = resolve_iface_ipv4("wg0", &table).unwrap_err().to_string();
+        assert!(no_v4.contains("no IPv4 address"));
+        let missing = resolve_iface_ipv4("eth9", &table).unwrap_err().to_string();
+        assert!(missing.contains("no such network interface"));
+    }
+
+    #[test]
+    fn iface_conflicts_with_ip() {
+        let args = parse_args_from(["agent-box", "--iface", "eth0"]);
+        assert_eq!(args.iface.as_deref(), Some("eth0"));
+        let err = CliArgs::try_parse_from(["agent-box", "--iface", "eth0", "--ip", "10.0.0.1"]);
+        assert!(err.is_err());
     }
 
     #[test]
@@ -156,6 +1058,124 @@ mod tests {
         assert!(args.public);
     }
 
+    struct FixedLookup {
+        v4: Option<&'static str>,
+        v6: Option<&'static str>,
+    }
+
+    impl PublicIpLookup for FixedLookup {
+        fn lookup(&self, ipv6: bool) -> Result<String> {
+            let answer = if ipv6 { self.v6 } else { self.v4 };
+            answer
+                .map(str::to_string)
+                .ok_or_else(|| anyhow!("no route for this family"))
+        }
+    }
+
+    #[test]
+    fn public_ip_prefers_requested_family_and_falls_back() {
+        let dual = FixedLookup {
+            v4: Some("203.0.113.7\n"),
+            v6: Some("2001:db8::7"),
+        };
+        assert_eq!(detect_public_ip_with(&dual, false).unwrap(), "203.0.113.7");
+        assert_eq!(detect_public_ip_with(&dual, true).unwrap(), "2001:db8::7");
+
+        let v6_only = FixedLookup {
+            v4: None,
+            v6: Some("2001:db8::7"),
+        };
+        let ip = detect_public_ip_with(&v6_only, false).expect("falls back to IPv6");
+        assert_eq!(ip, "2001:db8::7");
+        validate_bind(&ip, DEFAULT_PORT).expect("IPv6 literal is a valid bind address");
+
+        let join = join_string(&ip, DEFAULT_PORT, "abc123");
+        assert_eq!(join, "agent-box [2001:db8::7]:abc123");
+        let parsed = parse_peer(join.trim_start_matches("agent-box "), 1).expect("round trip");
+        assert_eq!((parsed.host.as_str(), parsed.auth_key.as_str()), ("2001:db8::7", "abc123"));
+        assert_eq!(parse_peer("[2001:db8::7]", 1).unwrap().host, "2001:db8::7");
+
+        let mislabeled = FixedLookup {
+            v4: Some("2001:db8::7"),
+            v6: None,
+        };
+        assert!(detect_public_ip_with(&mislabeled, false).is_err());
+    }
+
+    /// Fails every lookup until `failures` calls have been made, then answers.
+    struct FlakyLookup {
+        failures: u32,
+        calls: std::sync::atomic::AtomicU32,
+    }
+
+    impl PublicIpLookup for FlakyLookup {
+        fn lookup(&self, ipv6: bool) -> Result<String> {
+            let call = self.calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            if call < self.failures {
+                return Err(anyhow!("network unreachable"));
+            }
+            Ok(if ipv6 { "2001:db8::7" } else { "203.0.113.7" }.to_string())
+        }
+    }
+
+    #[test]
+    fn public_ip_lookup_retries_after_a_transient_failure() {
+        // One attempt queries both families, so two failed calls fail the first attempt.
+        let flaky = FlakyLookup {
+            failures: 2,
+            calls: Default::default(),
+        };
+        let policy = RetryPolicy {
+            base_delay_ms: 0,
+            ..public_ip_retry_policy()
+        };
+        let mut retried = Vec::new();
+        let ip = detect_public_ip_retrying(&flaky, false, &policy, &mut |attempt, _| {
+            retried.push(attempt)
+        })
+        .expect("second attempt succeeds");
+        assert_eq!(ip, "203.0.113.7");
+        assert_eq!(retried, [1]);
+
+        let down = FlakyLookup {
+            failures: u32::MAX,
+            calls: Default::default(),
+        };
+        let mut retried = 0;
+        let err = detect_public_ip_retrying(&down, false, &policy, &mut |_, _| retried += 1);
+        assert!(err.is_err());
+        assert_eq!(retried, policy.max_attempts - 1);
+    }
+
+    #[test]
+    fn parses_interval_units() {
+        assert_eq!(parse_interval("500ms").unwrap(), Duration::from_millis(500));
+        assert_eq!(parse_interval("2s").unwrap(), Duration::from_secs(2));
+        assert_eq!(parse_interval("1m").unwrap(), Duration::from_secs(60));
+        assert_eq!(parse_interval("4").unwrap(), Duration::from_secs(4));
+        assert!(parse_interval("fast").is_err());
+        assert!(parse_interval("5h").is_err());
+    }
+
+    #[test]
+    fn interval_is_floored() {
+        assert_eq!(parse_interval("10ms").unwrap(), MIN_INTERVAL);
+        let args = parse_args_from(["agent-box", "--interval", "10ms"]);
+        assert_eq!(args.interval, MIN_INTERVAL);
+    }
+
+    #[test]
+    fn parses_theme_values() {
+        for (value, expected) in [
+            ("dark", ThemeName::Dark),
+            ("light", ThemeName::Light),
+            ("mono", ThemeName::Mono),
+        ] {
+            let args = parse_args_from(["agent-box", "--theme", value]);
+            assert_eq!(args.theme, expected);
+        }
+    }
+
     #[test]
     fn parses_key_flag() {
         let args = parse_args_from(["agent-box", "--key", "my-key"]);
@@ -170,6 +1190,25 @@ mod tests {
         assert!(!parsed.generated_auth_key);
     }
 
+    #[test]
+    fn canonicalizes_ip_and_hostname() {
+        assert_eq!(canonicalize_host(" 10.0.0.12 ").unwrap(), "10.0.0.12");
+        assert_eq!(canonicalize_host("[::1]").unwrap(), "::1");
+        assert_eq!(
+            canonicalize_host("Build-Box.Example.COM").unwrap(),
+            "build-box.example.com"
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_hosts() {
+        assert!(canonicalize_host("bad host").is_err());
+        assert!(canonicalize_host("").is_err());
+        assert!(canonicalize_host("300.1.1.1").is_err());
+        assert!(canonicalize_host("-leading.example").is_err());
+        assert!(parse_peer("bad host:key", 100).is_err());
+    }
+
     #[test]
     fn peer_without_key_generates_one() {
         let parsed = parse_peer("127.0.0.1", 100).expect("valid peer");
@@ -177,5 +1216,40 @@ mod tests {
         assert_eq!(parsed.auth_key.len(), 40);
         assert!(parsed.generated_auth_key);
     }
+
+    const PUBLIC_V4: FixedLookup = FixedLookup {
+        v4: Some("203.0.113.7"),
+        v6: None,
+    };
+
+    #[test]
+    fn runtime_config_rejects_public_bind_without_key() {
+        let args = parse_args_from(["agent-box", "--public", "--no-expose"]);
+        let err = resolve_runtime_config_with(&args, &PUBLIC_V4, 100).unwrap_err();
+        assert_eq!(error_kind(&err), ErrorKind::InvalidBind);
+
+        let args = parse_args_from(["agent-box", "--public", "--no-expose", "--key", "k-123456"]);
+        let config = resolve_runtime_config_with(&args, &PUBLIC_V4, 100).expect("keyed");
+        assert_eq!(config.bind_ip, "0.0.0.0");
+        assert_eq!(config.listen_ip, "203.0.113.7");
+    }
+
+    #[test]
+    fn runtime_config_generates_a_key_for_a_bare_peer() {
+        let args = parse_args_from(["agent-box", "10.0.0.5"]);
+        let config = resolve_runtime_config_with(&args, &PUBLIC_V4, 100).expect("resolves");
+        let peer = config.peer.as_ref().expect("peer kept");
+        assert_eq!(config.peer_host(), Some("10.0.0.5"));
+        assert!(peer.generated_auth_key);
+        assert_eq!(peer.auth_key.len(), 40);
+        assert_eq!(config.session_key.as_deref(), Some(peer.auth_key.as_str()));
+        assert_eq!(config.listen_ip, "203.0.113.7");
+        assert_eq!(config.bind_ip, "0.0.0.0");
+
+        let args = parse_args_from(["agent-box", "10.0.0.5", "--key", "shared-key-1"]);
+        let config = resolve_runtime_config_with(&args, &PUBLIC_V4, 100).expect("resolves");
+        assert!(!config.peer.as_ref().unwrap().generated_auth_key);
+        assert_eq!(config.session_key.as_deref(), Some("shared-key-1"));
+    }
 }
 
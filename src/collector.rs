@@ -1,25 +1,90 @@
-use std::process::Command;
-use std::path::Path;
+use std::cmp::Reverse;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::fs::File;
+use std::hash::{Hash, Hasher};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, LazyLock, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime};
 
-use crate::model::{AgentKind, SessionEvent, SessionStatus};
+use anyhow::{anyhow, Result};
+
+use crate::agents::AgentConfig;
+use crate::model::{
+    AgentKind, Origin, SessionEvent, SessionStatus, MAX_LAST_LINES, MAX_LINE_CHARS,
+};
+use crate::sync::namespace_remote_event;
 use crate::unix_ms_now;
 
 pub trait Collector {
     fn collect(&self) -> Vec<SessionEvent>;
+
+    /// Like `collect`, but lets implementations report a broken source instead of
+    /// looking identical to "no sessions running".
+    fn try_collect(&self) -> Result<Vec<SessionEvent>> {
+        Ok(self.collect())
+    }
+
+    /// Like `try_collect`, plus how many sessions were seen and why any were dropped.
+    fn collect_with_stats(&self) -> Result<(Vec<SessionEvent>, CollectStats)> {
+        let events = self.try_collect()?;
+        let stats = CollectStats::unfiltered(events.len());
+        Ok((events, stats))
+    }
+}
+
+/// Counts from one collection pass, so a view can say how much it is not showing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CollectStats {
+    pub total_seen: usize,
+    pub returned: usize,
+    pub dropped_by_cap: usize,
+    pub dropped_by_filter: usize,
+}
+
+impl CollectStats {
+    pub fn unfiltered(count: usize) -> Self {
+        Self {
+            total_seen: count,
+            returned: count,
+            ..Self::default()
+        }
+    }
+
+    pub fn hidden(&self) -> usize {
+        self.dropped_by_cap + self.dropped_by_filter
+    }
 }
 
 #[derive(Debug, Default)]
-pub struct MockCollector;
+pub struct MockCollector {
+    demo: bool,
+}
 
 impl MockCollector {
     pub fn new() -> Self {
-        Self
+        Self::default()
+    }
+
+    /// A varied set for `--demo`: every built-in agent and status, pending approvals,
+    /// an idle session and one from a sync peer.
+    pub fn demo() -> Self {
+        Self { demo: true }
     }
 }
 
 impl Collector for MockCollector {
     fn collect(&self) -> Vec<SessionEvent> {
         let now = unix_ms_now();
+        if self.demo {
+            return demo_sessions(now);
+        }
         vec![
             SessionEvent {
                 id: "local-claude-1".to_string(),
@@ -35,6 +100,10 @@ impl Collector for MockCollector {
                     "inspecting cli parser".to_string(),
                     "preparing patch".to_string(),
                 ],
+                idle: false,
+                origin: Origin::Local,
+                branch: None,
+                project: None,
             },
             SessionEvent {
                 id: "local-gemini-1".to_string(),
@@ -47,128 +116,1055 @@ impl Collector for MockCollector {
                 started_at_unix_ms: now.saturating_sub(80_000),
                 updated_at_unix_ms: now,
                 last_lines: vec!["awaiting confirmation".to_string()],
+                idle: false,
+                origin: Origin::Local,
+                branch: None,
+                project: None,
             },
         ]
     }
 }
 
+/// Runs an external program and returns its stdout; injectable for tests.
+pub trait CommandRunner: Send + Sync {
+    fn run(&self, program: &str, args: &[&str]) -> Result<String>;
+}
+
 #[derive(Debug, Default)]
-pub struct LocalProcessCollector;
+pub struct SystemCommandRunner;
+
+impl CommandRunner for SystemCommandRunner {
+    fn run(&self, program: &str, args: &[&str]) -> Result<String> {
+        let output = Command::new(program)
+            .args(args)
+            .output()
+            .map_err(|e| anyhow!("failed to run {program}: {e}"))?;
+        if !output.status.success() {
+            return Err(anyhow!("{program} exited with {}", output.status));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+/// Which process listing source produced the last successful collection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProcessBackend {
+    Ps,
+    Proc,
+    Pgrep,
+}
+
+pub struct LocalProcessCollector {
+    runner: Arc<dyn CommandRunner>,
+    proc_root: PathBuf,
+    backend: Mutex<Option<ProcessBackend>>,
+    agents: AgentConfig,
+    self_names: Vec<String>,
+}
+
+impl std::fmt::Debug for LocalProcessCollector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LocalProcessCollector")
+            .field("proc_root", &self.proc_root)
+            .field("backend", &self.backend())
+            .finish()
+    }
+}
+
+impl Default for LocalProcessCollector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl LocalProcessCollector {
     pub fn new() -> Self {
-        Self
+        Self {
+            runner: Arc::new(SystemCommandRunner),
+            proc_root: PathBuf::from("/proc"),
+            backend: Mutex::new(None),
+            agents: AgentConfig::default(),
+            self_names: current_exe_name().into_iter().collect(),
+        }
+    }
+
+    pub fn with_runner(mut self, runner: Arc<dyn CommandRunner>) -> Self {
+        self.runner = runner;
+        self
+    }
+
+    pub fn with_proc_root(mut self, proc_root: impl Into<PathBuf>) -> Self {
+        self.proc_root = proc_root.into();
+        self
+    }
+
+    /// Extra agent kinds and detection tokens, e.g. from `agents.toml`.
+    pub fn with_agents(mut self, agents: AgentConfig) -> Self {
+        self.agents = agents;
+        self
+    }
+
+    /// Executable names treated as other copies of this monitor and never reported.
+    /// Defaults to the running binary's file name.
+    pub fn with_self_names(mut self, names: Vec<String>) -> Self {
+        self.self_names = names;
+        self
+    }
+
+    /// Backend used by the most recent successful collection, for diagnostics.
+    pub fn backend(&self) -> Option<ProcessBackend> {
+        *self.backend.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Every listed process with control characters scrubbed from its command, before
+    /// agent classification or self-exclusion. Empty when no listing backend works.
+    pub fn collect_raw(&self) -> Vec<RawProcess> {
+        let Ok((ps, backend)) = self.list_processes() else {
+            return Vec::new();
+        };
+        *self.backend.lock().unwrap_or_else(|e| e.into_inner()) = Some(backend);
+        ps.lines()
+            .filter_map(parse_ps_line)
+            .map(|mut raw| {
+                raw.command = sanitize_control_chars(&raw.command);
+                raw
+            })
+            .collect()
+    }
+
+    /// Lists processes as `pid ppid command` lines, trying `ps`, then `/proc`, then `pgrep`.
+    fn list_processes(&self) -> Result<(String, ProcessBackend)> {
+        let ps_err = match self.runner.run("ps", &["-axo", "pid=,ppid=,command="]) {
+            Ok(out) => return Ok((out, ProcessBackend::Ps)),
+            Err(err) => err,
+        };
+        let proc_err = match scan_proc_table(&self.proc_root) {
+            Ok(out) => return Ok((out, ProcessBackend::Proc)),
+            Err(err) => err,
+        };
+        let pgrep_err = match self.runner.run("pgrep", &["-fl", "."]) {
+            Ok(out) => return Ok((pgrep_to_ps_lines(&out), ProcessBackend::Pgrep)),
+            Err(err) => err,
+        };
+        Err(anyhow!(
+            "no process listing available (ps: {ps_err}; proc: {proc_err}; pgrep: {pgrep_err})"
+        ))
     }
 }
 
 impl Collector for LocalProcessCollector {
     fn collect(&self) -> Vec<SessionEvent> {
-        collect_local_process_sessions()
+        self.try_collect().unwrap_or_default()
+    }
+
+    fn try_collect(&self) -> Result<Vec<SessionEvent>> {
+        let (ps, backend) = self.list_processes()?;
+        *self.backend.lock().unwrap_or_else(|e| e.into_inner()) = Some(backend);
+        Ok(local_sessions_from_listing(
+            &ps,
+            &self.proc_root,
+            &self.agents,
+            &self.self_names,
+        ))
+    }
+}
+
+/// How long `SshCollector` waits for the remote listing before giving up on a tick.
+pub const DEFAULT_SSH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Lists agent processes on another machine by running `ps` over `ssh`, so one monitor
+/// can watch hosts that do not run agent-box. Session ids and origin are namespaced by
+/// host like synced sessions. Only the command line is known remotely: titles come from
+/// it, and the working directory is left empty.
+pub struct SshCollector {
+    host: String,
+    runner: Arc<dyn CommandRunner>,
+    timeout: Duration,
+    agents: AgentConfig,
+}
+
+impl std::fmt::Debug for SshCollector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SshCollector")
+            .field("host", &self.host)
+            .field("timeout", &self.timeout)
+            .finish()
     }
 }
 
-fn collect_local_process_sessions() -> Vec<SessionEvent> {
-    let output = match Command::new("ps").args(["-axo", "pid=,command="]).output() {
-        Ok(v) if v.status.success() => v,
-        _ => return Vec::new(),
+impl SshCollector {
+    /// `host` is anything `ssh` accepts, e.g. `build-box` or `ci@10.0.0.5`.
+    pub fn new(host: &str) -> Self {
+        Self {
+            host: host.trim().to_string(),
+            runner: Arc::new(SystemCommandRunner),
+            timeout: DEFAULT_SSH_TIMEOUT,
+            agents: AgentConfig::default(),
+        }
+    }
+
+    pub fn with_runner(mut self, runner: Arc<dyn CommandRunner>) -> Self {
+        self.runner = runner;
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn with_agents(mut self, agents: AgentConfig) -> Self {
+        self.agents = agents;
+        self
+    }
+
+    /// Runs the remote listing on a helper thread so a hung connection costs at most
+    /// `timeout`; `ssh` itself is also told to give up connecting by then.
+    fn list_processes(&self) -> Result<String> {
+        let connect_timeout = format!("ConnectTimeout={}", self.timeout.as_secs().max(1));
+        let args = [
+            "-o".to_string(),
+            "BatchMode=yes".to_string(),
+            "-o".to_string(),
+            connect_timeout,
+            self.host.clone(),
+            "ps -axo pid=,ppid=,command=".to_string(),
+        ];
+        let runner = Arc::clone(&self.runner);
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+            let _ = tx.send(runner.run("ssh", &args));
+        });
+        rx.recv_timeout(self.timeout)
+            .map_err(|_| anyhow!("ssh {} timed out after {:?}", self.host, self.timeout))?
+            .map_err(|e| anyhow!("ssh {}: {e}", self.host))
+    }
+}
+
+impl Collector for SshCollector {
+    fn collect(&self) -> Vec<SessionEvent> {
+        self.try_collect().unwrap_or_default()
+    }
+
+    fn try_collect(&self) -> Result<Vec<SessionEvent>> {
+        let ps = self.list_processes()?;
+        let (user, name) = match self.host.split_once('@') {
+            Some((user, name)) => (user.to_string(), name),
+            None => (
+                std::env::var("USER").unwrap_or_else(|_| "remote".to_string()),
+                self.host.as_str(),
+            ),
+        };
+        let now = unix_ms_now();
+        // Another monitor on that host is not an agent either; no pid of ours lives there.
+        let self_names = current_exe_name().into_iter().collect::<Vec<_>>();
+        let this = SelfProcess {
+            pid: 0,
+            names: &self_names,
+        };
+        let entries = ps.lines().filter_map(parse_ps_line).collect::<Vec<_>>();
+        let parents = entries
+            .iter()
+            .map(|entry| (entry.pid, entry.ppid))
+            .collect::<HashMap<_, _>>();
+        Ok(entries
+            .iter()
+            .filter(|entry| !this.excludes(entry, &parents))
+            .filter_map(|entry| {
+                let command = sanitize_control_chars(&entry.command);
+                let agent = detect_agent(&command, &self.agents)?;
+                let event = SessionEvent {
+                    id: format!("proc-{}", entry.pid),
+                    agent,
+                    title: truncate_keep_right(&summarize_command(&command, 48), 48),
+                    working_dir: String::new(),
+                    user: user.clone(),
+                    status: SessionStatus::Running,
+                    pending_action: None,
+                    started_at_unix_ms: now,
+                    updated_at_unix_ms: now,
+                    last_lines: vec![
+                        format!("pid={}", entry.pid),
+                        format!("cmd: {}", summarize_command(&command, 64)),
+                    ],
+                    idle: false,
+                    origin: Origin::Local,
+                    branch: None,
+                    project: None,
+                };
+                Some(namespace_remote_event(event, name))
+            })
+            .collect())
+    }
+}
+
+/// Replays a captured process listing through the same parsing and classification as
+/// `LocalProcessCollector`, so a `ps` dump attached to a bug report reproduces what the
+/// reporter saw. Takes `ps -axo pid=,ppid=,command=` or `ps -axo pid=,command=` output;
+/// nothing is read from this machine's `/proc`.
+#[derive(Debug, Clone)]
+pub struct RecordedPsCollector {
+    /// The dump as `pid ppid command` lines; parents are 0 when it had none.
+    listing: String,
+    user: String,
+    cwd: String,
+    agents: AgentConfig,
+    self_names: Vec<String>,
+}
+
+/// Never fails: lines that are not `pid [ppid] command` are skipped, as in a live listing.
+impl FromStr for RecordedPsCollector {
+    type Err = Infallible;
+
+    fn from_str(dump: &str) -> Result<Self, Self::Err> {
+        let lines = dump.lines().filter(|line| !line.trim().is_empty());
+        // A dump has parents only if every line's second column is a number.
+        let has_ppid = lines.clone().all(|line| {
+            let mut fields = line.split_whitespace();
+            fields.next().is_some_and(|pid| pid.parse::<u32>().is_ok())
+                && fields.next().is_some_and(|ppid| ppid.parse::<u32>().is_ok())
+        });
+        let listing = if has_ppid {
+            dump.to_string()
+        } else {
+            lines
+                .filter_map(|line| {
+                    let (pid, command) = line.trim().split_once(char::is_whitespace)?;
+                    Some(format!("{pid} 0 {}\n", command.trim()))
+                })
+                .collect()
+        };
+        Ok(Self {
+            listing,
+            user: "recorded".to_string(),
+            cwd: "/".to_string(),
+            agents: AgentConfig::default(),
+            self_names: vec!["agent-box".to_string()],
+        })
+    }
+}
+
+impl RecordedPsCollector {
+    /// User the sessions are reported under; defaults to `recorded`.
+    pub fn with_user(mut self, user: &str) -> Self {
+        self.user = user.to_string();
+        self
+    }
+
+    /// Working directory assumed for every process; defaults to `/`.
+    pub fn with_cwd(mut self, cwd: &str) -> Self {
+        self.cwd = cwd.to_string();
+        self
+    }
+
+    pub fn with_agents(mut self, agents: AgentConfig) -> Self {
+        self.agents = agents;
+        self
+    }
+
+    /// Monitor executables left out of the replay, like `--self-name`; defaults to
+    /// `agent-box`.
+    pub fn with_self_names(mut self, names: Vec<String>) -> Self {
+        self.self_names = names;
+        self
+    }
+}
+
+impl Collector for RecordedPsCollector {
+    fn collect(&self) -> Vec<SessionEvent> {
+        // The recording has no monitor pid of ours in it, only its executable name.
+        let this = SelfProcess {
+            pid: 0,
+            names: &self.self_names,
+        };
+        sessions_from_ps_output(
+            &self.listing,
+            &this,
+            &self.user,
+            &self.cwd,
+            unix_ms_now(),
+            None,
+            &self.agents,
+        )
+    }
+}
+
+/// Builds `pid ppid command` lines from `<root>/<pid>/{stat,cmdline}`, skipping kernel threads.
+fn scan_proc_table(root: &Path) -> Result<String> {
+    let entries = std::fs::read_dir(root)
+        .map_err(|e| anyhow!("cannot read {}: {e}", root.display()))?;
+    let mut out = String::new();
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        let Some(pid) = name.to_str().and_then(|n| n.parse::<u32>().ok()) else {
+            continue;
+        };
+        let dir = entry.path();
+        let Ok(cmdline) = std::fs::read(dir.join("cmdline")) else {
+            continue;
+        };
+        let command = cmdline
+            .split(|b| *b == 0)
+            .filter(|arg| !arg.is_empty())
+            .map(|arg| String::from_utf8_lossy(arg).into_owned())
+            .collect::<Vec<_>>()
+            .join(" ");
+        if command.is_empty() {
+            continue;
+        }
+        // stat is "pid (comm) state ppid ..."; comm may itself contain spaces or parens.
+        let ppid = std::fs::read_to_string(dir.join("stat"))
+            .ok()
+            .and_then(|stat| {
+                let rest = &stat[stat.rfind(')')? + 1..];
+                rest.split_whitespace().nth(1)?.parse::<u32>().ok()
+            })
+            .unwrap_or(0);
+        out.push_str(&format!("{pid} {ppid} {command}\n"));
+    }
+    if out.is_empty() {
+        return Err(anyhow!("no processes found under {}", root.display()));
+    }
+    Ok(out)
+}
+
+fn demo_sessions(now: u64) -> Vec<SessionEvent> {
+    let session = |id: &str, agent: AgentKind, title: &str, status: SessionStatus| {
+        SessionEvent {
+            id: id.to_string(),
+            agent,
+            title: title.to_string(),
+            working_dir: "/home/demo/src/webshop".to_string(),
+            user: "demo".to_string(),
+            status,
+            pending_action: None,
+            started_at_unix_ms: now.saturating_sub(25 * 60 * 1000),
+            updated_at_unix_ms: now,
+            last_lines: Vec::new(),
+            idle: false,
+            origin: Origin::Local,
+            branch: Some("main".to_string()),
+            project: Some("webshop".to_string()),
+        }
+    };
+
+    let mut refactor = session(
+        "demo-claude-1",
+        AgentKind::Claude,
+        "refactor checkout flow",
+        SessionStatus::Running,
+    );
+    refactor.branch = Some("feat/checkout".to_string());
+    refactor.last_lines = vec![
+        "Reading src/checkout/cart.rs".to_string(),
+        "Editing src/checkout/payment.rs".to_string(),
+    ];
+
+    let mut tests = session(
+        "demo-codex-1",
+        AgentKind::Codex,
+        "fix flaky payment tests",
+        SessionStatus::WaitingInput,
+    );
+    tests.pending_action = Some("Approve: cargo test --workspace".to_string());
+    tests.last_lines = vec!["Proposed patch touches 3 files".to_string()];
+
+    let mut docs = session(
+        "demo-gemini-1",
+        AgentKind::Gemini,
+        "write API docs",
+        SessionStatus::Success,
+    );
+    docs.last_lines = vec!["Wrote docs/api.md (412 lines)".to_string()];
+
+    let mut migrate = session(
+        "demo-copilot-1",
+        AgentKind::Copilot,
+        "migrate to postgres 16",
+        SessionStatus::Failed,
+    );
+    migrate.working_dir = "/home/demo/src/infra".to_string();
+    migrate.project = Some("infra".to_string());
+    migrate.last_lines = vec!["error: relation \"orders\" does not exist".to_string()];
+
+    let mut review = session(
+        "demo-cody-1",
+        AgentKind::Cody,
+        "review open PRs",
+        SessionStatus::Running,
+    );
+    review.idle = true;
+    review.updated_at_unix_ms = now.saturating_sub(8 * 60 * 1000);
+    review.last_lines = vec!["Waiting for CI on #218".to_string()];
+
+    let mut remote = session(
+        "remote:build-box:demo-claude-2",
+        AgentKind::Claude,
+        "bump dependencies",
+        SessionStatus::WaitingInput,
+    );
+    remote.user = "ci".to_string();
+    remote.pending_action = Some("Allow network access to crates.io".to_string());
+    remote.origin = Origin::Remote {
+        peer: "build-box".to_string(),
     };
 
+    vec![refactor, tests, docs, migrate, review, remote]
+}
+
+/// `pgrep -fl` prints `pid command`; parent pids are unknown, so report them as 0.
+fn pgrep_to_ps_lines(output: &str) -> String {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (pid, command) = line.trim().split_once(char::is_whitespace)?;
+            Some(format!("{pid} 0 {}\n", command.trim()))
+        })
+        .collect()
+}
+
+/// Default quiet period after which an unchanged session is marked idle.
+pub const DEFAULT_IDLE_THRESHOLD_MS: u64 = 120_000;
+
+/// Wraps a collector and flags sessions whose `last_lines` stop changing. It also dates
+/// `updated_at_unix_ms` back to the last change, so quiet cards can fade in the view.
+#[derive(Debug)]
+pub struct IdleTracker<C> {
+    inner: C,
+    threshold_ms: u64,
+    // session id -> (hash of last_lines, unix ms the hash last changed)
+    seen: Mutex<HashMap<String, (u64, u64)>>,
+}
+
+impl<C: Collector> IdleTracker<C> {
+    pub fn new(inner: C, threshold_ms: u64) -> Self {
+        Self {
+            inner,
+            threshold_ms,
+            seen: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn annotate(&self, events: Vec<SessionEvent>, now: u64) -> Vec<SessionEvent> {
+        let mut seen = self.seen.lock().unwrap_or_else(|e| e.into_inner());
+        let mut live = HashMap::with_capacity(events.len());
+        let events = events
+            .into_iter()
+            .map(|mut event| {
+                let hash = hash_lines(&event.last_lines);
+                let changed_at = match seen.get(&event.id) {
+                    Some(&(prev, since)) if prev == hash => since,
+                    _ => now,
+                };
+                event.idle = now.saturating_sub(changed_at) >= self.threshold_ms;
+                event.updated_at_unix_ms = event.updated_at_unix_ms.min(changed_at);
+                live.insert(event.id.clone(), (hash, changed_at));
+                event
+            })
+            .collect();
+        // Sessions that disappeared start fresh if they come back.
+        *seen = live;
+        events
+    }
+}
+
+impl<C: Collector> Collector for IdleTracker<C> {
+    fn collect(&self) -> Vec<SessionEvent> {
+        self.annotate(self.inner.collect(), unix_ms_now())
+    }
+
+    fn try_collect(&self) -> Result<Vec<SessionEvent>> {
+        Ok(self.annotate(self.inner.try_collect()?, unix_ms_now()))
+    }
+
+    fn collect_with_stats(&self) -> Result<(Vec<SessionEvent>, CollectStats)> {
+        let (events, stats) = self.inner.collect_with_stats()?;
+        Ok((self.annotate(events, unix_ms_now()), stats))
+    }
+}
+
+/// Drops sessions whose user or working directory matches an exclusion glob before
+/// they reach the store. A directory pattern also excludes everything beneath it.
+pub struct ExcludeFilter<C> {
+    inner: C,
+    users: Vec<String>,
+    dirs: Vec<String>,
+}
+
+impl<C: Collector> ExcludeFilter<C> {
+    pub fn new(inner: C) -> Self {
+        Self {
+            inner,
+            users: Vec::new(),
+            dirs: Vec::new(),
+        }
+    }
+
+    pub fn with_excluded_users(mut self, patterns: Vec<String>) -> Self {
+        self.users = patterns;
+        self
+    }
+
+    pub fn with_excluded_dirs(mut self, patterns: Vec<String>) -> Self {
+        self.dirs = patterns;
+        self
+    }
+
+    pub fn is_excluded(&self, event: &SessionEvent) -> bool {
+        if self.users.iter().any(|p| glob_match(p, &event.user)) {
+            return true;
+        }
+        Path::new(&event.working_dir).ancestors().any(|dir| {
+            let dir = dir.to_string_lossy();
+            !dir.is_empty() && self.dirs.iter().any(|p| glob_match(p.trim_end_matches('/'), &dir))
+        })
+    }
+
+    pub fn retain(&self, events: Vec<SessionEvent>) -> Vec<SessionEvent> {
+        events.into_iter().filter(|e| !self.is_excluded(e)).collect()
+    }
+}
+
+impl<C: Collector> Collector for ExcludeFilter<C> {
+    fn collect(&self) -> Vec<SessionEvent> {
+        self.retain(self.inner.collect())
+    }
+
+    fn try_collect(&self) -> Result<Vec<SessionEvent>> {
+        Ok(self.retain(self.inner.try_collect()?))
+    }
+
+    fn collect_with_stats(&self) -> Result<(Vec<SessionEvent>, CollectStats)> {
+        let (events, mut stats) = self.inner.collect_with_stats()?;
+        let kept = self.retain(events);
+        stats.dropped_by_filter += stats.returned - kept.len();
+        stats.returned = kept.len();
+        Ok((kept, stats))
+    }
+}
+
+/// Keeps at most `max_sessions` of the most recently updated sessions, in their
+/// original order.
+#[derive(Debug)]
+pub struct SessionCap<C> {
+    inner: C,
+    max_sessions: Option<usize>,
+}
+
+impl<C: Collector> SessionCap<C> {
+    pub fn new(inner: C, max_sessions: Option<usize>) -> Self {
+        Self {
+            inner,
+            max_sessions,
+        }
+    }
+
+    pub fn limit(&self, events: Vec<SessionEvent>) -> Vec<SessionEvent> {
+        let Some(max) = self.max_sessions.filter(|&max| events.len() > max) else {
+            return events;
+        };
+        let mut newest = (0..events.len()).collect::<Vec<_>>();
+        newest.sort_by_key(|&idx| Reverse(events[idx].updated_at_unix_ms));
+        let mut keep = vec![false; events.len()];
+        for &idx in newest.iter().take(max) {
+            keep[idx] = true;
+        }
+        events
+            .into_iter()
+            .zip(keep)
+            .filter_map(|(event, keep)| keep.then_some(event))
+            .collect()
+    }
+}
+
+impl<C: Collector> Collector for SessionCap<C> {
+    fn collect(&self) -> Vec<SessionEvent> {
+        self.limit(self.inner.collect())
+    }
+
+    fn try_collect(&self) -> Result<Vec<SessionEvent>> {
+        Ok(self.limit(self.inner.try_collect()?))
+    }
+
+    fn collect_with_stats(&self) -> Result<(Vec<SessionEvent>, CollectStats)> {
+        let (events, mut stats) = self.inner.collect_with_stats()?;
+        let kept = self.limit(events);
+        stats.dropped_by_cap += stats.returned - kept.len();
+        stats.returned = kept.len();
+        Ok((kept, stats))
+    }
+}
+
+/// Shell-style match: `*` is any run of characters (including `/`), `?` is one character.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let p = pattern.chars().collect::<Vec<_>>();
+    let t = text.chars().collect::<Vec<_>>();
+    let (mut pi, mut ti) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while ti < t.len() {
+        if pi < p.len() && (p[pi] == '?' || p[pi] == t[ti]) {
+            pi += 1;
+            ti += 1;
+        } else if pi < p.len() && p[pi] == '*' {
+            backtrack = Some((pi, ti));
+            pi += 1;
+        } else if let Some((star, matched)) = backtrack {
+            pi = star + 1;
+            ti = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+    p[pi..].iter().all(|&c| c == '*')
+}
+
+fn hash_lines(lines: &[String]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    lines.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn local_sessions_from_listing(
+    ps: &str,
+    proc_root: &Path,
+    agents: &AgentConfig,
+    self_names: &[String],
+) -> Vec<SessionEvent> {
     let now = unix_ms_now();
     let user = std::env::var("USER").unwrap_or_else(|_| "local".to_string());
     let cwd = std::env::current_dir()
         .ok()
         .map(|p| p.display().to_string())
         .unwrap_or_else(|| "/".to_string());
-    let ps = String::from_utf8_lossy(&output.stdout);
-    let mut sessions = Vec::new();
+    let this = SelfProcess {
+        pid: process::id(),
+        names: self_names,
+    };
+    sessions_from_ps_output(ps, &this, &user, &cwd, now, Some(proc_root), agents)
+}
 
-    for line in ps.lines() {
-        let raw = line.trim();
-        if raw.is_empty() {
-            continue;
-        }
-        let mut fields = raw.split_whitespace();
-        let pid_token = match fields.next() {
-            Some(v) => v,
-            None => continue,
-        };
-        let pid = match pid_token.parse::<u32>() {
-            Ok(v) => v,
-            Err(_) => continue,
-        };
+/// File name of the running binary, e.g. `agent-box`.
+fn current_exe_name() -> Option<String> {
+    let exe = std::env::current_exe().ok()?;
+    Some(exe.file_name()?.to_string_lossy().into_owned())
+}
 
-        let command = raw
-            .strip_prefix(pid_token)
-            .map(str::trim)
-            .unwrap_or_default()
-            .to_string();
-        if command.is_empty() {
-            continue;
+/// How the monitor recognises itself in a process listing.
+struct SelfProcess<'a> {
+    pid: u32,
+    /// Executable names of other copies of the monitor, matched against the first token.
+    names: &'a [String],
+}
+
+impl SelfProcess<'_> {
+    fn excludes(&self, entry: &RawProcess, parents: &HashMap<u32, u32>) -> bool {
+        let exe = entry.command.split_whitespace().next().unwrap_or_default();
+        is_self_or_descendant(entry.pid, self.pid, parents)
+            || self.names.iter().any(|name| is_exec_token(exe, name))
+    }
+}
+
+/// One process from the listing, before any agent classification.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawProcess {
+    pub pid: u32,
+    pub ppid: u32,
+    pub command: String,
+}
+
+fn parse_ps_line(line: &str) -> Option<RawProcess> {
+    let raw = line.trim();
+    let mut fields = raw.split_whitespace();
+    let pid_token = fields.next()?;
+    let ppid_token = fields.next()?;
+    let pid = pid_token.parse::<u32>().ok()?;
+    let ppid = ppid_token.parse::<u32>().ok()?;
+    let command = raw
+        .strip_prefix(pid_token)
+        .map(str::trim_start)
+        .and_then(|rest| rest.strip_prefix(ppid_token))
+        .map(str::trim)
+        .unwrap_or_default()
+        .to_string();
+    if command.is_empty() {
+        return None;
+    }
+    Some(RawProcess { pid, ppid, command })
+}
+
+/// Returns true when `pid` is the monitor itself or any process spawned beneath it,
+/// regardless of what the binary happens to be called.
+fn is_self_or_descendant(pid: u32, self_pid: u32, parents: &HashMap<u32, u32>) -> bool {
+    let mut current = pid;
+    // Bound the walk so a malformed parent table cannot loop forever.
+    for _ in 0..parents.len() + 1 {
+        if current == self_pid {
+            return true;
         }
-        if command.contains("agent-box") {
-            continue;
+        match parents.get(&current) {
+            Some(&parent) if parent != current && parent != 0 => current = parent,
+            _ => return false,
         }
+    }
+    false
+}
 
-        let Some(agent) = detect_agent_kind(&command) else {
+fn sessions_from_ps_output(
+    ps: &str,
+    this: &SelfProcess,
+    user: &str,
+    cwd: &str,
+    now: u64,
+    proc_root: Option<&Path>,
+    agents: &AgentConfig,
+) -> Vec<SessionEvent> {
+    let entries = ps.lines().filter_map(parse_ps_line).collect::<Vec<_>>();
+    let parents = entries
+        .iter()
+        .map(|entry| (entry.pid, entry.ppid))
+        .collect::<HashMap<_, _>>();
+    let mut sessions = Vec::new();
+
+    for entry in entries.iter() {
+        if this.excludes(entry, &parents) {
             continue;
-        };
+        }
+        sessions.extend(classify_process(entry, user, cwd, now, proc_root, agents));
+    }
+
+    sessions
+}
+
+/// Turns one process into a session when its command is a known agent CLI, using the
+/// built-in title logic. Callers with their own titling can overwrite `title` afterwards.
+pub fn classify(raw: &RawProcess) -> Option<SessionEvent> {
+    let user = std::env::var("USER").unwrap_or_else(|_| "local".to_string());
+    let cwd = std::env::current_dir()
+        .ok()
+        .map(|p| p.display().to_string())
+        .unwrap_or_else(|| "/".to_string());
+    let proc_root = Some(Path::new("/proc"));
+    classify_process(raw, &user, &cwd, unix_ms_now(), proc_root, &AgentConfig::default())
+}
 
-        let last_lines = session_logs_from_command(&command).unwrap_or_else(|| {
+fn classify_process(
+    raw: &RawProcess,
+    user: &str,
+    cwd: &str,
+    now: u64,
+    proc_root: Option<&Path>,
+    agents: &AgentConfig,
+) -> Option<SessionEvent> {
+    let pid = raw.pid;
+    let command = sanitize_control_chars(&raw.command);
+    let command = command.as_str();
+    let agent = match detect_agent(command, agents) {
+        Some(agent) => agent,
+        None if agents.shows_unknown()
+            && proc_root.is_some_and(|root| is_interactive_long_runner(root, pid, command)) =>
+        {
+            AgentKind::Unknown
+        }
+        None => return None,
+    };
+    let title = title_from_command(command, &agent, cwd, pid);
+
+    let last_lines = session_logs_from_command(command)
+        .or_else(|| proc_root.and_then(|root| tail_stdout(root, pid)))
+        .unwrap_or_else(|| {
             vec![
                 format!("pid={pid}"),
-                format!("cmd: {}", summarize_command(&command, 64)),
+                format!("cmd: {}", summarize_command(command, 64)),
                 "(no session logs)".to_string(),
             ]
         });
 
-        sessions.push(SessionEvent {
+    Some(
+        SessionEvent {
             id: format!("proc-{pid}"),
             agent,
-            title: title_from_command(&command, agent, &cwd, pid),
-            working_dir: cwd.clone(),
-            user: user.clone(),
+            title,
+            working_dir: cwd.to_string(),
+            user: user.to_string(),
             status: SessionStatus::Running,
             pending_action: None,
             started_at_unix_ms: now,
             updated_at_unix_ms: now,
             last_lines,
-        });
+            idle: false,
+            origin: Origin::Local,
+            branch: git_branch(Path::new(cwd)),
+            project: git_project(Path::new(cwd)),
+        }
+        .truncated(MAX_LAST_LINES, MAX_LINE_CHARS),
+    )
+}
+
+/// Branch checked out in the repository containing `dir`, read straight from `HEAD`
+/// (no `git` subprocess). Detached checkouts yield `detached@<short sha>`.
+pub fn git_branch(dir: &Path) -> Option<String> {
+    let git_dir = dir.ancestors().find_map(|d| git_dir_for(&d.join(".git")))?;
+    let head = std::fs::read_to_string(git_dir.join("HEAD")).ok()?;
+    let head = head.trim();
+    if let Some(reference) = head.strip_prefix("ref:") {
+        let reference = reference.trim();
+        let branch = reference.strip_prefix("refs/heads/").unwrap_or(reference);
+        return (!branch.is_empty()).then(|| branch.to_string());
     }
+    let is_sha = head.len() >= 7 && head.chars().all(|c| c.is_ascii_hexdigit());
+    is_sha.then(|| format!("detached@{}", &head[..7]))
+}
 
-    sessions
+/// Name of the repository `dir` is in: the basename of the nearest ancestor (or `dir`
+/// itself) that has a `.git` directory or worktree pointer file.
+pub fn git_project(dir: &Path) -> Option<String> {
+    let root = dir.ancestors().find(|d| d.join(".git").exists())?;
+    Some(root.file_name()?.to_string_lossy().into_owned())
+}
+
+/// Resolves `.git` to the real git directory: itself when a directory, or the
+/// `gitdir:` target when it is a worktree/submodule pointer file.
+fn git_dir_for(dot_git: &Path) -> Option<PathBuf> {
+    if dot_git.is_dir() {
+        return Some(dot_git.to_path_buf());
+    }
+    let pointer = std::fs::read_to_string(dot_git).ok()?;
+    let target = pointer.trim().strip_prefix("gitdir:")?.trim();
+    let parent = dot_git.parent()?;
+    Some(parent.join(target))
+}
+
+/// Drops `ESC [ ... <letter>` color/cursor sequences that CLIs write even to files.
+fn strip_ansi(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    let mut chars = input.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' && chars.peek() == Some(&'[') {
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Keeps argv-derived text from injecting terminal escapes or breaking the card layout:
+/// whitespace controls become spaces and every other control character is dropped.
+fn sanitize_control_chars(input: &str) -> String {
+    input
+        .chars()
+        .filter_map(|c| match c {
+            '\n' | '\r' | '\t' => Some(' '),
+            c if c.is_control() => None,
+            c => Some(c),
+        })
+        .collect()
+}
+
+/// Built-in detection patterns in priority order. A pattern is an executable name,
+/// `launcher subcommand` for tools started through another CLI (`gh copilot`), or a
+/// `/segment/` that any path in the command line may contain.
+pub(crate) const AGENT_PATTERNS: &[(AgentKind, &[&str])] = &[
+    (AgentKind::Claude, &["claude"]),
+    (AgentKind::Codex, &["codex", "openai"]),
+    (AgentKind::Gemini, &["gemini"]),
+    (AgentKind::Copilot, &["github-copilot-cli", "gh copilot"]),
+    (AgentKind::Cody, &["cody"]),
+];
+
+/// IDE-integrated agents, reported as `AgentKind::Custom` with these labels. They often
+/// run as `node <script>`, so their install paths count too. Checked after
+/// `AGENT_PATTERNS`, since a path segment is weaker evidence than an executable name.
+pub(crate) const IDE_AGENT_PATTERNS: &[(&str, &[&str])] = &[
+    ("cursor-agent", &["cursor-agent", "/cursor/", "/cursor-agent/"]),
+    ("windsurf", &["windsurf", "codeium", "/windsurf/", "/codeium/"]),
+];
+
+/// Operator-configured tokens win: `aider --model claude` is an aider session.
+fn detect_agent(command: &str, agents: &AgentConfig) -> Option<AgentKind> {
+    agents.detect(command).or_else(|| detect_agent_kind(command))
 }
 
 fn detect_agent_kind(command: &str) -> Option<AgentKind> {
     let lower = command.to_lowercase();
-    if contains_exec_token(&lower, "claude") {
-        return Some(AgentKind::Claude);
-    }
-    if contains_exec_token(&lower, "codex") || contains_exec_token(&lower, "openai") {
-        return Some(AgentKind::Codex);
+    let matches = |patterns: &[&str]| patterns.iter().any(|p| matches_pattern(&lower, p));
+    AGENT_PATTERNS
+        .iter()
+        .find(|(_, patterns)| matches(patterns))
+        .map(|(kind, _)| kind.clone())
+        .or_else(|| {
+            IDE_AGENT_PATTERNS
+                .iter()
+                .find(|(_, patterns)| matches(patterns))
+                .map(|(label, _)| AgentKind::Custom(label.to_string()))
+        })
+}
+
+fn matches_pattern(command: &str, pattern: &str) -> bool {
+    if pattern.len() > 2 && pattern.starts_with('/') && pattern.ends_with('/') {
+        return command.split_whitespace().any(|token| token.contains(pattern));
     }
-    if contains_exec_token(&lower, "gemini") {
-        return Some(AgentKind::Gemini);
+    match pattern.split_once(' ') {
+        Some((launcher, subcommand)) => contains_exec_sequence(command, launcher, subcommand),
+        None => contains_exec_token(command, pattern),
     }
-    None
 }
 
-fn contains_exec_token(command: &str, needle: &str) -> bool {
+fn is_exec_token(token: &str, needle: &str) -> bool {
+    token == needle || token.ends_with(&format!("/{needle}"))
+}
+
+pub(crate) fn contains_exec_token(command: &str, needle: &str) -> bool {
     command
         .split_whitespace()
-        .any(|token| token == needle || token.ends_with(&format!("/{needle}")))
+        .any(|token| is_exec_token(token, needle))
 }
 
-fn title_from_command(command: &str, agent: AgentKind, cwd: &str, pid: u32) -> String {
-    let title = match agent {
-        AgentKind::Claude => claude_title_from_command(command, cwd, pid)
-            .unwrap_or_else(|| summarize_command(command, 48)),
-        AgentKind::Codex => codex_title_from_command(command, cwd, pid)
-            .unwrap_or_else(|| summarize_command(command, 48)),
-        _ => summarize_command(command, 48),
-    };
+/// Matches a launcher followed directly by a subcommand, e.g. `gh copilot`.
+fn contains_exec_sequence(command: &str, launcher: &str, subcommand: &str) -> bool {
+    let tokens = command.split_whitespace().collect::<Vec<_>>();
+    tokens
+        .windows(2)
+        .any(|pair| is_exec_token(pair[0], launcher) && pair[1] == subcommand)
+}
+
+fn title_from_command(command: &str, agent: &AgentKind, cwd: &str, pid: u32) -> String {
+    let title = session_hint_title(command, agent)
+        .or_else(|| match agent {
+            AgentKind::Claude => claude_title_from_command(command, cwd, pid),
+            AgentKind::Codex => codex_title_from_command(command, cwd, pid),
+            _ => None,
+        })
+        .unwrap_or_else(|| summarize_command(command, 48));
     truncate_keep_right(&title, 48)
 }
 
+/// Title from a session/transcript file named on the command line, for any agent. Unknown
+/// agents are labeled with their executable name rather than `unknown`.
+fn session_hint_title(command: &str, agent: &AgentKind) -> Option<String> {
+    let tokens = command
+        .split_whitespace()
+        .map(normalize_token)
+        .filter(|t| !t.is_empty())
+        .collect::<Vec<_>>();
+    let path = find_session_path_hint(&tokens)?;
+    let title = read_title_from_session_file(&path).or_else(|| summarize_session_path(&path))?;
+    let label = match agent {
+        AgentKind::Unknown => {
+            let exe = tokens.first()?;
+            exe.rsplit('/').next().unwrap_or(exe)
+        }
+        agent => agent.as_label(),
+    };
+    Some(format!("{label} {title}"))
+}
+
 fn process_cwd(pid: u32) -> Option<String> {
     #[cfg(target_os = "linux")]
     {
@@ -189,15 +1185,6 @@ fn claude_title_from_command(command: &str, cwd: &str, pid: u32) -> Option<Strin
         .filter(|t| !t.is_empty())
         .collect::<Vec<_>>();
 
-    if let Some(path) = find_session_path_hint(&tokens) {
-        if let Some(title) = read_title_from_session_file(&path) {
-            return Some(format!("claude {title}"));
-        }
-        if let Some(hint) = summarize_session_path(&path) {
-            return Some(format!("claude {hint}"));
-        }
-    }
-
     if let Some(title) = title_from_claude_projects(pid) {
         return Some(format!("claude {title}"));
     }
@@ -243,7 +1230,7 @@ fn title_from_claude_projects(pid: u32) -> Option<String> {
         }
         for entry in std::fs::read_dir(&sessions_dir).ok()?.flatten() {
             let path = entry.path();
-            if path.extension().map_or(true, |e| e != "jsonl") {
+            if path.extension().is_none_or(|e| e != "jsonl") {
                 continue;
             }
             let meta = std::fs::metadata(&path).ok()?;
@@ -251,20 +1238,69 @@ fn title_from_claude_projects(pid: u32) -> Option<String> {
             let title = read_title_from_session_file(path.to_str()?)
                 .or_else(|| project_dir.file_name().to_str().map(String::from));
             if let Some(t) = title {
-                if best.as_ref().map_or(true, |(m, _)| modified > *m) {
+                if best.as_ref().is_none_or(|(m, _)| modified > *m) {
                     best = Some((modified, t));
                 }
             }
         }
-        if process_cwd.is_some() && best.is_some() {
-            break;
-        }
+        if process_cwd.is_some() && best.is_some() {
+            break;
+        }
+    }
+    best.map(|(_, t)| t)
+}
+
+/// Session/transcript files agent-box would consider for sessions started in `cwd`,
+/// newest first: project-local `.claude`/`.cursor`/`.happy` directories plus the
+/// matching `~/.claude/projects/<encoded cwd>` directory.
+pub fn find_session_files(cwd: &Path) -> Vec<PathBuf> {
+    let home = std::env::var("HOME").ok().map(PathBuf::from);
+    find_session_files_in(cwd, home.as_deref())
+}
+
+fn find_session_files_in(cwd: &Path, home: Option<&Path>) -> Vec<PathBuf> {
+    let mut roots = [".claude", ".cursor", ".happy"]
+        .iter()
+        .map(|dir| cwd.join(dir))
+        .collect::<Vec<_>>();
+    if let Some(home) = home {
+        let encoded = url_encode_path(&cwd.to_string_lossy());
+        roots.push(home.join(".claude").join("projects").join(encoded));
+    }
+
+    let mut files = roots
+        .iter()
+        .flat_map(|root| walk_session_files(root))
+        .filter_map(|path| {
+            let modified = std::fs::metadata(&path).ok()?.modified().ok()?;
+            Some((modified, path))
+        })
+        .collect::<Vec<_>>();
+    files.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+    files.dedup_by(|a, b| a.1 == b.1);
+    files.into_iter().map(|(_, path)| path).collect()
+}
+
+fn walk_session_files(dir: &Path) -> Vec<PathBuf> {
+    let mut out = Vec::new();
+    if let Ok(entries) = std::fs::read_dir(dir) {
+        for e in entries.flatten() {
+            let path = e.path();
+            if path.is_dir() {
+                out.extend(walk_session_files(&path));
+            } else if path
+                .extension()
+                .is_some_and(|e| e == "jsonl" || e == "json")
+            {
+                out.push(path);
+            }
+        }
     }
-    best.map(|(_, t)| t)
+    out
 }
 
 fn url_encode_path(path: &str) -> String {
-    path.trim().replace('/', "-").replace(' ', "-")
+    path.trim().replace(['/', ' '], "-")
 }
 
 fn codex_title_from_command(_command: &str, cwd: &str, pid: u32) -> Option<String> {
@@ -293,7 +1329,7 @@ fn title_from_codex_sessions(pid: u32) -> Option<String> {
         let title = read_title_from_session_file(path.to_str()?)
             .or_else(|| path.file_stem().and_then(|s: &std::ffi::OsStr| s.to_str()).map(String::from));
         if let Some(t) = title {
-            if best.as_ref().map_or(true, |(m, _)| modified > *m) {
+            if best.as_ref().is_none_or(|(m, _)| modified > *m) {
                 best = Some((modified, t));
             }
         }
@@ -308,7 +1344,7 @@ fn walkdir_jsonl(dir: &Path) -> Vec<std::path::PathBuf> {
             let path = e.path();
             if path.is_dir() {
                 out.extend(walkdir_jsonl(&path));
-            } else if path.extension().map_or(false, |e| e == "jsonl") {
+            } else if path.extension().is_some_and(|e| e == "jsonl") {
                 out.push(path);
             }
         }
@@ -334,6 +1370,7 @@ fn find_session_path_hint(tokens: &[String]) -> Option<String> {
                     || lower.contains("transcript")
                     || lower.contains(".cursor")
                     || lower.contains(".claude")
+                    || lower.contains(".codex")
                     || lower.contains(".happy"))
         })
         .cloned()
@@ -356,7 +1393,10 @@ fn session_logs_from_command(command: &str) -> Option<Vec<String>> {
 }
 
 fn extract_session_logs_from_file(path: &str) -> Option<Vec<String>> {
-    let p = Path::new(path);
+    TRANSCRIPTS.logs(Path::new(path), parse_session_logs_from_file)
+}
+
+fn parse_session_logs_from_file(p: &Path) -> Option<Vec<String>> {
     if !p.exists() || !p.is_file() {
         return None;
     }
@@ -369,7 +1409,7 @@ fn extract_session_logs_from_file(path: &str) -> Option<Vec<String>> {
             continue;
         }
         if let Some(text) = extract_content_from_jsonl_line(trimmed) {
-            let truncated = truncate_for_display(&text, 56);
+            let truncated = truncate_for_display(&sanitize_control_chars(&text), 56);
             if !truncated.is_empty() && !out.contains(&truncated) {
                 out.push(truncated);
                 if out.len() >= 2 {
@@ -439,13 +1479,184 @@ fn truncate_for_display(input: &str, limit: usize) -> String {
     out
 }
 
+/// How much of a redirected stdout file is read from the end for `last_lines`.
+const STDOUT_TAIL_BYTES: u64 = 16 * 1024;
+
+/// How long an unmatched process must have been running to count for `--show-unknown`.
+const UNKNOWN_MIN_AGE_SECS: f64 = 60.0;
+
+/// Executables that hold a terminal without being a task worth a card of their own.
+const TERMINAL_PLUMBING: &[&str] = &[
+    "sh", "bash", "zsh", "fish", "dash", "ksh", "tcsh", "csh", "nu", "tmux", "screen",
+    "login", "sudo", "su", "ssh", "mosh-server", "agetty", "getty",
+];
+
+/// The `--show-unknown` heuristic: stdin is a terminal, the executable is not a shell or
+/// multiplexer, and the process has been up for at least `UNKNOWN_MIN_AGE_SECS`.
+fn is_interactive_long_runner(proc_root: &Path, pid: u32, command: &str) -> bool {
+    let exe = command.split_whitespace().next().unwrap_or_default();
+    let exe = exe.rsplit('/').next().unwrap_or(exe).trim_start_matches('-');
+    if exe.is_empty() || TERMINAL_PLUMBING.contains(&exe) {
+        return false;
+    }
+    let dir = proc_root.join(pid.to_string());
+    let on_tty = std::fs::read_link(dir.join("fd").join("0")).is_ok_and(|target| {
+        let target = target.to_string_lossy();
+        target.starts_with("/dev/pts/") || target.starts_with("/dev/tty")
+    });
+    on_tty && process_age_secs(proc_root, &dir).is_some_and(|age| age >= UNKNOWN_MIN_AGE_SECS)
+}
+
+/// Seconds since the process started: `<root>/uptime` minus `starttime` from its stat.
+fn process_age_secs(proc_root: &Path, dir: &Path) -> Option<f64> {
+    let uptime = std::fs::read_to_string(proc_root.join("uptime")).ok()?;
+    let uptime = uptime.split_whitespace().next()?.parse::<f64>().ok()?;
+    let stat = std::fs::read_to_string(dir.join("stat")).ok()?;
+    // Fields after "pid (comm)" start at 3 (state); starttime is field 22.
+    let rest = &stat[stat.rfind(')')? + 1..];
+    let start_ticks = rest.split_whitespace().nth(19)?.parse::<f64>().ok()?;
+    Some(uptime - start_ticks / clock_ticks_per_sec())
+}
+
+#[cfg(unix)]
+fn clock_ticks_per_sec() -> f64 {
+    // SAFETY: sysconf only reads a configuration value.
+    match unsafe { libc::sysconf(libc::_SC_CLK_TCK) } {
+        ticks if ticks > 0 => ticks as f64,
+        _ => 100.0,
+    }
+}
+
+#[cfg(not(unix))]
+fn clock_ticks_per_sec() -> f64 {
+    100.0
+}
+
+/// Last two non-empty lines the process wrote to stdout, when `<proc_root>/<pid>/fd/1`
+/// resolves to a regular file (e.g. `agent > run.log`). Pipes, ttys and sockets are
+/// never opened, so a live pipe cannot block collection.
+fn tail_stdout(proc_root: &Path, pid: u32) -> Option<Vec<String>> {
+    let fd = proc_root.join(pid.to_string()).join("fd").join("1");
+    if !std::fs::metadata(&fd).ok()?.is_file() {
+        return None;
+    }
+    let mut file = File::open(&fd).ok()?;
+    let len = file.metadata().ok()?.len();
+    let tail = read_chunk(&mut file, len.saturating_sub(STDOUT_TAIL_BYTES), STDOUT_TAIL_BYTES)?;
+    let mut out = Vec::with_capacity(2);
+    for line in tail.lines().rev() {
+        let line = truncate_for_display(&sanitize_control_chars(&strip_ansi(line)), 56);
+        if !line.is_empty() {
+            out.push(line);
+            if out.len() >= 2 {
+                break;
+            }
+        }
+    }
+    out.reverse();
+    (!out.is_empty()).then_some(out)
+}
+
+/// Transcript parses shared by every collector, so each tick only re-reads files that
+/// changed since the last one.
+static TRANSCRIPTS: LazyLock<TranscriptCache> = LazyLock::new(TranscriptCache::default);
+
+/// Past this many files the cache starts over, so deleted transcripts don't pile up.
+const MAX_CACHED_TRANSCRIPTS: usize = 4_096;
+
+/// Titles and last lines parsed from transcript files, reused while a file's size and
+/// mtime are unchanged. A file that grows or is rewritten is parsed again.
+#[derive(Debug, Default)]
+struct TranscriptCache {
+    entries: Mutex<HashMap<PathBuf, CachedTranscript>>,
+    parses: AtomicUsize,
+}
+
+#[derive(Debug)]
+struct CachedTranscript {
+    size: u64,
+    modified: Option<SystemTime>,
+    title: Option<Option<String>>,
+    logs: Option<Option<Vec<String>>>,
+}
+
+impl TranscriptCache {
+    fn title(&self, path: &Path, parse: fn(&Path) -> Option<String>) -> Option<String> {
+        self.cached(path, |entry| &mut entry.title, parse)
+    }
+
+    fn logs(&self, path: &Path, parse: fn(&Path) -> Option<Vec<String>>) -> Option<Vec<String>> {
+        self.cached(path, |entry| &mut entry.logs, parse)
+    }
+
+    fn cached<T: Clone>(
+        &self,
+        path: &Path,
+        slot: fn(&mut CachedTranscript) -> &mut Option<Option<T>>,
+        parse: fn(&Path) -> Option<T>,
+    ) -> Option<T> {
+        let meta = std::fs::metadata(path).ok().filter(|meta| meta.is_file())?;
+        let (size, modified) = (meta.len(), meta.modified().ok());
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let fresh = entries
+            .get(path)
+            .is_some_and(|entry| entry.size == size && entry.modified == modified);
+        if !fresh {
+            if entries.len() >= MAX_CACHED_TRANSCRIPTS {
+                entries.clear();
+            }
+            entries.insert(
+                path.to_path_buf(),
+                CachedTranscript {
+                    size,
+                    modified,
+                    title: None,
+                    logs: None,
+                },
+            );
+        }
+        let entry = entries.get_mut(path)?;
+        if let Some(parsed) = slot(entry) {
+            return parsed.clone();
+        }
+        self.parses.fetch_add(1, Ordering::Relaxed);
+        let parsed = parse(path);
+        *slot(entry) = Some(parsed.clone());
+        parsed
+    }
+}
+
+/// How much of a session file's head and tail is scanned for a title.
+const TITLE_SCAN_BYTES: u64 = 64 * 1024;
+
 fn read_title_from_session_file(path: &str) -> Option<String> {
-    let p = Path::new(path);
+    TRANSCRIPTS.title(Path::new(path), parse_title_from_session_file)
+}
+
+fn parse_title_from_session_file(p: &Path) -> Option<String> {
     if !p.exists() || !p.is_file() {
         return None;
     }
-    let content = std::fs::read_to_string(p).ok()?;
-    extract_json_title(&content)
+    // Transcripts can grow to hundreds of MB; titles live at the head or the tail.
+    let mut file = File::open(p).ok()?;
+    let len = file.metadata().ok()?.len();
+    let head = read_chunk(&mut file, 0, TITLE_SCAN_BYTES)?;
+    if let Some(title) = extract_json_title(&head) {
+        return Some(title);
+    }
+    if len <= TITLE_SCAN_BYTES {
+        return None;
+    }
+    let tail_start = len.saturating_sub(TITLE_SCAN_BYTES).max(TITLE_SCAN_BYTES);
+    let tail = read_chunk(&mut file, tail_start, TITLE_SCAN_BYTES)?;
+    extract_json_title(&tail)
+}
+
+fn read_chunk(file: &mut File, offset: u64, limit: u64) -> Option<String> {
+    file.seek(SeekFrom::Start(offset)).ok()?;
+    let mut buf = Vec::new();
+    file.by_ref().take(limit).read_to_end(&mut buf).ok()?;
+    Some(String::from_utf8_lossy(&buf).into_owned())
 }
 
 fn extract_json_title(content: &str) -> Option<String> {
@@ -539,13 +1750,159 @@ fn truncate_keep_right(input: &str, limit: usize) -> String {
 #[cfg(test)]
 mod tests {
     use std::fs;
-    use std::time::{SystemTime, UNIX_EPOCH};
+    use std::path::PathBuf;
+    use std::str::FromStr;
+    use std::sync::Arc;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
     use super::{
-        claude_title_from_command, detect_agent_kind, extract_json_title, summarize_command,
-        title_from_command,
+        classify, detect_agent_kind, extract_json_title, find_session_files_in, git_branch,
+        git_project, glob_match, summarize_command, tail_stdout, title_from_command, CollectStats,
+        Collector, CommandRunner, ExcludeFilter, IdleTracker, LocalProcessCollector, MockCollector,
+        ProcessBackend, RecordedPsCollector, SessionCap, SshCollector,
     };
-    use crate::model::AgentKind;
+    use crate::agents::AgentConfig;
+    use crate::model::{AgentKind, SessionEvent};
+
+    /// A monitor that appears nowhere in the test listings.
+    const NOT_SELF: super::SelfProcess<'static> = super::SelfProcess {
+        pid: 99_999,
+        names: &[],
+    };
+
+    struct FailingRunner;
+
+    struct StaticRunner(&'static str);
+
+    impl CommandRunner for StaticRunner {
+        fn run(&self, _program: &str, _args: &[&str]) -> anyhow::Result<String> {
+            Ok(self.0.to_string())
+        }
+    }
+
+    struct FixedCollector(Vec<SessionEvent>);
+
+    impl Collector for FixedCollector {
+        fn collect(&self) -> Vec<SessionEvent> {
+            self.0.clone()
+        }
+    }
+
+    impl CommandRunner for FailingRunner {
+        fn run(&self, program: &str, _args: &[&str]) -> anyhow::Result<String> {
+            Err(anyhow::anyhow!("{program} unavailable"))
+        }
+    }
+
+    /// Records the `ssh` invocation and answers with a canned remote `ps` listing.
+    struct SshRunner(std::sync::Mutex<Vec<String>>);
+
+    impl CommandRunner for SshRunner {
+        fn run(&self, program: &str, args: &[&str]) -> anyhow::Result<String> {
+            let mut calls = self.0.lock().expect("calls lock");
+            calls.push(format!("{program} {}", args.join(" ")));
+            Ok("  10     1 /usr/lib/systemd/systemd --user\n\
+                3131     1 /home/ci/.local/bin/claude --resume\n\
+                3200  3131 codex exec fix-tests\n"
+                .to_string())
+        }
+    }
+
+    struct HangingRunner;
+
+    impl CommandRunner for HangingRunner {
+        fn run(&self, _program: &str, _args: &[&str]) -> anyhow::Result<String> {
+            std::thread::sleep(Duration::from_secs(2));
+            Ok(String::new())
+        }
+    }
+
+    /// A fresh path under the system temp dir, unique per call.
+    fn temp_path(prefix: &str) -> PathBuf {
+        let unique = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("clock")
+            .as_nanos();
+        std::env::temp_dir().join(format!("{prefix}-{unique}"))
+    }
+
+    #[test]
+    fn ssh_collector_namespaces_remote_sessions_by_host() {
+        let runner = Arc::new(SshRunner(Default::default()));
+        let collector = SshCollector::new("ci@build-box").with_runner(runner.clone());
+        let sessions = collector.try_collect().expect("canned listing");
+
+        let calls = runner.0.lock().expect("calls lock");
+        assert_eq!(calls.len(), 1);
+        assert!(calls[0].starts_with("ssh -o BatchMode=yes -o ConnectTimeout=10 ci@build-box "));
+        assert!(calls[0].ends_with("ps -axo pid=,ppid=,command="));
+
+        let summary = sessions
+            .iter()
+            .map(|s| (s.id.as_str(), s.agent.clone(), s.user.as_str(), s.origin.peer()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            [
+                ("remote:build-box:proc-3131", AgentKind::Claude, "ci", Some("build-box")),
+                ("remote:build-box:proc-3200", AgentKind::Codex, "ci", Some("build-box")),
+            ]
+        );
+        assert!(sessions.iter().all(|s| s.working_dir.is_empty() && s.branch.is_none()));
+    }
+
+    #[test]
+    fn recorded_ps_dump_replays_through_the_collector_pipeline() {
+        // Trimmed from a real `ps -axo pid=,command=` on a dev laptop.
+        let dump = "    1 /sbin/launchd
+  412 /usr/libexec/logd
+ 1803 /Applications/iTerm.app/Contents/MacOS/iTerm2
+ 1950 -zsh
+ 2011 node /opt/homebrew/bin/claude --resume
+ 2012 /opt/homebrew/bin/codex --sandbox workspace-write
+ 2013 vim src/main.rs
+ 2014 /Users/dev/.cargo/bin/agent-box --peer 10.0.0.5:abc
+ 2015 gh copilot suggest \"undo last commit\"
+";
+        let sessions = RecordedPsCollector::from_str(dump)
+            .expect("any dump parses")
+            .with_user("dev")
+            .with_cwd("/Users/dev/shop")
+            .collect();
+        let found = sessions
+            .iter()
+            .map(|s| (s.id.as_str(), s.agent.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            found,
+            [
+                ("proc-2011", AgentKind::Claude),
+                ("proc-2012", AgentKind::Codex),
+                ("proc-2015", AgentKind::Copilot),
+            ]
+        );
+        assert!(sessions.iter().all(|s| s.user == "dev" && s.working_dir == "/Users/dev/shop"));
+
+        // A dump with parents gives the same sessions.
+        let with_ppid = "2011 1950 node /opt/homebrew/bin/claude --resume\n2013 1950 vim x\n";
+        let ids = with_ppid
+            .parse::<RecordedPsCollector>()
+            .expect("any dump parses")
+            .collect()
+            .into_iter()
+            .map(|s| s.id)
+            .collect::<Vec<_>>();
+        assert_eq!(ids, ["proc-2011"]);
+    }
+
+    #[test]
+    fn ssh_collector_gives_up_after_its_timeout() {
+        let collector = SshCollector::new("build-box")
+            .with_runner(Arc::new(HangingRunner))
+            .with_timeout(Duration::from_millis(50));
+        let err = collector.try_collect().expect_err("hung ssh");
+        assert!(err.to_string().contains("timed out"), "{err}");
+    }
 
     #[test]
     fn detects_known_agent_processes() {
@@ -558,11 +1915,46 @@ mod tests {
         assert_eq!(detect_agent_kind("bash -lc ls"), None);
     }
 
+    #[test]
+    fn detects_copilot_and_cody() {
+        assert_eq!(
+            detect_agent_kind("gh copilot suggest 'list files'"),
+            Some(AgentKind::Copilot)
+        );
+        assert_eq!(
+            detect_agent_kind("/usr/bin/github-copilot-cli what-the-shell"),
+            Some(AgentKind::Copilot)
+        );
+        assert_eq!(detect_agent_kind("cody chat -m hi"), Some(AgentKind::Cody));
+        assert_eq!(detect_agent_kind("gh pr list"), None);
+    }
+
+    #[test]
+    fn detects_ide_agents_by_executable_or_install_path() {
+        let cursor = Some(AgentKind::Custom("cursor-agent".to_string()));
+        let windsurf = Some(AgentKind::Custom("windsurf".to_string()));
+        assert_eq!(detect_agent_kind("/home/u/.local/bin/cursor-agent -p 'fix ci'"), cursor);
+        assert_eq!(
+            detect_agent_kind("node /opt/cursor/resources/app/out/agent/index.js --stdio"),
+            cursor
+        );
+        assert_eq!(
+            detect_agent_kind("/home/u/.codeium/windsurf/bin/language_server_linux_x64 --api"),
+            windsurf
+        );
+        assert_eq!(detect_agent_kind("/usr/share/codeium/codeium --port 42100"), windsurf);
+        // A known CLI launched from inside the IDE is still that CLI.
+        assert_eq!(detect_agent_kind("/opt/cursor/bin/claude"), Some(AgentKind::Claude));
+        // Only whole path segments count, not a substring of a name.
+        assert_eq!(detect_agent_kind("vim /src/cursorline.vim"), None);
+        assert_eq!(detect_agent_kind("less notes/cursor.md"), None);
+    }
+
     #[test]
     fn title_is_truncated() {
         let title = title_from_command(
             "/opt/tools/claude this is a very very very very very very long command string with extra tail",
-            AgentKind::Claude,
+            &AgentKind::Claude,
             "/tmp/project",
             12345,
         );
@@ -588,30 +1980,140 @@ mod tests {
 
     #[test]
     fn claude_title_reads_session_file_when_present() {
-        let unique = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .expect("clock")
-            .as_nanos();
-        let dir = std::env::temp_dir().join(format!("agent-box-title-{unique}"));
+        let dir = temp_path("agent-box-title");
         fs::create_dir_all(&dir).expect("create temp dir");
         let path = dir.join("session.json");
         fs::write(&path, r#"{"title":"Bug bash - login flow"}"#).expect("write file");
 
         let cmd = format!("claude --session {}", path.display());
-        let title = claude_title_from_command(&cmd, "/tmp/project", 12345).expect("title");
+        let title = title_from_command(&cmd, &AgentKind::Claude, "/tmp/project", 12345);
         assert!(title.contains("Bug bash - login flow"));
 
         let _ = fs::remove_file(&path);
         let _ = fs::remove_dir(&dir);
     }
 
+    #[test]
+    fn codex_and_unknown_titles_read_session_file_when_present() {
+        let dir = temp_path("agent-box-codex-title");
+        fs::create_dir_all(&dir).expect("create temp dir");
+        let path = dir.join("rollout-session.jsonl");
+        fs::write(&path, "{\"title\":\"Port the parser\"}\n").expect("write file");
+
+        let cmd = format!("codex resume {}", path.display());
+        let title = title_from_command(&cmd, &AgentKind::Codex, "/tmp/project", 12345);
+        assert_eq!(title, "codex Port the parser");
+
+        let cmd = format!("/opt/bin/my-agent --transcript {}", path.display());
+        let title = title_from_command(&cmd, &AgentKind::Unknown, "/tmp/project", 12345);
+        assert_eq!(title, "my-agent Port the parser");
+
+        let _ = fs::remove_file(&path);
+        let _ = fs::remove_dir(&dir);
+    }
+
+    #[test]
+    fn transcript_cache_reparses_only_changed_files() {
+        use std::sync::atomic::Ordering;
+
+        let path = temp_path("agent-box-cache").with_extension("jsonl");
+        fs::write(&path, "{\"title\":\"first pass\",\"content\":\"hello\"}\n").expect("write");
+        let cache = super::TranscriptCache::default();
+        let title = |cache: &super::TranscriptCache| {
+            cache.title(&path, super::parse_title_from_session_file)
+        };
+
+        assert_eq!(title(&cache).as_deref(), Some("first pass"));
+        assert_eq!(title(&cache).as_deref(), Some("first pass"));
+        assert_eq!(cache.parses.load(Ordering::Relaxed), 1);
+
+        let logs = cache.logs(&path, super::parse_session_logs_from_file);
+        assert_eq!(logs, Some(vec!["hello".to_string()]));
+        cache.logs(&path, super::parse_session_logs_from_file);
+        assert_eq!(cache.parses.load(Ordering::Relaxed), 2);
+
+        let mut file = fs::OpenOptions::new().append(true).open(&path).expect("open");
+        std::io::Write::write_all(&mut file, b"{\"content\":\"world\"}\n").expect("append");
+        drop(file);
+        assert_eq!(title(&cache).as_deref(), Some("first pass"));
+        let logs = cache.logs(&path, super::parse_session_logs_from_file);
+        assert_eq!(logs, Some(vec!["hello".to_string(), "world".to_string()]));
+        assert_eq!(cache.parses.load(Ordering::Relaxed), 4);
+
+        let _ = fs::remove_file(&path);
+        assert_eq!(title(&cache), None);
+    }
+
+    #[test]
+    fn session_files_are_listed_newest_first() {
+        let root = temp_path("agent-box-find");
+        let cwd = root.join("repo");
+        let home = root.join("home");
+        let projects = home
+            .join(".claude")
+            .join("projects")
+            .join(cwd.to_string_lossy().replace(['/', ' '], "-"))
+            .join("sessions");
+        for dir in [
+            cwd.join(".claude"),
+            cwd.join(".cursor").join("chats"),
+            cwd.join(".happy"),
+            projects.clone(),
+        ] {
+            fs::create_dir_all(dir).expect("create temp dir");
+        }
+        let base = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let files = [
+            (cwd.join(".claude").join("old.jsonl"), 10),
+            (cwd.join(".cursor").join("chats").join("mid.json"), 20),
+            (cwd.join(".happy").join("notes.txt"), 40),
+            (projects.join("newest.jsonl"), 30),
+        ];
+        for (path, offset) in &files {
+            fs::write(path, "{}").expect("write file");
+            fs::File::options()
+                .write(true)
+                .open(path)
+                .and_then(|f| f.set_modified(base + Duration::from_secs(*offset)))
+                .expect("set mtime");
+        }
+
+        let found = find_session_files_in(&cwd, Some(&home));
+        assert_eq!(
+            found,
+            vec![files[3].0.clone(), files[1].0.clone(), files[0].0.clone()]
+        );
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn title_near_end_of_large_file_is_found_without_reading_middle() {
+        let dir = temp_path("agent-box-big");
+        fs::create_dir_all(&dir).expect("create temp dir");
+        let path = dir.join("session.jsonl");
+
+        let filler = format!(
+            "{{\"type\":\"assistant\",\"content\":\"{}\"}}\n",
+            "x".repeat(200)
+        );
+        let mut content = filler.repeat(1_000);
+        // A decoy in the middle proves the middle is skipped: a full read would find it first.
+        content.push_str("{\"title\":\"middle decoy\"}\n");
+        content.push_str(&filler.repeat(1_000));
+        content.push_str("{\"title\":\"Tail title\"}\n");
+        fs::write(&path, &content).expect("write file");
+        assert!(content.len() > 4 * super::TITLE_SCAN_BYTES as usize);
+
+        let title = super::read_title_from_session_file(path.to_str().expect("utf8 path"));
+        assert_eq!(title.as_deref(), Some("Tail title"));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
     #[test]
     fn session_logs_extracted_from_jsonl_when_present() {
-        let unique = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .expect("clock")
-            .as_nanos();
-        let dir = std::env::temp_dir().join(format!("agent-box-logs-{unique}"));
+        let dir = temp_path("agent-box-logs");
         fs::create_dir_all(&dir).expect("create temp dir");
         let path = dir.join("session.jsonl");
         let content = r#"{"type":"user","content":"fix the bug"}
@@ -629,6 +2131,365 @@ mod tests {
         let _ = fs::remove_dir(&dir);
     }
 
+    #[test]
+    fn excludes_own_process_tree_without_name_match() {
+        let self_pid = std::process::id();
+        let ps = format!(
+            "{self_pid} 1 /usr/local/bin/ab claude\n\
+             {child} {self_pid} /bin/sh -c claude --version\n\
+             4242 1 claude\n",
+            child = self_pid.wrapping_add(1),
+        );
+        let this = super::SelfProcess {
+            pid: self_pid,
+            names: &[],
+        };
+        let sessions = super::sessions_from_ps_output(
+            &ps,
+            &this,
+            "alice",
+            "/tmp/project",
+            10,
+            None,
+            &AgentConfig::default(),
+        );
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].id, "proc-4242");
+    }
+
+    #[test]
+    fn self_names_match_the_executable_not_any_substring() {
+        let ps = "100 1 /opt/tools/monitor claude\n\
+                  101 1 /usr/local/bin/agent-box claude\n\
+                  102 1 /home/u/agent-box/bin/claude\n";
+        let ids = |names: &[String]| {
+            let this = super::SelfProcess { pid: 99_999, names };
+            super::sessions_from_ps_output(
+                ps,
+                &this,
+                "alice",
+                "/tmp/project",
+                10,
+                None,
+                &AgentConfig::default(),
+            )
+            .into_iter()
+            .map(|s| s.id)
+            .collect::<Vec<_>>()
+        };
+        assert_eq!(ids(&["monitor".to_string()]), ["proc-101", "proc-102"]);
+        assert_eq!(ids(&["agent-box".to_string()]), ["proc-100", "proc-102"]);
+    }
+
+    #[test]
+    fn idle_tracker_flags_unchanged_sessions_after_threshold() {
+        let tracker = IdleTracker::new(MockCollector::new(), 1_000);
+        let events = MockCollector::new().collect();
+
+        let first = tracker.annotate(events.clone(), 10_000);
+        assert!(first.iter().all(|e| !e.idle));
+        let second = tracker.annotate(events.clone(), 10_500);
+        assert!(second.iter().all(|e| !e.idle));
+        let third = tracker.annotate(events.clone(), 11_000);
+        assert!(third.iter().all(|e| e.idle));
+        assert!(third.iter().all(|e| e.updated_at_unix_ms == 10_000));
+
+        let mut changed = events;
+        changed[0].last_lines.push("new output".to_string());
+        let fourth = tracker.annotate(changed, 11_500);
+        assert!(!fourth[0].idle);
+        assert!(fourth[1].idle);
+        assert_eq!(fourth[0].updated_at_unix_ms, 11_500);
+    }
+
+    #[test]
+    fn control_characters_are_scrubbed_from_titles() {
+        let command = super::sanitize_control_chars("claude fix\nlogin\x1b[2J flow");
+        let title = title_from_command(&command, &AgentKind::Claude, "/tmp/project", 12345);
+        assert!(!title.contains('\n'));
+        assert!(!title.contains('\x1b'));
+        assert!(title.contains("fix login"));
+
+        let ps = "4242 1 claude review\x1b[31m branch\n";
+        let sessions = super::sessions_from_ps_output(
+            ps,
+            &NOT_SELF,
+            "alice",
+            "/tmp/project",
+            10,
+            None,
+            &AgentConfig::default(),
+        );
+        assert!(!sessions[0].title.contains('\x1b'));
+        assert!(sessions[0].last_lines.iter().all(|l| !l.contains('\x1b')));
+    }
+
+    #[test]
+    fn failing_process_listing_is_reported() {
+        let collector = LocalProcessCollector::new()
+            .with_runner(Arc::new(FailingRunner))
+            .with_proc_root("/nonexistent/agent-box-proc");
+        let err = collector.try_collect().expect_err("all backends fail");
+        assert!(err.to_string().contains("ps unavailable"));
+        assert!(collector.collect().is_empty());
+        assert_eq!(collector.backend(), None);
+    }
+
+    #[test]
+    fn collect_raw_keeps_processes_that_collect_drops() {
+        let listing = "  900001 1 /bin/bash -l\n  900002 900001 claude --resume\n";
+        let collector = LocalProcessCollector::new().with_runner(Arc::new(StaticRunner(listing)));
+
+        let raw = collector.collect_raw();
+        assert_eq!(raw.len(), 2);
+        assert_eq!(raw[0].pid, 900001);
+        assert_eq!(raw[0].ppid, 1);
+        assert_eq!(raw[0].command, "/bin/bash -l");
+
+        let sessions = collector.collect();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].id, "proc-900002");
+
+        assert!(classify(&raw[0]).is_none());
+        let mut custom = classify(&raw[1]).expect("claude is an agent");
+        custom.title = format!("my title for {}", raw[1].pid);
+        assert_eq!(custom.agent, AgentKind::Claude);
+    }
+
+    #[test]
+    fn git_branch_reads_head_and_handles_detached_and_missing() {
+        let root = temp_path("agent-box-git");
+        let repo = root.join("repo");
+        let nested = repo.join("src").join("deep");
+        fs::create_dir_all(repo.join(".git")).expect("create .git");
+        fs::create_dir_all(&nested).expect("create nested");
+
+        fs::write(repo.join(".git").join("HEAD"), "ref: refs/heads/feature/login\n")
+            .expect("write HEAD");
+        assert_eq!(git_branch(&repo).as_deref(), Some("feature/login"));
+        assert_eq!(git_branch(&nested).as_deref(), Some("feature/login"));
+
+        let sha = "3f2a9c1d0b8e7f6a5b4c3d2e1f0a9b8c7d6e5f4a";
+        fs::write(repo.join(".git").join("HEAD"), format!("{sha}\n")).expect("write HEAD");
+        assert_eq!(git_branch(&repo).as_deref(), Some("detached@3f2a9c1"));
+
+        let worktree = root.join("worktree");
+        fs::create_dir_all(root.join("wt-git")).expect("create gitdir");
+        fs::create_dir_all(&worktree).expect("create worktree");
+        fs::write(root.join("wt-git").join("HEAD"), "ref: refs/heads/main\n").expect("HEAD");
+        fs::write(worktree.join(".git"), "gitdir: ../wt-git\n").expect("pointer");
+        assert_eq!(git_branch(&worktree).as_deref(), Some("main"));
+
+        let plain = root.join("plain");
+        fs::create_dir_all(&plain).expect("create plain");
+        // The temp dir itself is not expected to sit inside a git checkout.
+        if git_branch(&root).is_none() {
+            assert_eq!(git_branch(&plain), None);
+        }
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn project_is_named_after_the_enclosing_git_root() {
+        let root = temp_path("agent-box-project");
+        let repo = root.join("billing-service");
+        let nested = repo.join("crates").join("api").join("src");
+        fs::create_dir_all(repo.join(".git")).expect("create .git");
+        fs::create_dir_all(&nested).expect("create nested");
+
+        assert_eq!(git_project(&nested).as_deref(), Some("billing-service"));
+        assert_eq!(git_project(&repo).as_deref(), Some("billing-service"));
+
+        let raw = super::RawProcess {
+            pid: 7,
+            ppid: 1,
+            command: "claude".to_string(),
+        };
+        let cwd = nested.to_string_lossy().into_owned();
+        let event = super::classify_process(&raw, "alice", &cwd, 10, None, &AgentConfig::default())
+            .expect("claude");
+        assert_eq!(event.project.as_deref(), Some("billing-service"));
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn glob_match_supports_star_and_question_mark() {
+        assert!(glob_match("/home/*", "/home/bob"));
+        assert!(glob_match("/srv/*/secret", "/srv/a/b/secret"));
+        assert!(glob_match("bo?", "bob"));
+        assert!(!glob_match("bo?", "bobby"));
+        assert!(!glob_match("alice", "alice2"));
+    }
+
+    #[test]
+    fn excluded_users_and_dirs_are_dropped() {
+        let events = || {
+            let mut events = MockCollector::new().collect();
+            events[0].user = "bob".to_string();
+            events[0].working_dir = "/home/alice/repo".to_string();
+            events[1].user = "carol".to_string();
+            events[1].working_dir = "/srv/private/tool/src".to_string();
+            let mut kept = events[0].clone();
+            kept.id = "kept".to_string();
+            kept.user = "alice".to_string();
+            events.push(kept);
+            events
+        };
+        let filter = ExcludeFilter::new(MockCollector::new())
+            .with_excluded_users(vec!["b*".to_string()])
+            .with_excluded_dirs(vec!["/srv/priv*/tool/".to_string()]);
+        let kept = filter.retain(events());
+        assert_eq!(kept.len(), 1);
+        assert_eq!(kept[0].id, "kept");
+
+        let nothing_excluded = ExcludeFilter::new(MockCollector::new());
+        assert_eq!(nothing_excluded.retain(events()).len(), 3);
+    }
+
+    #[test]
+    fn stats_count_sessions_dropped_by_filter_and_cap() {
+        let mut events = MockCollector::new().collect();
+        let mut bot = events[0].clone();
+        bot.id = "bot-session".to_string();
+        bot.user = "bot".to_string();
+        events.push(bot);
+        events[1].updated_at_unix_ms -= 1;
+
+        let collector = IdleTracker::new(
+            SessionCap::new(
+                ExcludeFilter::new(FixedCollector(events))
+                    .with_excluded_users(vec!["bot".to_string()]),
+                Some(1),
+            ),
+            60_000,
+        );
+        let (kept, stats) = collector.collect_with_stats().expect("collect");
+        assert_eq!(kept.len(), 1);
+        assert_eq!(kept[0].id, "local-claude-1");
+        assert_eq!(
+            stats,
+            CollectStats {
+                total_seen: 3,
+                returned: 1,
+                dropped_by_cap: 1,
+                dropped_by_filter: 1,
+            }
+        );
+        assert_eq!(stats.hidden(), 2);
+
+        let (_, unfiltered) = MockCollector::new().collect_with_stats().expect("collect");
+        assert_eq!(unfiltered, CollectStats::unfiltered(2));
+    }
+
+    #[test]
+    fn tails_stdout_redirected_to_a_file() {
+        let root = temp_path("agent-box-fd");
+        let fd_dir = root.join("4242").join("fd");
+        fs::create_dir_all(&fd_dir).expect("create fd dir");
+        let log = root.join("run.log");
+        fs::write(&log, "booting\n\x1b[32mreading src/lib.rs\x1b[0m\nwriting patch\n\n")
+            .expect("log");
+        std::os::unix::fs::symlink(&log, fd_dir.join("1")).expect("fd symlink");
+
+        assert_eq!(
+            tail_stdout(&root, 4242),
+            Some(vec!["reading src/lib.rs".to_string(), "writing patch".to_string()])
+        );
+        assert_eq!(tail_stdout(&root, 4343), None);
+
+        let ps = "4242 1 /usr/local/bin/codex\n";
+        let sessions = super::sessions_from_ps_output(
+            ps,
+            &NOT_SELF,
+            "alice",
+            "/tmp/project",
+            10,
+            Some(&root),
+            &AgentConfig::default(),
+        );
+        assert_eq!(sessions[0].last_lines, vec!["reading src/lib.rs", "writing patch"]);
+
+        // A fifo on fd 1 must be skipped, not opened (opening it would block).
+        let fifo_pid = root.join("4343").join("fd");
+        fs::create_dir_all(&fifo_pid).expect("create fifo fd dir");
+        let fifo = std::ffi::CString::new(fifo_pid.join("1").to_string_lossy().as_bytes())
+            .expect("path");
+        // SAFETY: `fifo` is a valid NUL-terminated path that outlives the call.
+        assert_eq!(unsafe { libc::mkfifo(fifo.as_ptr(), 0o600) }, 0);
+        assert_eq!(tail_stdout(&root, 4343), None);
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn show_unknown_reports_interactive_long_runners_as_unknown() {
+        let root = temp_path("agent-box-unknown");
+        fs::create_dir_all(&root).expect("create proc root");
+        fs::write(root.join("uptime"), "5000.00 9000.00\n").expect("uptime");
+        let ticks = super::clock_ticks_per_sec();
+        let fake = |pid: u32, comm: &str, stdin: &str, started_secs: f64| {
+            let dir = root.join(pid.to_string());
+            fs::create_dir_all(dir.join("fd")).expect("create fd dir");
+            std::os::unix::fs::symlink(stdin, dir.join("fd").join("0")).expect("fd symlink");
+            let start = (started_secs * ticks) as u64;
+            // Fields 3..=21 (state through itrealvalue), then starttime.
+            let stat = format!(
+                "{pid} ({comm}) S 1 {pid} {pid} 34816 0 0 0 0 0 0 0 0 0 0 20 0 1 0 {start}"
+            );
+            fs::write(dir.join("stat"), stat).expect("stat");
+        };
+        fake(500, "htop", "/dev/pts/3", 100.0);
+        fake(501, "bash", "/dev/pts/3", 100.0);
+        fake(502, "htop", "/dev/pts/4", 4_990.0);
+        fake(503, "sleep", "/dev/null", 100.0);
+        let ps = "500 1 /usr/bin/htop\n501 1 -bash\n502 1 htop\n503 1 sleep 999\n";
+
+        let collect = |agents: &AgentConfig| {
+            super::sessions_from_ps_output(
+                ps,
+                &NOT_SELF,
+                "alice",
+                "/tmp/project",
+                10,
+                Some(&root),
+                agents,
+            )
+        };
+        assert!(collect(&AgentConfig::default()).is_empty());
+        let sessions = collect(&AgentConfig::default().with_show_unknown(true));
+        let _ = fs::remove_dir_all(&root);
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].id, "proc-500");
+        assert_eq!(sessions[0].agent, AgentKind::Unknown);
+    }
+
+    #[test]
+    fn falls_back_to_proc_when_ps_is_missing() {
+        let root = temp_path("agent-box-proc");
+        let pid_dir = root.join("4242");
+        fs::create_dir_all(&pid_dir).expect("create pid dir");
+        fs::write(pid_dir.join("cmdline"), b"/usr/bin/claude\0--resume\0").expect("cmdline");
+        fs::write(pid_dir.join("stat"), "4242 (claude) S 1 4242 4242").expect("stat");
+        fs::create_dir_all(root.join("self")).expect("non-pid dir");
+
+        let collector = LocalProcessCollector::new()
+            .with_runner(Arc::new(FailingRunner))
+            .with_proc_root(&root);
+        let sessions = collector.try_collect().expect("proc backend works");
+        assert_eq!(collector.backend(), Some(ProcessBackend::Proc));
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].id, "proc-4242");
+        assert_eq!(sessions[0].agent, AgentKind::Claude);
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn pgrep_output_is_normalized() {
+        let lines = super::pgrep_to_ps_lines("4242 /usr/bin/gemini chat\n");
+        assert_eq!(lines, "4242 0 /usr/bin/gemini chat\n");
+    }
+
     #[test]
     fn session_logs_fallback_when_no_path() {
         let logs = super::session_logs_from_command("claude");
@@ -1,39 +1,180 @@
+pub mod agents;
 pub mod cli;
 pub mod collector;
 pub mod model;
 pub mod renderer;
 pub mod security;
+pub mod snapshot;
 pub mod sync;
+pub mod syslog;
+pub mod transport;
+pub mod webhook;
 
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use crate::collector::{Collector, LocalProcessCollector};
-use crate::model::{RuntimeStateStore, SessionEvent};
-use crate::renderer::TerminalRenderer;
+use anyhow::{anyhow, Result};
 
-pub fn run_once_with_collector<C: Collector>(collector: &C, store: &mut RuntimeStateStore) {
+use crate::collector::{CollectStats, Collector, LocalProcessCollector};
+use crate::model::{aggregate_by_user, RuntimeStateStore, SessionEvent, SessionStatus};
+use crate::renderer::{FrameBlock, TerminalRenderer};
+
+pub fn run_once_with_collector<C: Collector + ?Sized>(collector: &C, store: &mut RuntimeStateStore) {
     let events = collector.collect();
     for event in events {
         store.upsert(event);
     }
 }
 
+pub fn try_run_once_with_collector<C: Collector + ?Sized>(
+    collector: &C,
+    store: &mut RuntimeStateStore,
+) -> Result<()> {
+    for event in collector.try_collect()? {
+        store.upsert(event);
+    }
+    Ok(())
+}
+
+/// Like `try_run_once_with_collector`, returning what the collector dropped on the way.
+pub fn try_run_once_with_stats<C: Collector + ?Sized>(
+    collector: &C,
+    store: &mut RuntimeStateStore,
+) -> Result<CollectStats> {
+    let (events, stats) = collector.collect_with_stats()?;
+    for event in events {
+        store.upsert(event);
+    }
+    Ok(stats)
+}
+
 pub fn run_once(store: &mut RuntimeStateStore) {
     let collector = LocalProcessCollector::new();
     run_once_with_collector(&collector, store);
 }
 
+pub fn try_run_once(store: &mut RuntimeStateStore) -> Result<()> {
+    let collector = LocalProcessCollector::new();
+    try_run_once_with_collector(&collector, store)
+}
+
 pub fn render_snapshot(store: &RuntimeStateStore) -> String {
     render_snapshot_with_frame(store, 0)
 }
 
 pub fn render_snapshot_with_frame(store: &RuntimeStateStore, frame: usize) -> String {
-    let rendered = TerminalRenderer::new().render_many_with_frame(store.all(), frame);
-    if rendered.trim().is_empty() {
+    render_snapshot_with_renderer(store, &TerminalRenderer::new(), frame)
+}
+
+pub fn render_snapshot_with_renderer(
+    store: &RuntimeStateStore,
+    renderer: &TerminalRenderer,
+    frame: usize,
+) -> String {
+    let (sessions, footer) = renderer.paginate(store.all());
+    // Only split into LOCAL/REMOTE sections once a remote session is actually visible.
+    let has_remote = sessions.iter().any(|s| s.origin.peer().is_some());
+    let rendered = if renderer.groups_by_project() {
+        renderer.render_many_by_project(sessions, frame)
+    } else if has_remote {
+        renderer.render_many_sectioned(sessions, frame)
+    } else {
+        renderer.render_many_with_frame(sessions, frame)
+    };
+    let mut view = if rendered.trim().is_empty() {
         "No active Claude/Codex/Gemini local sessions detected.".to_string()
+    } else if let Some(footer) = footer {
+        format!("{rendered}\n\n{footer}")
     } else {
         rendered
+    };
+    if !renderer.idle_peers().is_empty() {
+        view.push_str("\n\n");
+        view.push_str(&renderer.render_idle_peers(renderer.idle_peers()));
+    }
+    view
+}
+
+/// `render_snapshot_with_renderer` plus a final newline, as `IncrementalScreen` blocks:
+/// one per session card in the flat layout, otherwise a single block for the whole view.
+pub fn render_snapshot_blocks(
+    store: &RuntimeStateStore,
+    renderer: &TerminalRenderer,
+    frame: usize,
+) -> Vec<FrameBlock> {
+    let (sessions, footer) = renderer.paginate(store.all());
+    let flat = !renderer.groups_by_project() && sessions.iter().all(|s| s.origin.peer().is_none());
+    let mut blocks = if flat {
+        renderer.render_blocks(sessions, frame)
+    } else {
+        Vec::new()
+    };
+    if blocks.is_empty() {
+        let rendered = render_snapshot_with_renderer(store, renderer, frame);
+        return vec![FrameBlock::new("sessions", format!("{rendered}\n"))];
+    }
+    if let Some(footer) = footer {
+        blocks.push(FrameBlock::new("footer", format!("\n{footer}\n")));
     }
+    if !renderer.idle_peers().is_empty() {
+        let peers = renderer.render_idle_peers(renderer.idle_peers());
+        blocks.push(FrameBlock::new("peers", format!("\n{peers}\n")));
+    }
+    blocks
+}
+
+/// Renders a single session block for focus views; `None` when the id is not in the store.
+pub fn render_snapshot_session(
+    store: &RuntimeStateStore,
+    id: &str,
+    frame: usize,
+) -> Option<String> {
+    store
+        .get(id)
+        .map(|event| TerminalRenderer::new().render_session_with_frame(event, frame))
+}
+
+pub fn render_user_summary(store: &RuntimeStateStore) -> String {
+    let aggregates = aggregate_by_user(&store.all());
+    if aggregates.is_empty() {
+        "No active Claude/Codex/Gemini local sessions detected.".to_string()
+    } else {
+        TerminalRenderer::new().render_user_aggregates(&aggregates)
+    }
+}
+
+/// CI gate for `--fail-on-waiting`: errors, listing each one, when any session is
+/// blocked on the user.
+pub fn check_no_waiting(store: &RuntimeStateStore) -> Result<()> {
+    let waiting = store.summary().waiting_input;
+    if waiting == 0 {
+        return Ok(());
+    }
+    let lines = store
+        .all()
+        .into_iter()
+        .filter(|s| s.status == SessionStatus::WaitingInput)
+        .map(|s| {
+            let action = s.pending_action.as_deref().unwrap_or("needs input");
+            format!("  {} [{}] {} @ {}: {action}", s.id, s.agent.as_label(), s.title, s.working_dir)
+        })
+        .collect::<Vec<_>>();
+    let noun = if waiting == 1 { "session is" } else { "sessions are" };
+    Err(anyhow!("{waiting} {noun} waiting for input:\n{}", lines.join("\n")))
+}
+
+/// `check_no_waiting` for a store filled by a collection pass that may have failed. A
+/// failed pass fails the gate too: an empty store then proves nothing.
+pub fn check_collected_no_waiting(
+    store: &RuntimeStateStore,
+    collect_error: Option<&anyhow::Error>,
+) -> Result<()> {
+    if let Some(err) = collect_error {
+        return Err(anyhow!("cannot check for waiting sessions: collection failed ({err})"));
+    }
+    check_no_waiting(store)
 }
 
 pub fn unix_ms_now() -> u64 {
@@ -43,6 +184,45 @@ pub fn unix_ms_now() -> u64 {
         .as_millis() as u64
 }
 
+/// Asks the monitor loop to collect and render now instead of at the next tick. Its flag
+/// can be handed to a signal handler (SIGUSR1 in the binary).
+#[derive(Debug, Clone, Default)]
+pub struct RefreshTrigger {
+    requested: Arc<AtomicBool>,
+}
+
+impl RefreshTrigger {
+    pub fn flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.requested)
+    }
+
+    pub fn request(&self) {
+        self.requested.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether a refresh was requested since the last call; clears the request.
+    pub fn take(&self) -> bool {
+        self.requested.swap(false, Ordering::Relaxed)
+    }
+}
+
+/// Sleeps for `duration` in short slices so Ctrl+C and refresh requests are honored
+/// promptly. Returns whether the wait was cut short by `refresh`.
+pub fn wait_for_next_tick(duration: Duration, stop: &AtomicBool, refresh: &RefreshTrigger) -> bool {
+    let deadline = Instant::now() + duration;
+    while !stop.load(Ordering::Relaxed) {
+        if refresh.take() {
+            return true;
+        }
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            break;
+        }
+        thread::sleep(remaining.min(Duration::from_millis(50)));
+    }
+    false
+}
+
 pub fn sample_event(id: &str) -> SessionEvent {
     SessionEvent::new_running(
         id.to_string(),
@@ -1,101 +1,179 @@
 use std::collections::{HashMap, HashSet};
+use std::fmt::Write as _;
+use std::fs::OpenOptions;
+use std::io::{self, IsTerminal, Write};
 use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::thread;
 use std::time::Duration;
 
 use anyhow::Result;
 use clap::Parser;
 
-use agent_box::cli::{detect_public_ip, parse_peer, validate_bind, CliArgs};
-use agent_box::model::RuntimeStateStore;
-use agent_box::security::generate_passkey_sha1;
-use agent_box::sync::{discover_join_key, SyncClient, SyncServer, TransportProtocol};
-use agent_box::{render_snapshot_with_frame, run_once, unix_ms_now};
+use agent_box::agents::AgentConfig;
+use agent_box::cli::{
+    error_json, join_string, resolve_runtime_config, CliArgs, EffectiveConfig, ErrorFormat,
+    OutputFormat, PeerOrder, RuntimeConfig,
+};
+use agent_box::collector::{
+    find_session_files, Collector, ExcludeFilter, IdleTracker, LocalProcessCollector,
+    MockCollector, SessionCap, DEFAULT_IDLE_THRESHOLD_MS,
+};
+use agent_box::model::{
+    cap_per_agent, detect_conflicts, Churn, ChurnTracker, Linger, RuntimeStateStore,
+    TransitionObserver,
+};
+use agent_box::renderer::{
+    cards_per_screen, fit_width, format_clock, format_duration, render_statusline,
+    terminal_height, write_frame, FrameBlock, IncrementalScreen, ScreenMode, SpinnerStyle,
+    TerminalRenderer, Theme,
+};
+use agent_box::security::{derive_observer_key, RedactionPolicy, SecurityLayer};
+use agent_box::snapshot::write_jsonl_tick;
+use agent_box::sync::{fetch_key_fingerprint, RemoteCache, ShareProfile, SyncClient, SyncServer};
+use agent_box::syslog::SyslogSink;
+use agent_box::webhook::{WebhookDelivery, WebhookSink};
+use agent_box::{
+    check_collected_no_waiting, render_snapshot_blocks, render_user_summary,
+    try_run_once_with_collector, try_run_once_with_stats, unix_ms_now, wait_for_next_tick,
+    RefreshTrigger,
+};
 
-fn main() -> Result<()> {
-    let session_unix_ms = unix_ms_now();
+fn main() {
     let args = CliArgs::parse();
-    let prefer_public_ip = args.public || args.peer.is_some();
-    let listen_ip = if prefer_public_ip {
-        match detect_public_ip() {
-            Ok(ip) => ip,
-            Err(err) => {
-                eprintln!(
-                    "warning: public IP resolution failed ({err}); using configured bind IP {}",
-                    args.ip
-                );
-                args.ip.clone()
-            }
+    let error_format = args.error_format;
+    if let Err(err) = run(args) {
+        match error_format {
+            ErrorFormat::Json => eprintln!("{}", error_json(&err)),
+            ErrorFormat::Text => eprintln!("Error: {err:?}"),
         }
-    } else {
-        args.ip.clone()
-    };
-    validate_bind(&listen_ip, args.port)?;
-    let random_seed = session_unix_ms ^ (process::id() as u64);
-    let local_host = std::env::var("HOSTNAME")
-        .or_else(|_| std::env::var("COMPUTERNAME"))
-        .unwrap_or_else(|_| "localhost".to_string());
-    let mut session_key: Option<String> = None;
-    let mut peer_host: Option<String> = None;
-
-    if let Some(peer) = args.peer.as_deref() {
-        let parsed = parse_peer(peer, session_unix_ms)?;
-        let mut effective_key = if let Some(explicit) = args.key.as_deref() {
-            explicit.to_string()
-        } else {
-            parsed.auth_key.clone()
-        };
-        if parsed.generated_auth_key && args.key.is_none() {
-            match discover_join_key(&parsed.host, args.port, Duration::from_millis(500)) {
-                Ok(discovered) => {
-                    effective_key = discovered;
-                    println!("Discovered peer passkey from '{}'.", parsed.host);
+        process::exit(1);
+    }
+}
+
+fn run(mut args: CliArgs) -> Result<()> {
+    if args.demo {
+        // Sample sessions only: no process scan, no public IP lookup, no sync.
+        args.peer = None;
+        args.key = None;
+        args.public = false;
+        args.iface = None;
+        args.no_expose = true;
+    }
+    let session_unix_ms = unix_ms_now();
+    if args.debug_sessions {
+        let cwd = std::env::current_dir()?;
+        let files = find_session_files(&cwd);
+        if files.is_empty() {
+            println!("No session files found for {}", cwd.display());
+        }
+        for path in files {
+            println!("{}", path.display());
+        }
+        return Ok(());
+    }
+    let agents = agents_from_args(&args)?;
+    if args.list_agents {
+        print!("{}", agents.render_detection_table());
+        return Ok(());
+    }
+    if args.dump_config {
+        let config = EffectiveConfig::new(&args, redaction_from_args(&args), agents);
+        print!("{}", config.to_toml()?);
+        return Ok(());
+    }
+    let quiet = args.quiet;
+    if args.oneline {
+        let mut store = RuntimeStateStore::default();
+        try_run_once_with_collector(local_collector(&args, &agents).as_ref(), &mut store)?;
+        let theme = agents.apply_to_theme(theme_from_args(args.theme));
+        print!("{}", render_statusline(&store.all(), &theme));
+        io::stdout().flush()?;
+        return Ok(());
+    }
+    let config = resolve_runtime_config(&args)?;
+    for warning in &config.warnings {
+        warn(quiet, warning);
+    }
+    if let Some(parsed) = &config.peer {
+        let local_fingerprint = SecurityLayer::new(&parsed.auth_key).fingerprint();
+        match fetch_key_fingerprint(&parsed.host, args.port, Duration::from_millis(500)) {
+            Ok(remote) if remote == local_fingerprint => {
+                if !quiet {
+                    println!("Peer '{}' key fingerprint {remote} matches.", parsed.host);
                 }
-                Err(err) => {
-                    println!(
-                        "No passkey supplied for peer '{}'; discovery failed ({err}), using generated fallback key.",
+            }
+            Ok(remote) => {
+                let hint = if parsed.generated_auth_key {
+                    "no passkey was supplied; ask the peer for its passkey"
+                } else {
+                    "check the passkey with the peer"
+                };
+                warn(
+                    quiet,
+                    format_args!(
+                        "peer '{}' key fingerprint {remote} does not match yours \
+                         ({local_fingerprint}); {hint}.",
                         parsed.host
-                    );
-                }
+                    ),
+                );
+            }
+            Err(err) => {
+                warn(
+                    quiet,
+                    format_args!(
+                        "could not confirm key fingerprint with peer '{}' ({err}).",
+                        parsed.host
+                    ),
+                );
             }
         }
-        peer_host = Some(parsed.host.clone());
-        session_key = Some(effective_key.clone());
-        let client = SyncClient::new(&effective_key);
-        client.handshake(&effective_key)?;
-    } else if let Some(explicit_key) = args.key.as_deref() {
-        // Explicit key also defines local session sharing key without a join target.
-        session_key = Some(explicit_key.to_string());
-    } else if !args.no_expose {
-        // No passkey supplied at all in CLI input: generate one for join instructions.
-        session_key = Some(generate_passkey_sha1(
-            &format!("{local_host}:{listen_ip}"),
-            session_unix_ms,
-            random_seed,
-        ));
-    }
-
-    let tick_secs = args.interval.max(1);
+    }
+    let RuntimeConfig {
+        listen_ip,
+        bind_ip,
+        local_host,
+        session_key,
+        peer,
+        protocol,
+        ..
+    } = config;
+    let peer_host = peer.map(|peer| peer.host);
+
+    let tick = args.interval;
+    let mut out: Box<dyn Write> = match &args.output {
+        Some(path) => Box::new(OpenOptions::new().create(true).append(true).open(path)?),
+        // Held for the whole run so nothing can interleave with a frame being written.
+        None => Box::new(io::stdout().lock()),
+    };
+    let collector = local_collector(&args, &agents);
     let mut local_store = RuntimeStateStore::default();
     let mut combined_store = RuntimeStateStore::default();
     let mut frame: usize = 0;
-    let mut remote_cache: HashMap<String, (agent_box::model::SessionEvent, u64)> = HashMap::new();
+    let mut remote_cache =
+        RemoteCache::new(tick.as_millis() as u64 * 8).with_max_skew_ms(args.max_clock_skew_ms);
+    // pull target -> when a full pull to it last came back with no sessions
+    let mut idle_pulled_at: HashMap<String, u64> = HashMap::new();
     let mut known_peers: HashSet<String> = HashSet::new();
-    let protocol = transport_from_args(args.protocol);
-    let bind_ip = if prefer_public_ip {
-        "0.0.0.0".to_string()
-    } else {
-        listen_ip.clone()
-    };
 
+    let redaction = redaction_from_args(&args);
     let sync_server = if !args.no_expose {
         if let Some(key) = &session_key {
-            match SyncServer::bind(&bind_ip, args.port, key) {
+            let server = SyncServer::builder(key)
+                .with_source_host(&local_host)
+                .with_redaction(redaction.clone())
+                .with_share_profile(share_from_args(args.share))
+                .bind(&bind_ip, args.port);
+            match server {
                 Ok(server) => Some(server),
                 Err(err) => {
-                    eprintln!(
-                        "warning: could not start sync server on {}:{} ({err})",
-                        bind_ip, args.port
+                    warn(
+                        quiet,
+                        format_args!(
+                            "could not start sync server on {}:{} ({err})",
+                            bind_ip, args.port
+                        ),
                     );
                     None
                 }
@@ -107,14 +185,77 @@ fn main() -> Result<()> {
         None
     };
 
-    loop {
+    let is_tty = args.output.is_none() && io::stdout().is_terminal();
+    let mut renderer = TerminalRenderer::new()
+        .with_hide_done(args.hide_done)
+        .with_group_by_project(args.by_project)
+        .with_pinned(args.pin.clone())
+        .with_deterministic(args.no_spinner)
+        .with_home_dir(std::env::var("HOME").ok())
+        .with_width(fit_width(is_tty))
+        .with_max_width(args.max_width.map(usize::from))
+        .with_paging(
+            args.page.unwrap_or(1) as usize,
+            match (args.max_rows, args.page) {
+                (Some(rows), _) => Some(rows as usize),
+                (None, Some(_)) => terminal_height().map(cards_per_screen),
+                (None, None) => None,
+            },
+        )
+        .with_theme(
+            agents.apply_to_theme(
+                theme_from_args(args.theme).with_spinner(spinner_from_args(args.spinner)),
+            ),
+        );
+    let once = args.once || args.fail_on_waiting;
+    let anonymizer = args
+        .anonymize
+        .then(|| SecurityLayer::new(session_key.as_deref().unwrap_or_default()));
+    let screen = ScreenMode::from_flags(args.alt_screen, args.no_clear || once, is_tty);
+    let label = args.label.as_deref().map(str::trim).filter(|label| !label.is_empty());
+    let mut webhook = args
+        .webhook
+        .as_deref()
+        .map(|url| WebhookSink::new(url).map(|sink| sink.with_label(label.map(str::to_string))))
+        .transpose()?
+        .map(WebhookDelivery::spawn);
+    let mut syslog = args.syslog.then(SyslogSink::connect).transpose()?;
+    let mut observer = TransitionObserver::default();
+    let mut churn = ChurnTracker::default();
+    // Peers in the order their first session showed up, for `--peer-order first-seen`.
+    let mut peers_seen: Vec<String> = Vec::new();
+    let mut linger = Linger::new(args.linger_secs.saturating_mul(1000));
+    let mut incremental =
+        (args.incremental && screen != ScreenMode::Append).then(IncrementalScreen::default);
+    let stop = Arc::new(AtomicBool::new(false));
+    for signal in [signal_hook::consts::SIGINT, signal_hook::consts::SIGTERM] {
+        signal_hook::flag::register(signal, Arc::clone(&stop))?;
+    }
+    let resized = Arc::new(AtomicBool::new(false));
+    #[cfg(unix)]
+    signal_hook::flag::register(signal_hook::consts::SIGWINCH, Arc::clone(&resized))?;
+    let refresh = RefreshTrigger::default();
+    #[cfg(unix)]
+    signal_hook::flag::register(signal_hook::consts::SIGUSR1, refresh.flag())?;
+    if args.format == OutputFormat::Text {
+        write!(out, "{}", screen.enter_sequence())?;
+        out.flush()?;
+    }
+
+    // Kept past the loop so `--fail-on-waiting` can tell an empty store from a failed scan.
+    let mut last_collect_error = None;
+    while !stop.load(Ordering::Relaxed) {
         let now_ms = unix_ms_now();
         local_store.clear();
-        run_once(&mut local_store);
+        let collected = try_run_once_with_stats(collector.as_ref(), &mut local_store);
+        let (stats, collect_error) = match collected {
+            Ok(stats) => (stats, None),
+            Err(err) => (Default::default(), Some(err)),
+        };
         let local_events = local_store.all();
         let local_events_snapshot = local_events.clone();
 
-        if let (Some(server), Some(key)) = (&sync_server, session_key.as_deref()) {
+        if let Some(server) = &sync_server {
             if let Ok(incoming) = server.serve_once(
                 local_events.clone(),
                 &listen_ip,
@@ -123,16 +264,9 @@ fn main() -> Result<()> {
             ) {
                 for update in incoming {
                     known_peers.insert(update.peer.clone());
-                    for mut event in update.payload {
-                        event.id = format!("remote:{}:{}", update.peer, event.id);
-                        event.user = format!("{}@{}", event.user, update.peer);
-                        event.updated_at_unix_ms = now_ms;
-                        remote_cache.insert(event.id.clone(), (event, now_ms));
-                    }
+                    remote_cache.ingest(&update.peer, update.payload, now_ms);
                 }
             }
-            // Keep an explicit handshake check in loop for deterministic auth behavior.
-            let _ = SyncClient::new(key).handshake(key);
         }
 
         let mut pull_targets = known_peers.clone();
@@ -141,35 +275,50 @@ fn main() -> Result<()> {
         }
 
         if let Some(key) = session_key.as_deref() {
-            let pull_timeout = Duration::from_millis((tick_secs * 2 + 1) * 1000);
+            let pull_timeout = tick * 2 + Duration::from_secs(1);
             for target in pull_targets {
                 if target == listen_ip {
                     continue;
                 }
-                let client = SyncClient::new(key);
+                let client = SyncClient::new(key)
+                    .with_name(&local_host)
+                    .with_strict_peer(args.strict_peer)
+                    .with_redaction(redaction.clone())
+                    .with_share_profile(share_from_args(args.share));
                 let snapshot = local_events_snapshot.clone();
+                // A peer whose last full pull was empty only needs a heartbeat until the
+                // next full pull is due, as long as we have nothing to push either.
+                let heartbeat_only = snapshot.is_empty()
+                    && idle_pulled_at
+                        .get(&target)
+                        .is_some_and(|at| now_ms.saturating_sub(*at) < args.heartbeat_ms);
                 for attempt in 0..2 {
-                    match client.pull_once(
-                        &target,
-                        args.port,
-                        key,
-                        &listen_ip,
-                        snapshot.clone(),
-                        pull_timeout,
-                    ) {
+                    let result = if heartbeat_only {
+                        client.heartbeat(&target, args.port, key, &listen_ip, pull_timeout)
+                    } else {
+                        client.pull_once(
+                            &target,
+                            args.port,
+                            key,
+                            &listen_ip,
+                            snapshot.clone(),
+                            pull_timeout,
+                        )
+                    };
+                    match result {
                         Ok(remote) => {
-                            let source_peer = if remote.peer.trim().is_empty() {
-                                target.clone()
-                            } else {
-                                remote.peer.clone()
-                            };
-                            known_peers.insert(source_peer.clone());
-                            for mut event in remote.payload {
-                                event.id = format!("remote:{}:{}", source_peer, event.id);
-                                event.user = format!("{}@{}", event.user, source_peer);
-                                event.updated_at_unix_ms = now_ms;
-                                remote_cache.insert(event.id.clone(), (event, now_ms));
+                            // Keep dialing the address we reached; namespace by what the
+                            // server says it is, so loopback peers don't collide.
+                            known_peers.insert(target.clone());
+                            if !remote.heartbeat {
+                                if remote.payload.is_empty() {
+                                    idle_pulled_at.insert(target.clone(), now_ms);
+                                } else {
+                                    idle_pulled_at.remove(&target);
+                                }
                             }
+                            let source_peer = remote.source_name(&target).to_string();
+                            remote_cache.ingest(&source_peer, remote.payload, now_ms);
                             break;
                         }
                         Err(_) => {
@@ -183,36 +332,232 @@ fn main() -> Result<()> {
         }
 
         // Keep remote cache stable to avoid flicker, but prune stale entries.
-        let remote_ttl_ms = (tick_secs * 8 * 1000) as u64;
-        remote_cache.retain(|_, (_, seen_at)| now_ms.saturating_sub(*seen_at) <= remote_ttl_ms);
+        remote_cache.prune(now_ms);
 
         combined_store.clear();
-        for event in local_events {
+        let mut combined_events = local_events;
+        combined_events.extend(remote_cache.events());
+        let overflow = match args.max_per_agent {
+            Some(max) => {
+                let (kept, overflow) = cap_per_agent(combined_events, max);
+                combined_events = kept;
+                overflow
+            }
+            None => Vec::new(),
+        };
+        for event in combined_events {
+            let event = match &anonymizer {
+                Some(security) => security.anonymize(event),
+                None => event,
+            };
             let _ = combined_store.upsert(event);
         }
-        for (event, _) in remote_cache.values() {
-            let _ = combined_store.upsert(event.clone());
+        linger.apply(&mut combined_store, now_ms);
+        let transitions = observer.observe(&combined_store);
+        // Everything is "started" on the first tick; that is discovery, not churn.
+        if frame > 0 {
+            churn.record(&transitions, now_ms);
         }
+        let webhook_error = webhook
+            .as_mut()
+            .and_then(|delivery| delivery.push(&transitions, now_ms));
+        let syslog_error = syslog.as_mut().and_then(|sink| sink.send(&transitions).err());
 
-        // Clear screen and move cursor to top-left for live dashboard behavior.
-        print!("\x1b[2J\x1b[H");
-        println!("Agent-box live monitor (Ctrl+C to stop)");
-        if let Some(key) = &session_key {
-            println!("Join by: agent-box {}:{}\n", listen_ip, key);
+        if args.format == OutputFormat::Jsonl {
+            if let Some(err) = &collect_error {
+                warn(quiet, format_args!("local session collection failed ({err})"));
+            }
+            if let Some(err) = &webhook_error {
+                warn(quiet, format_args!("webhook delivery failed ({err})"));
+            }
+            if let Some(err) = &syslog_error {
+                warn(quiet, err);
+            }
+            write_jsonl_tick(&mut out, &combined_store.all(), now_ms, label)?;
         } else {
-            println!("--- refresh @ {} ---\n", now_ms);
+            let mut head = String::new();
+            let Churn { started, ended } = churn.counts(now_ms);
+            writeln!(
+                head,
+                "Agent-box live monitor (Ctrl+C to stop) · uptime {} · \
+                 {started} started / {ended} ended in last minute",
+                format_duration(now_ms.saturating_sub(session_unix_ms))
+            )?;
+            if let Some(key) = &session_key {
+                writeln!(
+                    head,
+                    "Join by: {} (fingerprint {})",
+                    join_string(&listen_ip, args.port, key),
+                    SecurityLayer::new(key).fingerprint()
+                )?;
+                writeln!(
+                    head,
+                    "Observe by: {}\n",
+                    join_string(&listen_ip, args.port, &derive_observer_key(key))
+                )?;
+            } else {
+                if args.verbose {
+                    writeln!(head, "--- refresh @ {} ({now_ms} ms) ---\n", format_clock(now_ms))?;
+                } else {
+                    writeln!(head, "--- refresh @ {} ---\n", format_clock(now_ms))?;
+                }
+            }
+            if let Some(err) = &collect_error {
+                writeln!(head, "warning: local session collection failed ({err})\n")?;
+            }
+            if let Some(err) = &webhook_error {
+                writeln!(head, "warning: webhook delivery failed ({err})\n")?;
+            }
+            if let Some(err) = &syslog_error {
+                writeln!(head, "warning: {err}\n")?;
+            }
+            if quiet {
+                head.clear();
+            }
+            // The label names the instance, so it stays even when `--quiet` drops the rest.
+            if let Some(label) = label {
+                head.insert_str(0, &format!("=== {label} ===\n"));
+            }
+            let mut blocks = vec![FrameBlock::new("banner", head)];
+            let mut tail = String::new();
+            if args.by_user {
+                let summary = render_user_summary(&combined_store);
+                blocks.push(FrameBlock::new("users", format!("{summary}\n")));
+            } else {
+                // Without SIGWINCH the size (`$COLUMNS` there) is simply re-read each tick.
+                if cfg!(not(unix)) {
+                    resized.store(true, Ordering::Relaxed);
+                }
+                if renderer.refresh_width(&resized, || fit_width(is_tty)) {
+                    if let Some(incremental) = &mut incremental {
+                        incremental.invalidate();
+                    }
+                }
+                let mut idle_peers = remote_cache.idle_peers(now_ms);
+                if let Some(security) = &anonymizer {
+                    for peer in &mut idle_peers {
+                        *peer = security.pseudonym("host", peer);
+                    }
+                }
+                if args.peer_order == PeerOrder::FirstSeen {
+                    for event in combined_store.all() {
+                        let Some(peer) = event.origin.peer() else {
+                            continue;
+                        };
+                        if !peers_seen.iter().any(|seen| seen == peer) {
+                            peers_seen.push(peer.to_string());
+                        }
+                    }
+                }
+                renderer = renderer
+                    .with_peer_order(peers_seen.clone())
+                    .with_now(Some(now_ms))
+                    .with_conflicts(&detect_conflicts(&combined_store.all()))
+                    .with_idle_peers(idle_peers);
+                blocks.extend(render_snapshot_blocks(&combined_store, &renderer, frame));
+                if !overflow.is_empty() {
+                    writeln!(tail, "\n{}", renderer.render_overflow(&overflow))?;
+                }
+                if stats.hidden() > 0 {
+                    writeln!(tail, "\n{}", renderer.render_hidden(stats.hidden()))?;
+                }
+            }
+            blocks.push(FrameBlock::new("trailer", tail));
+            // Build the whole frame (a redraw or just the changed blocks), then write it at once.
+            let frame_text = match &mut incremental {
+                Some(incremental) => incremental.update(blocks),
+                None => screen.compose(&blocks),
+            };
+            write_frame(&mut out, &frame_text)?;
+        }
+        out.flush()?;
+        last_collect_error = collect_error;
+        if once {
+            break;
         }
-        println!("{}", render_snapshot_with_frame(&combined_store, frame));
         frame = frame.wrapping_add(1);
-        thread::sleep(Duration::from_secs(tick_secs));
+        wait_for_next_tick(tick, &stop, &refresh);
+    }
+
+    if args.format == OutputFormat::Text {
+        write!(out, "{}", screen.exit_sequence())?;
+        out.flush()?;
+    }
+    if let Some(Err(err)) = webhook.map(WebhookDelivery::finish) {
+        warn(quiet, format_args!("webhook delivery failed ({err})"));
+    }
+    if args.fail_on_waiting {
+        check_collected_no_waiting(&combined_store, last_collect_error.as_ref())?;
+    }
+    Ok(())
+}
+
+/// Prints a `warning:` line to stderr unless `--quiet` asked for session output only.
+fn warn(quiet: bool, message: impl std::fmt::Display) {
+    if !quiet {
+        eprintln!("warning: {message}");
+    }
+}
+
+fn local_collector(args: &CliArgs, agents: &AgentConfig) -> Box<dyn Collector> {
+    if args.demo {
+        return Box::new(MockCollector::demo());
+    }
+    let mut local = LocalProcessCollector::new().with_agents(agents.clone());
+    if !args.self_name.is_empty() {
+        local = local.with_self_names(args.self_name.clone());
+    }
+    let filtered = ExcludeFilter::new(local)
+        .with_excluded_users(args.exclude_user.clone())
+        .with_excluded_dirs(args.exclude_dir.clone());
+    Box::new(IdleTracker::new(
+        SessionCap::new(filtered, args.max_sessions),
+        DEFAULT_IDLE_THRESHOLD_MS,
+    ))
+}
+
+/// An explicit `--agents-file` must exist; the default location is optional.
+fn agents_from_args(args: &CliArgs) -> Result<AgentConfig> {
+    let config = match (&args.agents_file, AgentConfig::default_path()) {
+        (Some(path), _) => AgentConfig::load(path)?,
+        (None, Some(path)) if path.is_file() => AgentConfig::load(&path)?,
+        (None, _) => AgentConfig::default(),
+    };
+    let show_unknown = args.show_unknown || config.shows_unknown();
+    Ok(config.with_show_unknown(show_unknown))
+}
+
+fn redaction_from_args(args: &CliArgs) -> RedactionPolicy {
+    if args.no_redact {
+        return RedactionPolicy::off();
+    }
+    RedactionPolicy::default()
+        .with_titles(args.redact_titles)
+        .with_extra_patterns(args.redact_pattern.clone())
+}
+
+fn theme_from_args(theme: agent_box::cli::ThemeName) -> Theme {
+    match theme {
+        agent_box::cli::ThemeName::Dark => Theme::dark(),
+        agent_box::cli::ThemeName::Light => Theme::light(),
+        agent_box::cli::ThemeName::Mono => Theme::mono(),
+    }
+}
+
+fn spinner_from_args(spinner: agent_box::cli::Spinner) -> SpinnerStyle {
+    match spinner {
+        agent_box::cli::Spinner::Quadrant => SpinnerStyle::QuadrantCircle,
+        agent_box::cli::Spinner::Braille => SpinnerStyle::Braille,
+        agent_box::cli::Spinner::Ascii => SpinnerStyle::Ascii,
+        agent_box::cli::Spinner::Dots => SpinnerStyle::Dots,
     }
 }
 
-fn transport_from_args(protocol: agent_box::cli::Protocol) -> TransportProtocol {
-    match protocol {
-        agent_box::cli::Protocol::Http => TransportProtocol::Http,
-        agent_box::cli::Protocol::Https => TransportProtocol::Https,
-        agent_box::cli::Protocol::Quic => TransportProtocol::Quic,
+fn share_from_args(share: agent_box::cli::Share) -> ShareProfile {
+    match share {
+        agent_box::cli::Share::Full => ShareProfile::Full,
+        agent_box::cli::Share::StatusOnly => ShareProfile::StatusOnly,
+        agent_box::cli::Share::Redacted => ShareProfile::Redacted,
     }
 }
 
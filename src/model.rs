@@ -1,26 +1,60 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::str::FromStr;
 
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub enum AgentKind {
     Claude,
     Codex,
     Gemini,
+    Copilot,
+    Cody,
     Unknown,
+    /// An agent outside the built-in set, identified by its lowercase label.
+    Custom(String),
 }
 
 impl AgentKind {
-    pub fn as_label(&self) -> &'static str {
+    pub fn as_label(&self) -> &str {
         match self {
             AgentKind::Claude => "claude",
             AgentKind::Codex => "codex",
             AgentKind::Gemini => "gemini",
+            AgentKind::Copilot => "copilot",
+            AgentKind::Cody => "cody",
             AgentKind::Unknown => "unknown",
+            AgentKind::Custom(label) => label,
+        }
+    }
+}
+
+impl FromStr for AgentKind {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "claude" => Ok(AgentKind::Claude),
+            "codex" => Ok(AgentKind::Codex),
+            "gemini" => Ok(AgentKind::Gemini),
+            "copilot" => Ok(AgentKind::Copilot),
+            "cody" => Ok(AgentKind::Cody),
+            "unknown" => Ok(AgentKind::Unknown),
+            other if is_custom_label(other) => Ok(AgentKind::Custom(other.to_string())),
+            other => Err(format!("unknown agent kind: {other}")),
         }
     }
 }
 
+/// Custom agent labels are short lowercase identifiers such as `aider` or `cursor-agent`.
+fn is_custom_label(label: &str) -> bool {
+    !label.is_empty()
+        && label.len() <= 32
+        && label
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+}
+
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 pub enum SessionStatus {
     Running,
@@ -31,6 +65,16 @@ pub enum SessionStatus {
 }
 
 impl SessionStatus {
+    pub fn as_label(&self) -> &'static str {
+        match self {
+            SessionStatus::Running => "running",
+            SessionStatus::WaitingInput => "waiting_input",
+            SessionStatus::Success => "success",
+            SessionStatus::Failed => "failed",
+            SessionStatus::Stopped => "stopped",
+        }
+    }
+
     pub fn is_terminal(&self) -> bool {
         matches!(
             self,
@@ -39,6 +83,45 @@ impl SessionStatus {
     }
 }
 
+/// Where a session was observed: on this machine or pulled from a sync peer.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum Origin {
+    #[default]
+    Local,
+    Remote {
+        peer: String,
+    },
+}
+
+impl Origin {
+    pub fn peer(&self) -> Option<&str> {
+        match self {
+            Origin::Local => None,
+            Origin::Remote { peer } => Some(peer),
+        }
+    }
+}
+
+/// Upper bound on `last_lines` kept per event at collection and sync boundaries.
+pub const MAX_LAST_LINES: usize = 8;
+/// Upper bound on characters kept per `last_lines` entry.
+pub const MAX_LINE_CHARS: usize = 240;
+
+impl FromStr for SessionStatus {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "running" => Ok(SessionStatus::Running),
+            "waiting_input" => Ok(SessionStatus::WaitingInput),
+            "success" => Ok(SessionStatus::Success),
+            "failed" => Ok(SessionStatus::Failed),
+            "stopped" => Ok(SessionStatus::Stopped),
+            other => Err(format!("unknown session status: {other}")),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct SessionEvent {
     pub id: String,
@@ -51,6 +134,17 @@ pub struct SessionEvent {
     pub started_at_unix_ms: u64,
     pub updated_at_unix_ms: u64,
     pub last_lines: Vec<String>,
+    /// Set by `IdleTracker` when `last_lines` has not changed for a while.
+    #[serde(default)]
+    pub idle: bool,
+    #[serde(default)]
+    pub origin: Origin,
+    /// Git branch checked out in `working_dir`, or `detached@<short sha>`.
+    #[serde(default)]
+    pub branch: Option<String>,
+    /// Name of the git repository `working_dir` belongs to (its root directory's name).
+    #[serde(default)]
+    pub project: Option<String>,
 }
 
 impl SessionEvent {
@@ -73,18 +167,220 @@ impl SessionEvent {
             started_at_unix_ms: now,
             updated_at_unix_ms: now,
             last_lines,
+            idle: false,
+            origin: Origin::Local,
+            branch: None,
+            project: None,
         }
     }
 
+    /// Keeps only the newest `max_lines` entries of `last_lines`, each cut to `max_len` chars.
+    pub fn truncated(mut self, max_lines: usize, max_len: usize) -> Self {
+        let skip = self.last_lines.len().saturating_sub(max_lines);
+        self.last_lines = self
+            .last_lines
+            .into_iter()
+            .skip(skip)
+            .map(|line| truncate_chars(&line, max_len))
+            .collect();
+        self
+    }
+
+    /// Whether `incoming` may replace this event: it must not be older, and a status
+    /// change must be a legal transition (terminal sessions are never revived).
+    pub fn accepts_update(&self, incoming: &SessionEvent) -> bool {
+        incoming.updated_at_unix_ms >= self.updated_at_unix_ms
+            && (incoming.status == self.status || self.can_transition_to(incoming.status))
+    }
+
     pub fn can_transition_to(&self, next: SessionStatus) -> bool {
-        use SessionStatus::*;
-        match (self.status, next) {
-            (Success, _) | (Failed, _) | (Stopped, _) => false,
-            (Running, Running | WaitingInput | Success | Failed | Stopped) => true,
-            (WaitingInput, WaitingInput | Running | Stopped) => true,
-            _ => false,
+        can_transition(self.status, next)
+    }
+}
+
+/// Whether a session may move from `from` to `to`. Terminal statuses are final, and a
+/// session waiting on the user can only resume, keep waiting, or be stopped.
+pub fn can_transition(from: SessionStatus, to: SessionStatus) -> bool {
+    use SessionStatus::*;
+    match (from, to) {
+        (Success, _) | (Failed, _) | (Stopped, _) => false,
+        (Running, Running | WaitingInput | Success | Failed | Stopped) => true,
+        (WaitingInput, WaitingInput | Running | Stopped) => true,
+        _ => false,
+    }
+}
+
+fn truncate_chars(input: &str, limit: usize) -> String {
+    if input.chars().count() <= limit {
+        return input.to_string();
+    }
+    let mut out: String = input.chars().take(limit.saturating_sub(3)).collect();
+    out.push_str("...");
+    out
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StatusCounts {
+    pub running: usize,
+    pub waiting_input: usize,
+    pub success: usize,
+    pub failed: usize,
+    pub stopped: usize,
+}
+
+impl StatusCounts {
+    pub fn add(&mut self, status: SessionStatus) {
+        match status {
+            SessionStatus::Running => self.running += 1,
+            SessionStatus::WaitingInput => self.waiting_input += 1,
+            SessionStatus::Success => self.success += 1,
+            SessionStatus::Failed => self.failed += 1,
+            SessionStatus::Stopped => self.stopped += 1,
         }
     }
+
+    pub fn total(&self) -> usize {
+        self.running + self.waiting_input + self.success + self.failed + self.stopped
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserAggregate {
+    pub user: String,
+    /// Sorted, de-duplicated hosts; `local` for sessions on this machine.
+    pub hosts: Vec<String>,
+    pub counts: StatusCounts,
+}
+
+/// Groups sessions by user across local and remote hosts, ordered by user.
+pub fn aggregate_by_user(sessions: &[SessionEvent]) -> Vec<UserAggregate> {
+    let mut grouped: BTreeMap<String, UserAggregate> = BTreeMap::new();
+    for event in sessions {
+        let user = event.user.clone();
+        let host = event.origin.peer().unwrap_or("local").to_string();
+        let entry = grouped
+            .entry(user.clone())
+            .or_insert_with(|| UserAggregate {
+                user,
+                hosts: Vec::new(),
+                counts: StatusCounts::default(),
+            });
+        if !entry.hosts.contains(&host) {
+            entry.hosts.push(host);
+        }
+        entry.counts.add(event.status);
+    }
+    grouped
+        .into_values()
+        .map(|mut aggregate| {
+            aggregate.hosts.sort();
+            aggregate
+        })
+        .collect()
+}
+
+/// Sessions of one agent kind dropped by `cap_per_agent`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AgentOverflow {
+    pub agent: AgentKind,
+    pub hidden: usize,
+}
+
+/// Keeps the `max` most recently updated sessions of each agent kind, preserving input
+/// order, and reports how many of each kind were dropped (sorted by agent label).
+pub fn cap_per_agent(
+    sessions: Vec<SessionEvent>,
+    max: usize,
+) -> (Vec<SessionEvent>, Vec<AgentOverflow>) {
+    let mut by_agent: HashMap<AgentKind, Vec<usize>> = HashMap::new();
+    for (idx, event) in sessions.iter().enumerate() {
+        by_agent.entry(event.agent.clone()).or_default().push(idx);
+    }
+
+    let mut dropped = vec![false; sessions.len()];
+    let mut overflow = Vec::new();
+    for (agent, mut indices) in by_agent {
+        if indices.len() <= max {
+            continue;
+        }
+        indices.sort_by(|&a, &b| {
+            let (a, b) = (&sessions[a], &sessions[b]);
+            b.updated_at_unix_ms
+                .cmp(&a.updated_at_unix_ms)
+                .then_with(|| a.id.cmp(&b.id))
+        });
+        for &idx in &indices[max..] {
+            dropped[idx] = true;
+        }
+        overflow.push(AgentOverflow {
+            agent,
+            hidden: indices.len() - max,
+        });
+    }
+    overflow.sort_by(|a, b| a.agent.as_label().cmp(b.agent.as_label()));
+
+    let kept = sessions
+        .into_iter()
+        .zip(dropped)
+        .filter(|(_, dropped)| !dropped)
+        .map(|(event, _)| event)
+        .collect();
+    (kept, overflow)
+}
+
+/// A working directory on one machine with more than one active session in it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    /// `None` for this machine; dirs on different peers never conflict.
+    pub peer: Option<String>,
+    pub working_dir: String,
+    pub session_ids: Vec<String>,
+}
+
+/// Reports directories shared by two or more non-terminal sessions, sorted by peer then
+/// dir, with ids in sorted order. Trailing slashes are ignored when comparing dirs, and
+/// sessions with an unknown (empty) dir never conflict.
+pub fn detect_conflicts(sessions: &[SessionEvent]) -> Vec<Conflict> {
+    let mut by_dir: BTreeMap<(Option<&str>, &str), Vec<String>> = BTreeMap::new();
+    let active = sessions
+        .iter()
+        .filter(|s| !s.status.is_terminal() && !s.working_dir.is_empty());
+    for session in active {
+        let dir = session.working_dir.trim_end_matches('/');
+        let dir = if dir.is_empty() { "/" } else { dir };
+        by_dir
+            .entry((session.origin.peer(), dir))
+            .or_default()
+            .push(session.id.clone());
+    }
+    by_dir
+        .into_iter()
+        .filter(|(_, ids)| ids.len() > 1)
+        .map(|((peer, dir), mut session_ids)| {
+            session_ids.sort();
+            Conflict {
+                peer: peer.map(str::to_string),
+                working_dir: dir.to_string(),
+                session_ids,
+            }
+        })
+        .collect()
+}
+
+/// Sessions blocked on an approval: `WaitingInput` with a `pending_action`, the longest
+/// waiting (oldest `updated_at_unix_ms`) first, ties broken by id.
+pub fn pending_approvals(sessions: &[SessionEvent]) -> Vec<SessionEvent> {
+    let mut pending = sessions
+        .iter()
+        .filter(|s| s.status == SessionStatus::WaitingInput && s.pending_action.is_some())
+        .cloned()
+        .collect::<Vec<_>>();
+    pending.sort_by(|a, b| {
+        a.updated_at_unix_ms
+            .cmp(&b.updated_at_unix_ms)
+            .then_with(|| a.id.cmp(&b.id))
+    });
+    pending
 }
 
 #[derive(Debug, Default)]
@@ -99,10 +395,7 @@ impl RuntimeStateStore {
 
     pub fn upsert(&mut self, incoming: SessionEvent) -> bool {
         if let Some(existing) = self.sessions.get_mut(&incoming.id) {
-            if incoming.updated_at_unix_ms < existing.updated_at_unix_ms {
-                return false;
-            }
-            if !existing.can_transition_to(incoming.status) && incoming.status != existing.status {
+            if !existing.accepts_update(&incoming) {
                 return false;
             }
             *existing = incoming;
@@ -118,9 +411,191 @@ impl RuntimeStateStore {
         items
     }
 
+    /// Every session in id order, leaving the store empty.
+    pub fn drain(&mut self) -> Vec<SessionEvent> {
+        let mut items: Vec<_> = self.sessions.drain().map(|(_, event)| event).collect();
+        items.sort_by(|a, b| a.id.cmp(&b.id));
+        items
+    }
+
     pub fn get(&self, id: &str) -> Option<&SessionEvent> {
         self.sessions.get(id)
     }
+
+    /// Per-status session counts across the whole store.
+    pub fn summary(&self) -> StatusCounts {
+        let mut counts = StatusCounts::default();
+        for event in self.sessions.values() {
+            counts.add(event.status);
+        }
+        counts
+    }
+}
+
+/// Keeps finished sessions on screen for a while after the collector stops reporting
+/// them, so a session that ends between two ticks does not vanish before anyone sees it.
+/// The window runs from the session's own `updated_at_unix_ms`.
+#[derive(Debug, Default)]
+pub struct Linger {
+    window_ms: u64,
+    finished: HashMap<String, SessionEvent>,
+}
+
+impl Linger {
+    pub fn new(window_ms: u64) -> Self {
+        Self {
+            window_ms,
+            finished: HashMap::new(),
+        }
+    }
+
+    /// Remembers terminal sessions in `store` and puts back the ones that dropped out of
+    /// it but are still inside the window at `now`.
+    pub fn apply(&mut self, store: &mut RuntimeStateStore, now: u64) {
+        for session in store.all() {
+            if session.status.is_terminal() {
+                self.finished.insert(session.id.clone(), session);
+            } else {
+                self.finished.remove(&session.id);
+            }
+        }
+        let window_ms = self.window_ms;
+        self.finished
+            .retain(|_, session| now.saturating_sub(session.updated_at_unix_ms) < window_ms);
+        for session in self.finished.values() {
+            if store.get(&session.id).is_none() {
+                store.upsert(session.clone());
+            }
+        }
+    }
+}
+
+/// What happened to a session between two observed snapshots.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Lifecycle {
+    Started,
+    StatusChanged,
+    Ended,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transition {
+    pub lifecycle: Lifecycle,
+    /// Status before the transition; `None` for a session seen for the first time.
+    pub from: Option<SessionStatus>,
+    /// The session as last observed.
+    pub session: SessionEvent,
+}
+
+/// Turns successive store snapshots into lifecycle transitions. A session ends when it
+/// reaches a terminal status or, failing that, when it drops out of the store.
+#[derive(Debug, Default)]
+pub struct TransitionObserver {
+    last: HashMap<String, SessionEvent>,
+}
+
+impl TransitionObserver {
+    pub fn observe(&mut self, store: &RuntimeStateStore) -> Vec<Transition> {
+        let mut transitions = Vec::new();
+        let mut previous = std::mem::take(&mut self.last);
+        for session in store.all() {
+            let from = previous.remove(&session.id).map(|old| old.status);
+            let lifecycle = match from {
+                None => Some(Lifecycle::Started),
+                Some(old) if old == session.status => None,
+                Some(_) if session.status.is_terminal() => Some(Lifecycle::Ended),
+                Some(_) => Some(Lifecycle::StatusChanged),
+            };
+            if let Some(lifecycle) = lifecycle {
+                transitions.push(Transition {
+                    lifecycle,
+                    from,
+                    session: session.clone(),
+                });
+            }
+            self.last.insert(session.id.clone(), session);
+        }
+        let mut vanished: Vec<_> = previous
+            .into_values()
+            .filter(|session| !session.status.is_terminal())
+            .collect();
+        vanished.sort_by(|a, b| a.id.cmp(&b.id));
+        transitions.extend(vanished.into_iter().map(|session| Transition {
+            lifecycle: Lifecycle::Ended,
+            from: Some(session.status),
+            session,
+        }));
+        transitions
+    }
+}
+
+/// Window `ChurnTracker` counts over by default.
+pub const DEFAULT_CHURN_WINDOW_MS: u64 = 60_000;
+
+/// Sessions started and ended within a `ChurnTracker` window.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Churn {
+    pub started: usize,
+    pub ended: usize,
+}
+
+/// Counts session starts and ends from `TransitionObserver` output over a sliding
+/// window, to show how busy a box is.
+#[derive(Debug)]
+pub struct ChurnTracker {
+    window_ms: u64,
+    // (time seen, lifecycle) oldest first; status changes are not kept
+    seen: VecDeque<(u64, Lifecycle)>,
+}
+
+impl Default for ChurnTracker {
+    fn default() -> Self {
+        Self::new(DEFAULT_CHURN_WINDOW_MS)
+    }
+}
+
+impl ChurnTracker {
+    pub fn new(window_ms: u64) -> Self {
+        Self {
+            window_ms,
+            seen: VecDeque::new(),
+        }
+    }
+
+    pub fn record(&mut self, transitions: &[Transition], now: u64) {
+        self.seen.extend(
+            transitions
+                .iter()
+                .filter(|t| t.lifecycle != Lifecycle::StatusChanged)
+                .map(|t| (now, t.lifecycle)),
+        );
+        self.expire(now);
+    }
+
+    /// Starts and ends seen within the window ending at `now`.
+    pub fn counts(&mut self, now: u64) -> Churn {
+        self.expire(now);
+        let mut churn = Churn::default();
+        for (_, lifecycle) in &self.seen {
+            match lifecycle {
+                Lifecycle::Started => churn.started += 1,
+                Lifecycle::Ended => churn.ended += 1,
+                Lifecycle::StatusChanged => {}
+            }
+        }
+        churn
+    }
+
+    fn expire(&mut self, now: u64) {
+        while self
+            .seen
+            .front()
+            .is_some_and(|(at, _)| now.saturating_sub(*at) >= self.window_ms)
+        {
+            self.seen.pop_front();
+        }
+    }
 }
 
 #[cfg(test)]
@@ -139,9 +614,138 @@ mod tests {
             started_at_unix_ms: 1,
             updated_at_unix_ms: ts,
             last_lines: vec!["hello".to_string()],
+            idle: false,
+            origin: Origin::Local,
+            branch: None,
+            project: None,
+        }
+    }
+
+    #[test]
+    fn pending_approvals_lists_waiting_sessions_with_an_action_oldest_first() {
+        let with_action = |id: &str, status, ts| {
+            let mut s = event(id, status, ts);
+            s.pending_action = Some(format!("approve {id}"));
+            s
+        };
+        let sessions = vec![
+            with_action("newer", SessionStatus::WaitingInput, 30),
+            event("no-action", SessionStatus::WaitingInput, 5),
+            with_action("running", SessionStatus::Running, 1),
+            with_action("oldest", SessionStatus::WaitingInput, 10),
+            with_action("done", SessionStatus::Success, 2),
+            with_action("also-oldest", SessionStatus::WaitingInput, 10),
+        ];
+        let ids: Vec<_> = pending_approvals(&sessions).into_iter().map(|s| s.id).collect();
+        assert_eq!(ids, vec!["also-oldest", "oldest", "newer"]);
+    }
+
+    #[test]
+    fn churn_counts_starts_and_ends_inside_the_window() {
+        let mut observer = TransitionObserver::default();
+        let mut churn = ChurnTracker::new(60_000);
+        let mut store = RuntimeStateStore::default();
+        let mut tick = |store: &RuntimeStateStore, now| {
+            let transitions = observer.observe(store);
+            churn.record(&transitions, now);
+            churn.counts(now)
+        };
+
+        store.upsert(event("a", SessionStatus::Running, 1));
+        store.upsert(event("b", SessionStatus::Running, 1));
+        assert_eq!(tick(&store, 0), Churn { started: 2, ended: 0 });
+
+        // A status change is neither a start nor an end.
+        store.clear();
+        store.upsert(event("a", SessionStatus::WaitingInput, 2));
+        store.upsert(event("b", SessionStatus::Running, 2));
+        store.upsert(event("c", SessionStatus::Running, 2));
+        assert_eq!(tick(&store, 20_000), Churn { started: 3, ended: 0 });
+
+        // `a` finishes and `b` vanishes.
+        store.clear();
+        store.upsert(event("a", SessionStatus::Success, 3));
+        store.upsert(event("c", SessionStatus::Running, 3));
+        assert_eq!(tick(&store, 45_000), Churn { started: 3, ended: 2 });
+
+        // The first two starts fall out of the window, then the third.
+        assert_eq!(tick(&store, 60_000), Churn { started: 1, ended: 2 });
+        assert_eq!(tick(&store, 80_000), Churn { started: 0, ended: 2 });
+        assert_eq!(tick(&store, 105_000), Churn::default());
+    }
+
+    #[test]
+    fn finished_sessions_linger_for_the_window_then_go() {
+        let mut linger = Linger::new(5_000);
+        let mut store = RuntimeStateStore::default();
+        store.upsert(event("done", SessionStatus::Success, 1_000));
+        store.upsert(event("busy", SessionStatus::Running, 1_000));
+        linger.apply(&mut store, 1_000);
+
+        // The collector no longer reports either session.
+        store.clear();
+        linger.apply(&mut store, 4_000);
+        let ids: Vec<_> = store.all().into_iter().map(|s| s.id).collect();
+        assert_eq!(ids, vec!["done".to_string()]);
+        assert_eq!(store.get("done").unwrap().status, SessionStatus::Success);
+
+        store.clear();
+        linger.apply(&mut store, 6_000);
+        assert!(store.all().is_empty());
+    }
+
+    #[test]
+    fn a_restarted_session_is_not_held_back_by_linger() {
+        let mut linger = Linger::new(5_000);
+        let mut store = RuntimeStateStore::default();
+        store.upsert(event("s", SessionStatus::Failed, 1_000));
+        linger.apply(&mut store, 1_000);
+        store.clear();
+        store.upsert(event("s", SessionStatus::Running, 2_000));
+        linger.apply(&mut store, 2_000);
+        store.clear();
+        linger.apply(&mut store, 3_000);
+        assert!(store.all().is_empty());
+    }
+
+    #[test]
+    fn agent_labels_round_trip() {
+        for kind in [
+            AgentKind::Claude,
+            AgentKind::Codex,
+            AgentKind::Gemini,
+            AgentKind::Copilot,
+            AgentKind::Cody,
+            AgentKind::Unknown,
+        ] {
+            assert_eq!(kind.as_label().parse::<AgentKind>(), Ok(kind));
         }
     }
 
+    #[test]
+    fn aggregates_same_user_across_peers() {
+        let local = event("a", SessionStatus::Running, 1);
+        let mut remote_a = event("remote:peer-a:b", SessionStatus::Running, 1);
+        remote_a.origin = Origin::Remote {
+            peer: "peer-a".to_string(),
+        };
+        let mut remote_b = event("remote:peer-a:c", SessionStatus::WaitingInput, 1);
+        remote_b.origin = remote_a.origin.clone();
+        let mut other = event("d", SessionStatus::Failed, 1);
+        other.user = "bob".to_string();
+
+        let aggregates = aggregate_by_user(&[local, remote_a, remote_b, other]);
+        assert_eq!(aggregates.len(), 2);
+        let alice = &aggregates[0];
+        assert_eq!(alice.user, "alice");
+        assert_eq!(alice.hosts, vec!["local".to_string(), "peer-a".to_string()]);
+        assert_eq!(alice.counts.running, 2);
+        assert_eq!(alice.counts.waiting_input, 1);
+        assert_eq!(alice.counts.total(), 3);
+        assert_eq!(aggregates[1].user, "bob");
+        assert_eq!(aggregates[1].counts.failed, 1);
+    }
+
     #[test]
     fn rejects_older_updates() {
         let mut store = RuntimeStateStore::default();
@@ -149,11 +753,149 @@ mod tests {
         assert!(!store.upsert(event("a", SessionStatus::Running, 19)));
     }
 
+    #[test]
+    fn truncated_caps_line_count_and_length() {
+        let mut e = event("a", SessionStatus::Running, 1);
+        e.last_lines = (0..20).map(|i| format!("{i}:{}", "x".repeat(500))).collect();
+        let capped = e.truncated(MAX_LAST_LINES, MAX_LINE_CHARS);
+        assert_eq!(capped.last_lines.len(), MAX_LAST_LINES);
+        assert!(capped.last_lines[0].starts_with("12:"));
+        assert!(capped
+            .last_lines
+            .iter()
+            .all(|l| l.chars().count() <= MAX_LINE_CHARS));
+    }
+
     #[test]
     fn rejects_invalid_transition_from_terminal_state() {
         let mut store = RuntimeStateStore::default();
         assert!(store.upsert(event("a", SessionStatus::Success, 20)));
         assert!(!store.upsert(event("a", SessionStatus::Running, 21)));
     }
-}
 
+    #[test]
+    fn can_transition_matches_the_event_method() {
+        use SessionStatus::*;
+        let all = [Running, WaitingInput, Success, Failed, Stopped];
+        for from in all {
+            for to in all {
+                assert_eq!(
+                    can_transition(from, to),
+                    event("a", from, 1).can_transition_to(to),
+                    "{from:?} -> {to:?}"
+                );
+            }
+        }
+        assert!(can_transition(Running, WaitingInput));
+        assert!(can_transition(WaitingInput, Running));
+        assert!(!can_transition(WaitingInput, Success));
+        assert!(!can_transition(Success, Running));
+        assert!(!can_transition(Stopped, Stopped));
+    }
+
+    #[test]
+    fn cap_per_agent_keeps_newest_and_leaves_other_agents_alone() {
+        let mut sessions = (0..50)
+            .map(|i| {
+                let mut e = event(&format!("g{i:02}"), SessionStatus::Running, i);
+                e.agent = AgentKind::Gemini;
+                e
+            })
+            .collect::<Vec<_>>();
+        sessions.push(event("c1", SessionStatus::Running, 1));
+        sessions.push(event("c2", SessionStatus::Running, 2));
+
+        let (kept, overflow) = cap_per_agent(sessions, 3);
+        let gemini = kept
+            .iter()
+            .filter(|e| e.agent == AgentKind::Gemini)
+            .map(|e| e.id.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(gemini, ["g47", "g48", "g49"]);
+        assert_eq!(
+            overflow,
+            vec![AgentOverflow {
+                agent: AgentKind::Gemini,
+                hidden: 47
+            }]
+        );
+        assert!(kept.iter().any(|e| e.id == "c1"));
+        assert!(kept.iter().any(|e| e.id == "c2"));
+    }
+
+    #[test]
+    fn detect_conflicts_flags_shared_dirs_of_active_sessions() {
+        let mut a = event("a", SessionStatus::Running, 1);
+        a.working_dir = "/work/repo".to_string();
+        let mut b = event("b", SessionStatus::Running, 2);
+        b.working_dir = "/work/repo/".to_string();
+        let mut done = event("c", SessionStatus::Success, 3);
+        done.working_dir = "/work/repo".to_string();
+
+        assert_eq!(
+            detect_conflicts(&[b.clone(), a.clone(), done.clone()]),
+            vec![Conflict {
+                peer: None,
+                working_dir: "/work/repo".to_string(),
+                session_ids: vec!["a".to_string(), "b".to_string()],
+            }]
+        );
+
+        b.working_dir = "/work/other".to_string();
+        assert!(detect_conflicts(&[a.clone(), b, done]).is_empty());
+
+        let mut remote = a.clone();
+        remote.id = "peer:a".to_string();
+        remote.origin = Origin::Remote {
+            peer: "build-box".to_string(),
+        };
+        assert!(detect_conflicts(&[a, remote]).is_empty());
+    }
+
+    #[test]
+    fn observer_reports_start_status_change_and_end() {
+        let mut observer = TransitionObserver::default();
+        let mut store = RuntimeStateStore::default();
+        store.upsert(event("a", SessionStatus::Running, 1));
+        store.upsert(event("b", SessionStatus::Running, 1));
+        let started = observer.observe(&store);
+        assert_eq!(started.len(), 2);
+        assert!(started.iter().all(|t| t.lifecycle == Lifecycle::Started && t.from.is_none()));
+        assert!(observer.observe(&store).is_empty());
+
+        store.clear();
+        store.upsert(event("a", SessionStatus::WaitingInput, 2));
+        let changed = observer.observe(&store);
+        let summary: Vec<_> = changed
+            .iter()
+            .map(|t| (t.session.id.as_str(), t.lifecycle, t.from))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("a", Lifecycle::StatusChanged, Some(SessionStatus::Running)),
+                ("b", Lifecycle::Ended, Some(SessionStatus::Running)),
+            ]
+        );
+
+        store.clear();
+        store.upsert(event("a", SessionStatus::Success, 3));
+        let ended = observer.observe(&store);
+        assert_eq!(ended[0].lifecycle, Lifecycle::Ended);
+        store.clear();
+        assert!(observer.observe(&store).is_empty(), "finished sessions end only once");
+    }
+
+    #[test]
+    fn drain_returns_sorted_sessions_and_empties_store() {
+        let mut store = RuntimeStateStore::default();
+        store.upsert(event("b", SessionStatus::Running, 1));
+        store.upsert(event("a", SessionStatus::WaitingInput, 1));
+        let expected = store.all();
+        let drained = store.drain();
+        assert_eq!(drained, expected);
+        assert_eq!(drained[0].id, "a");
+        assert!(store.all().is_empty());
+        assert_eq!(store.summary().total(), 0);
+    }
+}
@@ -1,7 +1,151 @@
-use crate::model::{AgentKind, SessionEvent, SessionStatus};
+use std::collections::{BTreeMap, HashSet};
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
 
+use crate::model::{
+    AgentKind, AgentOverflow, Conflict, Origin, SessionEvent, SessionStatus, UserAggregate,
+};
+
+#[derive(Debug, Default)]
+pub struct TerminalRenderer {
+    hide_done: bool,
+    theme: Theme,
+    home_dir: Option<String>,
+    width: Option<usize>,
+    max_width: Option<usize>,
+    page: usize,
+    per_page: Option<usize>,
+    group_by_project: bool,
+    now_ms: Option<u64>,
+    conflicted: HashSet<String>,
+    pinned: Vec<String>,
+    deterministic: bool,
+    idle_peers: Vec<String>,
+    peer_order: Vec<String>,
+}
+
+/// How the live view takes over the terminal between ticks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScreenMode {
+    /// Clear the visible screen each tick (original behavior).
+    Clear,
+    /// Draw on the alternate screen buffer so the user's scrollback survives.
+    AltScreen,
+    /// Emit no control sequences; frames are appended (non-TTY or `--no-clear`).
+    Append,
+}
+
+impl ScreenMode {
+    pub fn from_flags(alt_screen: bool, no_clear: bool, is_tty: bool) -> Self {
+        if no_clear || !is_tty {
+            ScreenMode::Append
+        } else if alt_screen {
+            ScreenMode::AltScreen
+        } else {
+            ScreenMode::Clear
+        }
+    }
+
+    pub fn enter_sequence(&self) -> &'static str {
+        match self {
+            ScreenMode::AltScreen => "\x1b[?1049h",
+            ScreenMode::Clear | ScreenMode::Append => "",
+        }
+    }
+
+    pub fn exit_sequence(&self) -> &'static str {
+        match self {
+            ScreenMode::AltScreen => "\x1b[?1049l",
+            ScreenMode::Clear | ScreenMode::Append => "",
+        }
+    }
+
+    pub fn frame_prefix(&self) -> &'static str {
+        match self {
+            ScreenMode::Clear | ScreenMode::AltScreen => "\x1b[2J\x1b[H",
+            ScreenMode::Append => "",
+        }
+    }
+
+    /// A whole frame as one string: `frame_prefix` followed by every block.
+    pub fn compose(&self, blocks: &[FrameBlock]) -> String {
+        let mut frame = self.frame_prefix().to_string();
+        for block in blocks {
+            frame.push_str(&block.text);
+        }
+        frame
+    }
+}
+
+/// Writes a complete frame with a single `write_all` and flushes it, so the terminal is
+/// never left showing half of a large snapshot between two writes.
+pub fn write_frame(out: &mut dyn Write, frame: &str) -> io::Result<()> {
+    out.write_all(frame.as_bytes())?;
+    out.flush()
+}
+
+/// A keyed run of newline-terminated lines: a session card (keyed by its id) or a fixed
+/// part of the view such as the banner.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameBlock {
+    pub key: String,
+    pub text: String,
+}
+
+impl FrameBlock {
+    pub fn new(key: impl Into<String>, text: impl Into<String>) -> Self {
+        Self {
+            key: key.into(),
+            text: text.into(),
+        }
+    }
+
+    fn rows(&self) -> impl Iterator<Item = &str> {
+        self.text.split_terminator('\n')
+    }
+}
+
+/// Draws frames of `FrameBlock`s, rewriting only the blocks whose text changed since the
+/// previous frame in place. When blocks appear, vanish, move or change height the whole
+/// screen is redrawn, so every block keeps a stable row while the layout holds.
 #[derive(Debug, Default)]
-pub struct TerminalRenderer;
+pub struct IncrementalScreen {
+    previous: Vec<FrameBlock>,
+}
+
+impl IncrementalScreen {
+    pub fn update(&mut self, blocks: Vec<FrameBlock>) -> String {
+        let same_layout = self.previous.len() == blocks.len()
+            && self.previous.iter().zip(&blocks).all(|(old, new)| {
+                old.key == new.key && old.rows().count() == new.rows().count()
+            });
+        let mut out = String::new();
+        if same_layout {
+            let mut row = 1;
+            for (old, new) in self.previous.iter().zip(&blocks) {
+                if old.text != new.text {
+                    for (offset, line) in new.rows().enumerate() {
+                        out.push_str(&format!("\x1b[{};1H{line}\x1b[K", row + offset));
+                    }
+                }
+                row += new.rows().count();
+            }
+            out.push_str(&format!("\x1b[{row};1H"));
+        } else {
+            out.push_str("\x1b[2J\x1b[H");
+            for block in &blocks {
+                out.push_str(&block.text);
+            }
+        }
+        self.previous = blocks;
+        out
+    }
+
+    /// Forces the next `update` to redraw the whole screen, e.g. after a resize reflowed it.
+    pub fn invalidate(&mut self) {
+        self.previous.clear();
+    }
+}
 
 const ANSI_RESET: &str = "\x1b[0m";
 const ANSI_BOLD: &str = "\x1b[1m";
@@ -12,14 +156,431 @@ const ANSI_GREEN: &str = "\x1b[32m";
 const ANSI_RED: &str = "\x1b[31m";
 const ANSI_CYAN: &str = "\x1b[36m";
 const ANSI_BLACK: &str = "\x1b[30m";
+const ANSI_WHITE: &str = "\x1b[97m";
 const ANSI_BG_ORANGE: &str = "\x1b[48;5;208m";
 const ANSI_BG_BLUE: &str = "\x1b[44m";
+const ANSI_BG_MAGENTA: &str = "\x1b[45m";
+const ANSI_BG_CYAN: &str = "\x1b[46m";
 const ANSI_BG_WHITE: &str = "\x1b[47m";
 const ANSI_BG_GRAY: &str = "\x1b[100m";
 
+/// Icon and title background for one custom agent label.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AgentStyle {
+    pub icon: String,
+    pub bg: String,
+}
+
+const CUSTOM_ICON: &str = "◇";
+
+/// Animation shown next to running sessions; styles differ in how many frames they cycle.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SpinnerStyle {
+    #[default]
+    QuadrantCircle,
+    Braille,
+    /// Plain `|/-\` for terminals whose fonts lack the other glyphs.
+    Ascii,
+    Dots,
+}
+
+impl SpinnerStyle {
+    pub fn frames(self) -> &'static [&'static str] {
+        match self {
+            SpinnerStyle::QuadrantCircle => &["◴", "◷", "◶", "◵"],
+            SpinnerStyle::Braille => &["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"],
+            SpinnerStyle::Ascii => &["|", "/", "-", "\\"],
+            SpinnerStyle::Dots => &["·", "•", "●"],
+        }
+    }
+
+    pub fn frame(self, frame: usize) -> &'static str {
+        let frames = self.frames();
+        frames[frame % frames.len()]
+    }
+}
+
+/// Color palette for session cards. Every field is a raw ANSI sequence; an empty
+/// string means "leave the terminal default".
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Theme {
+    pub title_fg: &'static str,
+    pub muted: &'static str,
+    pub accent: &'static str,
+    pub claude_bg: &'static str,
+    pub codex_bg: &'static str,
+    pub gemini_bg: &'static str,
+    pub copilot_bg: &'static str,
+    pub cody_bg: &'static str,
+    pub unknown_bg: &'static str,
+    pub running: &'static str,
+    pub waiting: &'static str,
+    pub success: &'static str,
+    pub failed: &'static str,
+    pub stopped: &'static str,
+    /// Backgrounds handed out to custom agents without a registered style, by label hash.
+    pub custom_palette: &'static [&'static str],
+    /// Registered styles for custom agent labels.
+    pub custom_agents: BTreeMap<String, AgentStyle>,
+    /// Without an update for this long a card's title is dimmed; `None` never fades.
+    pub stale_after_ms: Option<u64>,
+    /// Without an update for this long its status line is dimmed as well.
+    pub old_after_ms: Option<u64>,
+    pub spinner: SpinnerStyle,
+}
+
+const STALE_AFTER_MS: u64 = 5 * 60 * 1000;
+const OLD_AFTER_MS: u64 = 30 * 60 * 1000;
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::dark()
+    }
+}
+
+impl Theme {
+    pub fn dark() -> Self {
+        Self {
+            title_fg: ANSI_BLACK,
+            muted: ANSI_GRAY,
+            accent: ANSI_CYAN,
+            claude_bg: ANSI_BG_ORANGE,
+            codex_bg: ANSI_BG_WHITE,
+            gemini_bg: ANSI_BG_BLUE,
+            copilot_bg: ANSI_BG_MAGENTA,
+            cody_bg: ANSI_BG_CYAN,
+            unknown_bg: ANSI_BG_GRAY,
+            running: ANSI_CYAN,
+            waiting: ANSI_ORANGE,
+            success: ANSI_GREEN,
+            failed: ANSI_RED,
+            stopped: ANSI_GRAY,
+            custom_palette: &[
+                "\x1b[48;5;172m",
+                "\x1b[48;5;71m",
+                "\x1b[48;5;68m",
+                "\x1b[48;5;133m",
+                "\x1b[48;5;37m",
+                "\x1b[48;5;179m",
+                "\x1b[48;5;97m",
+                "\x1b[48;5;110m",
+            ],
+            custom_agents: BTreeMap::new(),
+            stale_after_ms: Some(STALE_AFTER_MS),
+            old_after_ms: Some(OLD_AFTER_MS),
+            spinner: SpinnerStyle::QuadrantCircle,
+        }
+    }
+
+    /// Deeper backgrounds with white titles so cards stay legible on light terminals.
+    pub fn light() -> Self {
+        Self {
+            title_fg: ANSI_WHITE,
+            muted: "\x1b[38;5;240m",
+            accent: "\x1b[38;5;25m",
+            claude_bg: "\x1b[48;5;166m",
+            codex_bg: "\x1b[48;5;238m",
+            gemini_bg: "\x1b[48;5;25m",
+            copilot_bg: "\x1b[48;5;90m",
+            cody_bg: "\x1b[48;5;30m",
+            unknown_bg: "\x1b[48;5;244m",
+            running: "\x1b[38;5;25m",
+            waiting: "\x1b[38;5;166m",
+            success: "\x1b[38;5;28m",
+            failed: "\x1b[38;5;160m",
+            stopped: "\x1b[38;5;240m",
+            custom_palette: &[
+                "\x1b[48;5;130m",
+                "\x1b[48;5;28m",
+                "\x1b[48;5;24m",
+                "\x1b[48;5;89m",
+                "\x1b[48;5;23m",
+                "\x1b[48;5;94m",
+                "\x1b[48;5;54m",
+                "\x1b[48;5;31m",
+            ],
+            custom_agents: BTreeMap::new(),
+            stale_after_ms: Some(STALE_AFTER_MS),
+            old_after_ms: Some(OLD_AFTER_MS),
+            spinner: SpinnerStyle::QuadrantCircle,
+        }
+    }
+
+    pub fn with_spinner(mut self, spinner: SpinnerStyle) -> Self {
+        self.spinner = spinner;
+        self
+    }
+
+    /// No colors or backgrounds at all; only bold/dim emphasis remains.
+    pub fn mono() -> Self {
+        Self {
+            title_fg: "",
+            muted: "",
+            accent: "",
+            claude_bg: "",
+            codex_bg: "",
+            gemini_bg: "",
+            copilot_bg: "",
+            cody_bg: "",
+            unknown_bg: "",
+            running: "",
+            waiting: "",
+            success: "",
+            failed: "",
+            stopped: "",
+            custom_palette: &[],
+            custom_agents: BTreeMap::new(),
+            stale_after_ms: Some(STALE_AFTER_MS),
+            old_after_ms: Some(OLD_AFTER_MS),
+            spinner: SpinnerStyle::QuadrantCircle,
+        }
+    }
+
+    /// Fade thresholds for cards by time since their last update (see `with_now`).
+    pub fn with_age_fading(
+        mut self,
+        stale_after_ms: Option<u64>,
+        old_after_ms: Option<u64>,
+    ) -> Self {
+        self.stale_after_ms = stale_after_ms;
+        self.old_after_ms = old_after_ms;
+        self
+    }
+
+    /// Registers (or replaces) the icon and background used for a custom agent label.
+    pub fn with_custom_agent(mut self, label: &str, style: AgentStyle) -> Self {
+        self.custom_agents.insert(label.to_string(), style);
+        self
+    }
+
+    pub fn bg_for_agent(&self, agent: &AgentKind) -> &str {
+        match agent {
+            AgentKind::Claude => self.claude_bg,
+            AgentKind::Codex => self.codex_bg,
+            AgentKind::Gemini => self.gemini_bg,
+            AgentKind::Copilot => self.copilot_bg,
+            AgentKind::Cody => self.cody_bg,
+            AgentKind::Unknown => self.unknown_bg,
+            AgentKind::Custom(label) => match self.custom_agents.get(label) {
+                Some(style) => &style.bg,
+                None if self.custom_palette.is_empty() => "",
+                None => {
+                    let idx = label_hash(label) % self.custom_palette.len() as u64;
+                    self.custom_palette[idx as usize]
+                }
+            },
+        }
+    }
+
+    pub fn icon_for_agent(&self, agent: &AgentKind) -> &str {
+        match agent {
+            AgentKind::Custom(label) => self
+                .custom_agents
+                .get(label)
+                .map(|style| style.icon.as_str())
+                .unwrap_or(CUSTOM_ICON),
+            builtin => agent_icon(builtin),
+        }
+    }
+
+    pub fn color_for_status(&self, status: SessionStatus) -> &'static str {
+        match status {
+            SessionStatus::Running => self.running,
+            SessionStatus::WaitingInput => self.waiting,
+            SessionStatus::Success => self.success,
+            SessionStatus::Failed => self.failed,
+            SessionStatus::Stopped => self.stopped,
+        }
+    }
+}
+
 impl TerminalRenderer {
     pub fn new() -> Self {
-        Self
+        Self::default()
+    }
+
+    /// Skip Success/Failed/Stopped sessions when rendering; the store still keeps them.
+    pub fn with_hide_done(mut self, hide_done: bool) -> Self {
+        self.hide_done = hide_done;
+        self
+    }
+
+    pub fn with_theme(mut self, theme: Theme) -> Self {
+        self.theme = theme;
+        self
+    }
+
+    /// Current time, so cards can fade with age; without it every card renders fresh.
+    pub fn with_now(mut self, now_ms: Option<u64>) -> Self {
+        self.now_ms = now_ms;
+        self
+    }
+
+    /// Sessions named in `conflicts` get a shared-dir badge on their dir line.
+    pub fn with_conflicts(mut self, conflicts: &[Conflict]) -> Self {
+        self.conflicted = conflicts
+            .iter()
+            .flat_map(|c| c.session_ids.iter().cloned())
+            .collect();
+        self
+    }
+
+    /// Abbreviate local working directories under `home` to `~/...`.
+    pub fn with_home_dir(mut self, home: Option<String>) -> Self {
+        self.home_dir = home.filter(|h| !h.trim().is_empty());
+        self
+    }
+
+    /// Detected terminal width; lines are clipped to it (see `fit_width`). A zero width
+    /// counts as `MIN_WIDTH`.
+    pub fn with_width(mut self, width: Option<usize>) -> Self {
+        self.width = width;
+        self
+    }
+
+    /// If `resized` is set (e.g. by SIGWINCH), clears it and re-reads the width from `size`.
+    /// Returns whether the width changed.
+    pub fn refresh_width(
+        &mut self,
+        resized: &AtomicBool,
+        size: impl FnOnce() -> Option<usize>,
+    ) -> bool {
+        if !resized.swap(false, Ordering::Relaxed) {
+            return false;
+        }
+        let width = size();
+        let changed = width != self.width;
+        self.width = width;
+        changed
+    }
+
+    /// Hard cap on line width that applies even when the terminal is wider.
+    pub fn with_max_width(mut self, max_width: Option<usize>) -> Self {
+        self.max_width = max_width;
+        self
+    }
+
+    /// Group cards under `── PROJECT <name> ──` dividers instead of LOCAL/REMOTE.
+    pub fn with_group_by_project(mut self, group_by_project: bool) -> Self {
+        self.group_by_project = group_by_project;
+        self
+    }
+
+    pub fn groups_by_project(&self) -> bool {
+        self.group_by_project
+    }
+
+    /// Freezes the running spinner and turns off age fading, so cards depend only on the
+    /// sessions; for snapshot tests and screenshots.
+    pub fn with_deterministic(mut self, deterministic: bool) -> Self {
+        self.deterministic = deterministic;
+        self
+    }
+
+    /// Session ids shown before all others, in this order; ids not present are ignored.
+    pub fn with_pinned(mut self, ids: Vec<String>) -> Self {
+        self.pinned = ids;
+        self
+    }
+
+    /// Order of `REMOTE <peer>` sections, e.g. by when each peer was first seen. Peers
+    /// not listed follow in name order.
+    pub fn with_peer_order(mut self, peers: Vec<String>) -> Self {
+        self.peer_order = peers;
+        self
+    }
+
+    /// Sort key placing local sessions first, then peers as `with_peer_order` asks.
+    fn peer_rank<'a>(&self, session: &'a SessionEvent) -> Option<(usize, &'a str)> {
+        session.origin.peer().map(|peer| {
+            let rank = self.peer_order.iter().position(|p| p == peer);
+            (rank.unwrap_or(usize::MAX), peer)
+        })
+    }
+
+    /// Peers that answer sync but have no sessions; listed under the view, so an idle but
+    /// connected setup does not look like a broken one.
+    pub fn with_idle_peers(mut self, peers: Vec<String>) -> Self {
+        self.idle_peers = peers;
+        self
+    }
+
+    pub fn idle_peers(&self) -> &[String] {
+        &self.idle_peers
+    }
+
+    /// Show only the 1-based `page` of `per_page` sessions; `None` shows every session.
+    pub fn with_paging(mut self, page: usize, per_page: Option<usize>) -> Self {
+        self.page = page.max(1);
+        self.per_page = per_page.filter(|&n| n > 0);
+        self
+    }
+
+    /// The visible sessions on the current page, in display order (pinned ids first, then
+    /// local before remote by peer; by project when grouping by project), plus a
+    /// `(showing 1-8 of 15, use --page 2)` footer when some are off-page. A page past the
+    /// end shows the last one. Sections keep this order, so pins lead their own section.
+    pub fn paginate(&self, sessions: Vec<SessionEvent>) -> (Vec<SessionEvent>, Option<String>) {
+        let mut visible = sessions
+            .into_iter()
+            .filter(|s| self.is_visible(s))
+            .collect::<Vec<_>>();
+        if !self.pinned.is_empty() {
+            visible.sort_by_key(|s| {
+                self.pinned
+                    .iter()
+                    .position(|id| *id == s.id)
+                    .unwrap_or(usize::MAX)
+            });
+        }
+        let total = visible.len();
+        let Some(per_page) = self.per_page.filter(|&n| total > n) else {
+            return (visible, None);
+        };
+        if self.group_by_project {
+            visible.sort_by(|a, b| project_order(a).cmp(&project_order(b)));
+        } else {
+            visible.sort_by(|a, b| self.peer_rank(a).cmp(&self.peer_rank(b)));
+        }
+        let pages = total.div_ceil(per_page);
+        let page = self.page.min(pages);
+        let start = (page - 1) * per_page;
+        let end = (start + per_page).min(total);
+        let hint = if page < pages {
+            format!(", use --page {}", page + 1)
+        } else {
+            String::new()
+        };
+        let footer = self.fit(format!(
+            "{}(showing {}-{end} of {total}{hint}){ANSI_RESET}",
+            self.theme.muted,
+            start + 1
+        ));
+        let shown = visible.drain(start..end).collect();
+        (shown, Some(footer))
+    }
+
+    fn line_width(&self) -> Option<usize> {
+        let width = self.width.map(|width| if width == 0 { MIN_WIDTH } else { width });
+        match (width, self.max_width) {
+            (Some(width), Some(max)) => Some(width.min(max)),
+            (width, max) => width.or(max),
+        }
+    }
+
+    fn fit(&self, text: String) -> String {
+        match self.line_width() {
+            Some(width) => text
+                .lines()
+                .map(|line| clip_visible(line, width))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            None => text,
+        }
+    }
+
+    pub fn is_visible(&self, s: &SessionEvent) -> bool {
+        !(self.hide_done && s.status.is_terminal())
     }
 
     pub fn render_many(&self, sessions: Vec<SessionEvent>) -> String {
@@ -29,60 +590,313 @@ impl TerminalRenderer {
     pub fn render_many_with_frame(&self, sessions: Vec<SessionEvent>, frame: usize) -> String {
         sessions
             .iter()
+            .filter(|s| self.is_visible(s))
             .map(|s| self.render_session_with_frame(s, frame))
             .collect::<Vec<_>>()
             .join("\n\n")
     }
 
+    /// The flat card list as one block per session id, separated by blank lines, so
+    /// the concatenated text is `render_many_with_frame` plus a final newline.
+    pub fn render_blocks(&self, sessions: Vec<SessionEvent>, frame: usize) -> Vec<FrameBlock> {
+        sessions
+            .iter()
+            .filter(|s| self.is_visible(s))
+            .enumerate()
+            .map(|(i, s)| {
+                let gap = if i == 0 { "" } else { "\n" };
+                let card = self.render_session_with_frame(s, frame);
+                FrameBlock::new(s.id.clone(), format!("{gap}{card}\n"))
+            })
+            .collect()
+    }
+
+    /// Like `render_many_with_frame`, but under `── LOCAL ──` / `── REMOTE <peer> ──`
+    /// dividers: local sessions first, then remote ones grouped by peer name.
+    pub fn render_many_sectioned(&self, sessions: Vec<SessionEvent>, frame: usize) -> String {
+        let mut local = Vec::new();
+        let mut remotes: BTreeMap<String, Vec<SessionEvent>> = BTreeMap::new();
+        for s in sessions.into_iter().filter(|s| self.is_visible(s)) {
+            match &s.origin {
+                Origin::Local => local.push(s),
+                Origin::Remote { peer } => remotes.entry(peer.clone()).or_default().push(s),
+            }
+        }
+
+        let mut sections = Vec::new();
+        if !local.is_empty() {
+            sections.push((self.divider("LOCAL"), local));
+        }
+        let mut remotes = remotes.into_iter().collect::<Vec<_>>();
+        remotes.sort_by_key(|(peer, _)| {
+            self.peer_order
+                .iter()
+                .position(|p| p == peer)
+                .unwrap_or(usize::MAX)
+        });
+        for (peer, group) in remotes {
+            sections.push((self.divider(&format!("REMOTE {}", truncate(&peer, 32))), group));
+        }
+        sections
+            .into_iter()
+            .map(|(divider, group)| {
+                format!("{divider}\n{}", self.render_many_with_frame(group, frame))
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+
+    /// Like `render_many_sectioned`, but one `── PROJECT <name> ──` section per git
+    /// project in name order, with sessions outside any repository last.
+    pub fn render_many_by_project(&self, sessions: Vec<SessionEvent>, frame: usize) -> String {
+        let mut groups: BTreeMap<(bool, String), Vec<SessionEvent>> = BTreeMap::new();
+        for s in sessions.into_iter().filter(|s| self.is_visible(s)) {
+            let (missing, name) = project_order(&s);
+            groups.entry((missing, name.to_string())).or_default().push(s);
+        }
+        groups
+            .into_iter()
+            .map(|((missing, name), group)| {
+                let label = if missing {
+                    "NO PROJECT".to_string()
+                } else {
+                    format!("PROJECT {}", truncate(&name, 32))
+                };
+                format!(
+                    "{}\n{}",
+                    self.divider(&label),
+                    self.render_many_with_frame(group, frame)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+
+    fn divider(&self, label: &str) -> String {
+        self.fit(format!("{ANSI_BOLD}{}── {label} ──{ANSI_RESET}", self.theme.muted))
+    }
+
+    /// One muted `+N more <agent> sessions` line per agent trimmed by `cap_per_agent`.
+    pub fn render_overflow(&self, overflow: &[AgentOverflow]) -> String {
+        let muted = self.theme.muted;
+        overflow
+            .iter()
+            .map(|o| {
+                let noun = if o.hidden == 1 { "session" } else { "sessions" };
+                self.fit(format!(
+                    "{muted}+{} more {} {noun}{ANSI_RESET}",
+                    o.hidden,
+                    o.agent.as_label()
+                ))
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Muted `N hidden` line for local sessions dropped by caps or exclusion filters.
+    pub fn render_hidden(&self, hidden: usize) -> String {
+        self.fit(format!("{}{hidden} hidden{ANSI_RESET}", self.theme.muted))
+    }
+
+    /// One dim `⟿ peer · connected, no sessions` line per peer that answers but is idle.
+    pub fn render_idle_peers(&self, peers: &[String]) -> String {
+        let muted = self.theme.muted;
+        peers
+            .iter()
+            .map(|peer| {
+                self.fit(format!(
+                    "{ANSI_DIM}{muted}⟿ {} · connected, no sessions{ANSI_RESET}",
+                    truncate(peer, 32)
+                ))
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    pub fn render_user_aggregates(&self, aggregates: &[UserAggregate]) -> String {
+        aggregates
+            .iter()
+            .map(|a| self.render_user_aggregate(a))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    pub fn render_user_aggregate(&self, a: &UserAggregate) -> String {
+        let t = &self.theme;
+        let muted = t.muted;
+        let parts = [
+            (a.counts.running, "running", t.running),
+            (a.counts.waiting_input, "waiting", t.waiting),
+            (a.counts.success, "done", t.success),
+            (a.counts.failed, "failed", t.failed),
+            (a.counts.stopped, "stopped", t.stopped),
+        ]
+        .iter()
+        .filter(|(count, _, _)| *count > 0)
+        .map(|(count, label, color)| format!("{color}{count} {label}{ANSI_RESET}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+        let host_word = if a.hosts.len() == 1 { "host" } else { "hosts" };
+        self.fit(format!(
+            "{ANSI_BOLD}{}{ANSI_RESET} has {parts} {muted}across {} {host_word}{ANSI_RESET}",
+            truncate(&a.user, 20),
+            a.hosts.len()
+        ))
+    }
+
     pub fn render_session(&self, s: &SessionEvent) -> String {
         self.render_session_with_frame(s, 0)
     }
 
     pub fn render_session_with_frame(&self, s: &SessionEvent, frame: usize) -> String {
-        let title_bg = bg_for_agent(s.agent);
-        let status_color = color_for_status(s.status);
-        let icon = agent_icon(s.agent);
-        let status_icon = status_icon(s.status, frame);
+        let title_bg = self.theme.bg_for_agent(&s.agent);
+        let title_fg = self.theme.title_fg;
+        let muted = self.theme.muted;
+        let accent = self.theme.accent;
+        let status_color = self.theme.color_for_status(s.status);
+        let icon = self.theme.icon_for_agent(&s.agent);
+        let frame = if self.deterministic { 0 } else { frame };
+        let status_icon = status_icon(s.status, self.theme.spinner, frame);
+        let age = self
+            .now_ms
+            .filter(|_| !self.deterministic)
+            .map(|now| now.saturating_sub(s.updated_at_unix_ms));
+        let older_than = |threshold: Option<u64>| {
+            matches!((age, threshold), (Some(age), Some(limit)) if age >= limit)
+        };
+        let title_fade = if older_than(self.theme.stale_after_ms) {
+            ANSI_DIM
+        } else {
+            ""
+        };
 
         let mut out = String::new();
         out.push_str(&format!(
-            "{title_bg}{ANSI_BLACK}[{icon} {}]{ANSI_RESET}\n",
+            "{title_fade}{title_bg}{title_fg}[{icon} {}]{ANSI_RESET}\n",
             truncate(&s.title, 32)
         ));
+        // Home only means something for paths on this machine.
+        let home = match s.origin {
+            Origin::Local => self.home_dir.as_deref(),
+            Origin::Remote { .. } => None,
+        };
+        let branch = s
+            .branch
+            .as_deref()
+            .map(|b| format!(" ⎇ {}", truncate(b, 24)))
+            .unwrap_or_default();
+        let shared = if self.conflicted.contains(&s.id) {
+            format!(" {}⚠ shared dir", self.theme.waiting)
+        } else {
+            String::new()
+        };
         out.push_str(&format!(
-            "{ANSI_GRAY}  dir {} @ {}{ANSI_RESET}\n",
+            "{muted}  dir {} @ {}{branch}{shared}{ANSI_RESET}\n",
             truncate(&s.user, 20),
-            truncate(&s.working_dir, 40)
+            shorten_path(&s.working_dir, home, 40)
         ));
+        if let Some(peer) = s.origin.peer() {
+            out.push_str(&format!(
+                "{ANSI_DIM}{muted}  ⟿ {}{ANSI_RESET}\n",
+                truncate(peer, 32)
+            ));
+        }
+        let weight = if s.idle || older_than(self.theme.old_after_ms) {
+            ANSI_DIM
+        } else {
+            ANSI_BOLD
+        };
+        let idle = if s.idle { " (idle)" } else { "" };
         out.push_str(&format!(
-            "  {ANSI_BOLD}{status_color}{}  {}{ANSI_RESET}\n",
+            "  {weight}{status_color}{}  {}{idle}{ANSI_RESET}\n",
             status_icon,
             format_status(s.status)
         ));
 
         if let Some(action) = &s.pending_action {
             out.push_str(&format!(
-                "  {ANSI_CYAN}{ANSI_BOLD}⏳ {}{ANSI_RESET}\n",
+                "  {accent}{ANSI_BOLD}⏳ {}{ANSI_RESET}\n",
                 truncate(action, 48)
             ));
         }
 
         for line in s.last_lines.iter().take(2) {
             out.push_str(&format!(
-                "{ANSI_DIM}{ANSI_GRAY}  > {}{ANSI_RESET}\n",
+                "{ANSI_DIM}{muted}  > {}{ANSI_RESET}\n",
                 truncate(line, 56)
             ));
         }
-        out.trim_end().to_string()
+        self.fit(out.trim_end().to_string())
+    }
+}
+
+/// Sort key that puts sessions with a project first, by name.
+fn project_order(s: &SessionEvent) -> (bool, &str) {
+    match s.project.as_deref() {
+        Some(name) => (false, name),
+        None => (true, ""),
+    }
+}
+
+/// Plain single line for status bars, e.g. `◆2 ✦1 ⏳1`: active sessions per agent,
+/// then how many are waiting on the user, with icons from `theme`. No color, no newline;
+/// empty when idle.
+pub fn render_statusline(sessions: &[SessionEvent], theme: &Theme) -> String {
+    const ORDER: [AgentKind; 6] = [
+        AgentKind::Claude,
+        AgentKind::Codex,
+        AgentKind::Gemini,
+        AgentKind::Copilot,
+        AgentKind::Cody,
+        AgentKind::Unknown,
+    ];
+    let active = sessions
+        .iter()
+        .filter(|s| !s.status.is_terminal())
+        .collect::<Vec<_>>();
+    let mut parts = ORDER
+        .iter()
+        .filter_map(|agent| {
+            let count = active.iter().filter(|s| s.agent == *agent).count();
+            (count > 0).then(|| format!("{}{count}", theme.icon_for_agent(agent)))
+        })
+        .collect::<Vec<_>>();
+    let mut custom = BTreeMap::<&str, usize>::new();
+    for s in &active {
+        if let AgentKind::Custom(label) = &s.agent {
+            *custom.entry(label).or_default() += 1;
+        }
+    }
+    parts.extend(custom.iter().map(|(label, count)| {
+        let icon = theme.icon_for_agent(&AgentKind::Custom(label.to_string()));
+        format!("{icon}{count}")
+    }));
+    let attention = active
+        .iter()
+        .filter(|s| s.status == SessionStatus::WaitingInput || s.pending_action.is_some())
+        .count();
+    if attention > 0 {
+        parts.push(format!("⏳{attention}"));
     }
+    parts.join(" ")
+}
+
+// FNV-1a: stable across runs and Rust versions, unlike `DefaultHasher`.
+fn label_hash(label: &str) -> u64 {
+    label.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+        (hash ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3)
+    })
 }
 
-fn agent_icon(agent: AgentKind) -> &'static str {
+fn agent_icon(agent: &AgentKind) -> &'static str {
     match agent {
         AgentKind::Claude => "◆",
         AgentKind::Codex => "◎",
         AgentKind::Gemini => "✦",
+        AgentKind::Copilot => "⬢",
+        AgentKind::Cody => "✺",
         AgentKind::Unknown => "?",
+        AgentKind::Custom(_) => CUSTOM_ICON,
     }
 }
 
@@ -96,12 +910,9 @@ fn format_status(status: SessionStatus) -> &'static str {
     }
 }
 
-fn status_icon(status: SessionStatus, frame: usize) -> &'static str {
+fn status_icon(status: SessionStatus, spinner: SpinnerStyle, frame: usize) -> &'static str {
     match status {
-        SessionStatus::Running => {
-            const FRAMES: [&str; 4] = ["◴", "◷", "◶", "◵"];
-            FRAMES[frame % FRAMES.len()]
-        }
+        SessionStatus::Running => spinner.frame(frame),
         SessionStatus::WaitingInput => "?",
         SessionStatus::Success => "✓",
         SessionStatus::Failed => "✗",
@@ -109,25 +920,6 @@ fn status_icon(status: SessionStatus, frame: usize) -> &'static str {
     }
 }
 
-fn bg_for_agent(agent: AgentKind) -> &'static str {
-    match agent {
-        AgentKind::Claude => ANSI_BG_ORANGE,
-        AgentKind::Codex => ANSI_BG_WHITE,
-        AgentKind::Gemini => ANSI_BG_BLUE,
-        AgentKind::Unknown => ANSI_BG_GRAY,
-    }
-}
-
-fn color_for_status(status: SessionStatus) -> &'static str {
-    match status {
-        SessionStatus::Running => ANSI_CYAN,
-        SessionStatus::WaitingInput => ANSI_ORANGE,
-        SessionStatus::Success => ANSI_GREEN,
-        SessionStatus::Failed => ANSI_RED,
-        SessionStatus::Stopped => ANSI_GRAY,
-    }
-}
-
 fn truncate(input: &str, limit: usize) -> String {
     if input.chars().count() <= limit {
         return input.to_string();
@@ -141,16 +933,196 @@ fn truncate(input: &str, limit: usize) -> String {
     out
 }
 
+/// Clips `line` to `width` visible columns, passing ANSI escapes through untouched.
+fn clip_visible(line: &str, width: usize) -> String {
+    if visible_len(line) <= width {
+        return line.to_string();
+    }
+    let keep = width.saturating_sub(3);
+    let mut out = String::new();
+    let mut shown = 0;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            out.push(c);
+            for c in chars.by_ref() {
+                out.push(c);
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+            continue;
+        }
+        if shown == keep {
+            break;
+        }
+        out.push(c);
+        shown += 1;
+    }
+    out.push_str("...");
+    if line.contains('\x1b') {
+        out.push_str(ANSI_RESET);
+    }
+    out
+}
+
+fn visible_len(line: &str) -> usize {
+    let mut len = 0;
+    let mut in_escape = false;
+    for c in line.chars() {
+        if in_escape {
+            in_escape = !c.is_ascii_alphabetic();
+        } else if c == '\x1b' {
+            in_escape = true;
+        } else {
+            len += 1;
+        }
+    }
+    len
+}
+
+/// Columns of the terminal on stdout, falling back to `$COLUMNS`.
+pub fn terminal_width() -> Option<usize> {
+    tty_size()
+        .map(|(columns, _)| columns)
+        .filter(|&width| width > 0)
+        .or_else(|| env_size("COLUMNS"))
+}
+
+/// Rows of the terminal on stdout, falling back to `$LINES`.
+pub fn terminal_height() -> Option<usize> {
+    tty_size()
+        .map(|(_, rows)| rows)
+        .filter(|&height| height > 0)
+        .or_else(|| env_size("LINES"))
+}
+
+/// Width assumed for a terminal that reports zero columns or none at all.
+pub const MIN_WIDTH: usize = 40;
+
+/// Width to clip lines to: the detected terminal width, else `MIN_WIDTH` when drawing on a
+/// terminal. Files and pipes without `$COLUMNS` stay unclipped.
+pub fn fit_width(on_terminal: bool) -> Option<usize> {
+    terminal_width().or_else(|| on_terminal.then_some(MIN_WIDTH))
+}
+
+/// Rough rows one session card takes, counting the blank line between cards.
+const CARD_ROWS: usize = 6;
+/// Rows taken by the refresh banner above the cards.
+const BANNER_ROWS: usize = 3;
+
+/// How many session cards fit in `height` terminal rows (at least one).
+pub fn cards_per_screen(height: usize) -> usize {
+    (height.saturating_sub(BANNER_ROWS + 2) / CARD_ROWS).max(1)
+}
+
+fn env_size(name: &str) -> Option<usize> {
+    std::env::var(name)
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+        .filter(|&n: &usize| n > 0)
+}
+
+#[cfg(unix)]
+fn tty_size() -> Option<(usize, usize)> {
+    // SAFETY: TIOCGWINSZ only writes into the zeroed `winsize` we own.
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    let rc = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+    (rc == 0).then_some((size.ws_col as usize, size.ws_row as usize))
+}
+
+#[cfg(not(unix))]
+fn tty_size() -> Option<(usize, usize)> {
+    None
+}
+
+/// Wall-clock `HH:MM:SS` for `now_ms` in the system's local timezone.
+pub fn format_clock(now_ms: u64) -> String {
+    format_clock_with_offset(now_ms, local_utc_offset_secs(now_ms))
+}
+
+/// `HH:MM:SS` for `now_ms` shifted by `offset_secs` east of UTC.
+pub fn format_clock_with_offset(now_ms: u64, offset_secs: i64) -> String {
+    let secs = (now_ms / 1000) as i64 + offset_secs;
+    let of_day = secs.rem_euclid(86_400);
+    format!(
+        "{:02}:{:02}:{:02}",
+        of_day / 3600,
+        of_day % 3600 / 60,
+        of_day % 60
+    )
+}
+
+/// Elapsed `HH:MM:SS` for a span of `ms`; hours keep counting past a day.
+pub fn format_duration(ms: u64) -> String {
+    let secs = ms / 1000;
+    format!("{:02}:{:02}:{:02}", secs / 3600, secs % 3600 / 60, secs % 60)
+}
+
+#[cfg(unix)]
+fn local_utc_offset_secs(now_ms: u64) -> i64 {
+    let t = (now_ms / 1000) as libc::time_t;
+    // SAFETY: localtime_r only writes into the zeroed `tm` we own.
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&t, &mut tm) }.is_null() {
+        return 0;
+    }
+    tm.tm_gmtoff as i64
+}
+
+#[cfg(not(unix))]
+fn local_utc_offset_secs(_now_ms: u64) -> i64 {
+    0
+}
+
+/// Abbreviates `home` to `~`, then fits `path` into `limit` chars with a middle
+/// ellipsis that keeps the start and the whole last component (`~/pro.../project`).
+pub fn shorten_path(path: &str, home: Option<&str>, limit: usize) -> String {
+    let home = home.map(|h| h.trim_end_matches('/')).filter(|h| !h.is_empty());
+    let path = match home.and_then(|h| path.strip_prefix(h)) {
+        Some("") => "~".to_string(),
+        Some(rest) if rest.starts_with('/') => format!("~{rest}"),
+        _ => path.to_string(),
+    };
+    let len = path.chars().count();
+    if len <= limit {
+        return path;
+    }
+    let chars = path.chars().collect::<Vec<_>>();
+    let tail_len = match path.trim_end_matches('/').rfind('/') {
+        Some(idx) => path[idx..].chars().count(),
+        None => 0,
+    };
+    let budget = limit.saturating_sub(3);
+    let tail_len = if tail_len > 0 && tail_len < budget {
+        tail_len
+    } else {
+        budget - budget / 2
+    };
+    let head_len = budget - tail_len;
+    let head = chars[..head_len].iter().collect::<String>();
+    let tail = chars[len - tail_len..].iter().collect::<String>();
+    format!("{head}...{tail}")
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::model::{AgentKind, SessionEvent, SessionStatus};
+    use std::sync::atomic::{AtomicBool, Ordering};
 
-    use super::TerminalRenderer;
+    use crate::model::{
+        aggregate_by_user, detect_conflicts, AgentKind, Origin, SessionEvent, SessionStatus,
+    };
 
-    #[test]
-    fn renders_pending_action() {
-        let renderer = TerminalRenderer::new();
-        let event = SessionEvent {
+    use super::{
+        format_clock_with_offset, format_duration, render_statusline, shorten_path, visible_len,
+        write_frame, AgentStyle, FrameBlock, IncrementalScreen, ScreenMode, SpinnerStyle,
+        TerminalRenderer, Theme, MIN_WIDTH,
+    };
+
+    fn event() -> SessionEvent {
+        SessionEvent {
             id: "1".to_string(),
             agent: AgentKind::Codex,
             title: "long title".to_string(),
@@ -161,9 +1133,657 @@ mod tests {
             started_at_unix_ms: 1,
             updated_at_unix_ms: 2,
             last_lines: vec!["line 1".to_string()],
+            idle: false,
+            origin: Origin::Local,
+            branch: None,
+            project: None,
+        }
+    }
+
+    #[test]
+    fn conflicting_sessions_get_a_shared_dir_badge() {
+        let mut other = event();
+        other.id = "2".to_string();
+        let conflicts = detect_conflicts(&[event(), other.clone()]);
+        let renderer = TerminalRenderer::new()
+            .with_theme(Theme::mono())
+            .with_conflicts(&conflicts);
+        let card = renderer.render_session_with_frame(&event(), 0);
+        let dir_line = card.lines().nth(1).expect("dir line");
+        assert!(dir_line.starts_with("  dir alice @ /tmp/repo ⚠ shared dir"));
+
+        other.id = "3".to_string();
+        let card = renderer.render_session_with_frame(&other, 0);
+        assert!(!card.contains("shared dir"));
+    }
+
+    #[test]
+    fn refresh_width_rereads_size_only_after_a_resize() {
+        let resized = AtomicBool::new(false);
+        let mut renderer = TerminalRenderer::new().with_width(Some(80));
+        assert!(!renderer.refresh_width(&resized, || Some(40)));
+        assert_eq!(renderer.width, Some(80));
+
+        resized.store(true, Ordering::Relaxed);
+        assert!(renderer.refresh_width(&resized, || Some(40)));
+        assert_eq!(renderer.width, Some(40));
+        assert!(!resized.load(Ordering::Relaxed));
+
+        resized.store(true, Ordering::Relaxed);
+        assert!(!renderer.refresh_width(&resized, || Some(40)));
+    }
+
+    #[test]
+    fn pinned_sessions_render_first_in_the_given_order() {
+        let sessions = ["a", "b", "y", "z"]
+            .into_iter()
+            .map(|id| {
+                let mut s = event();
+                s.id = id.to_string();
+                s.title = format!("task {id}");
+                s
+            })
+            .collect::<Vec<_>>();
+        let renderer = TerminalRenderer::new()
+            .with_theme(Theme::mono())
+            .with_pinned(vec!["z".to_string(), "missing".to_string(), "y".to_string()]);
+        let (shown, _) = renderer.paginate(sessions);
+        let ids = shown.iter().map(|s| s.id.as_str()).collect::<Vec<_>>();
+        assert_eq!(ids, ["z", "y", "a", "b"]);
+        let output = renderer.render_many(shown);
+        let at = |title: &str| output.find(title).expect("title rendered");
+        assert!(at("task z") < at("task y") && at("task y") < at("task a"));
+    }
+
+    #[test]
+    fn zero_width_falls_back_to_the_floor() {
+        let mut session = event();
+        session.working_dir = "/srv/checkouts/a-directory-name-longer-than-the-floor".to_string();
+        let renderer = TerminalRenderer::new().with_theme(Theme::mono()).with_width(Some(0));
+        let card = renderer.render_session(&session);
+        let widths: Vec<_> = card.lines().map(visible_len).collect();
+        assert!(widths.iter().all(|&width| width > 0 && width <= MIN_WIDTH), "{card}");
+        assert_eq!(widths.iter().max(), Some(&MIN_WIDTH));
+        assert!(card.contains("..."));
+    }
+
+    #[test]
+    fn max_width_and_narrow_terminals_are_not_raised_to_the_floor() {
+        let mut session = event();
+        session.working_dir = "/srv/checkouts/a-directory-name-longer-than-the-floor".to_string();
+        for (width, max_width, expected) in [
+            (Some(0), Some(20), 20),
+            (Some(100), Some(20), 20),
+            (Some(30), None, 30),
+        ] {
+            let renderer = TerminalRenderer::new()
+                .with_theme(Theme::mono())
+                .with_width(width)
+                .with_max_width(max_width);
+            let card = renderer.render_session(&session);
+            let widest = card.lines().map(visible_len).max();
+            assert_eq!(widest, Some(expected), "{width:?} / {max_width:?}");
+        }
+    }
+
+    #[test]
+    fn spinner_styles_cycle_through_their_frames() {
+        for (style, expected) in [
+            (SpinnerStyle::QuadrantCircle, &["◴", "◷", "◶", "◵"][..]),
+            (SpinnerStyle::Braille, &["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"][..]),
+            (SpinnerStyle::Ascii, &["|", "/", "-", "\\"][..]),
+            (SpinnerStyle::Dots, &["·", "•", "●"][..]),
+        ] {
+            let cycle: Vec<_> = (0..expected.len() * 2).map(|n| style.frame(n)).collect();
+            assert_eq!(cycle, [expected, expected].concat(), "{style:?}");
+        }
+
+        let mut running = event();
+        running.status = SessionStatus::Running;
+        let renderer =
+            TerminalRenderer::new().with_theme(Theme::mono().with_spinner(SpinnerStyle::Ascii));
+        assert!(renderer.render_session_with_frame(&running, 1).contains("/  RUNNING"));
+    }
+
+    #[test]
+    fn deterministic_mode_matches_golden_output_for_any_frame_and_time() {
+        let mut running = event();
+        running.id = "a".to_string();
+        running.agent = AgentKind::Claude;
+        running.title = "refactor parser".to_string();
+        running.status = SessionStatus::Running;
+        running.pending_action = None;
+        running.branch = Some("main".to_string());
+        let waiting = event();
+        let mut done = event();
+        done.id = "c".to_string();
+        done.agent = AgentKind::Gemini;
+        done.status = SessionStatus::Success;
+        done.pending_action = None;
+        done.last_lines = vec![];
+        let sessions = vec![running, waiting, done];
+
+        let renderer = |now: Option<u64>| {
+            TerminalRenderer::new()
+                .with_theme(Theme::mono())
+                .with_deterministic(true)
+                .with_now(now)
+        };
+        let golden = concat!(
+            "[◆ refactor parser]\x1b[0m\n",
+            "  dir alice @ /tmp/repo ⎇ main\x1b[0m\n",
+            "  \x1b[1m◴  RUNNING\x1b[0m\n",
+            "\x1b[2m  > line 1\x1b[0m\n",
+            "\n",
+            "[◎ long title]\x1b[0m\n",
+            "  dir alice @ /tmp/repo\x1b[0m\n",
+            "  \x1b[1m?  WAITING_INPUT\x1b[0m\n",
+            "  \x1b[1m⏳ Click approve\x1b[0m\n",
+            "\x1b[2m  > line 1\x1b[0m\n",
+            "\n",
+            "[✦ long title]\x1b[0m\n",
+            "  dir alice @ /tmp/repo\x1b[0m\n",
+            "  \x1b[1m✓  SUCCESS\x1b[0m",
+        );
+        for frame in 0..4 {
+            for now in [None, Some(u64::MAX)] {
+                let output = renderer(now).render_many_with_frame(sessions.clone(), frame);
+                assert_eq!(output, golden, "frame {frame}, now {now:?}");
+            }
+        }
+    }
+
+    /// Records every `write` call separately, to check frames are not split up.
+    #[derive(Default)]
+    struct CountingWriter {
+        writes: Vec<Vec<u8>>,
+        flushes: usize,
+    }
+
+    impl std::io::Write for CountingWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.writes.push(buf.to_vec());
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            self.flushes += 1;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn a_frame_goes_out_in_one_write_then_a_flush() {
+        let sessions = (0..40)
+            .map(|i| {
+                let mut s = event();
+                s.id = format!("s{i:02}");
+                s
+            })
+            .collect::<Vec<_>>();
+        let renderer = TerminalRenderer::new();
+        let mut blocks = vec![FrameBlock::new("banner", "Agent-box live monitor\n")];
+        blocks.extend(renderer.render_blocks(sessions, 0));
+        let frame = ScreenMode::Clear.compose(&blocks);
+
+        let mut out = CountingWriter::default();
+        write_frame(&mut out, &frame).expect("write frame");
+        assert_eq!(out.writes.len(), 1);
+        assert_eq!(out.flushes, 1);
+        let written = String::from_utf8(out.writes.remove(0)).expect("utf8");
+        assert!(written.starts_with("\x1b[2J\x1b[HAgent-box live monitor\n"));
+        assert_eq!(written.matches("Click approve").count(), 40);
+        assert_eq!(written, frame);
+    }
+
+    #[test]
+    fn alt_screen_emits_enter_and_exit_sequences() {
+        let mode = ScreenMode::from_flags(true, false, true);
+        assert_eq!(mode, ScreenMode::AltScreen);
+        assert_eq!(mode.enter_sequence(), "\x1b[?1049h");
+        assert_eq!(mode.exit_sequence(), "\x1b[?1049l");
+    }
+
+    #[test]
+    fn old_sessions_fade_while_fresh_ones_keep_full_brightness() {
+        let now = 10 * 60 * 60 * 1000;
+        let renderer = TerminalRenderer::new().with_now(Some(now));
+        let mut fresh = event();
+        fresh.updated_at_unix_ms = now - 1_000;
+        let mut stale = event();
+        stale.updated_at_unix_ms = now - 10 * 60 * 1000;
+        let mut old = event();
+        old.updated_at_unix_ms = now - 2 * 60 * 60 * 1000;
+        let line = |s: &SessionEvent, n: usize| {
+            renderer.render_session(s).lines().nth(n).unwrap_or_default().to_string()
         };
-        let output = renderer.render_session(&event);
+
+        assert!(!line(&fresh, 0).starts_with("\x1b[2m"), "{:?}", line(&fresh, 0));
+        assert!(line(&fresh, 2).contains("\x1b[1m"));
+        assert!(line(&stale, 0).starts_with("\x1b[2m"));
+        assert!(line(&stale, 2).contains("\x1b[1m"));
+        assert!(line(&old, 0).starts_with("\x1b[2m"));
+        assert!(line(&old, 2).starts_with("  \x1b[2m"), "{:?}", line(&old, 2));
+
+        let no_clock = TerminalRenderer::new();
+        assert!(!no_clock.render_session(&old).starts_with("\x1b[2m"));
+        let never = renderer.with_theme(Theme::dark().with_age_fading(None, None));
+        assert_eq!(never.render_session(&old), no_clock.render_session(&old));
+    }
+
+    #[test]
+    fn incremental_screen_redraws_only_the_changed_card() {
+        let renderer = TerminalRenderer::new();
+        let mut first = event();
+        first.title = "first task".to_string();
+        let mut second = event();
+        second.id = "2".to_string();
+        second.title = "second task".to_string();
+        let banner = FrameBlock::new("banner", "Agent-box\n\n");
+        let frame = |sessions: Vec<SessionEvent>| {
+            let mut blocks = vec![banner.clone()];
+            blocks.extend(renderer.render_blocks(sessions, 0));
+            blocks
+        };
+
+        let blocks = frame(vec![first.clone(), second.clone()]);
+        let joined: String = blocks[1..].iter().map(|b| b.text.as_str()).collect();
+        assert_eq!(
+            joined,
+            format!("{}\n", renderer.render_many(vec![first.clone(), second.clone()]))
+        );
+        let mut screen = IncrementalScreen::default();
+        let initial = screen.update(blocks);
+        assert!(initial.starts_with("\x1b[2J\x1b[H"));
+        assert!(initial.contains("first task") && initial.contains("second task"));
+
+        second.last_lines = vec!["line 2".to_string()];
+        let update = screen.update(frame(vec![first.clone(), second.clone()]));
+        assert!(!update.contains("\x1b[2J"), "same layout must not clear: {update:?}");
+        assert!(update.contains("second task"));
+        assert!(!update.contains("first task"), "unchanged card was redrawn: {update:?}");
+        assert!(!update.contains("Agent-box"));
+        // Two banner rows, then the first card; the second block starts at its gap line.
+        let second_row = 2 + renderer.render_session(&first).lines().count() + 1;
+        assert!(update.starts_with(&format!("\x1b[{second_row};1H")), "{update:?}");
+
+        let unchanged = screen.update(frame(vec![first.clone(), second.clone()]));
+        assert!(!unchanged.contains("task"));
+
+        let relayout = screen.update(frame(vec![second]));
+        assert!(relayout.starts_with("\x1b[2J\x1b[H"));
+    }
+
+    #[test]
+    fn no_clear_or_non_tty_emits_no_control_sequences() {
+        for mode in [
+            ScreenMode::from_flags(true, true, true),
+            ScreenMode::from_flags(true, false, false),
+        ] {
+            assert_eq!(mode, ScreenMode::Append);
+            assert!(mode.enter_sequence().is_empty());
+            assert!(mode.exit_sequence().is_empty());
+            assert!(mode.frame_prefix().is_empty());
+        }
+        let default = ScreenMode::from_flags(false, false, true);
+        assert_eq!(default, ScreenMode::Clear);
+        assert!(default.enter_sequence().is_empty());
+    }
+
+    #[test]
+    fn hide_done_omits_terminal_sessions() {
+        let statuses = [
+            ("run", SessionStatus::Running),
+            ("wait", SessionStatus::WaitingInput),
+            ("ok", SessionStatus::Success),
+            ("bad", SessionStatus::Failed),
+            ("halt", SessionStatus::Stopped),
+        ];
+        let sessions = statuses
+            .iter()
+            .map(|(title, status)| {
+                let mut e = event();
+                e.title = format!("task-{title}");
+                e.status = *status;
+                e
+            })
+            .collect::<Vec<_>>();
+
+        let all = TerminalRenderer::new().render_many(sessions.clone());
+        assert!(all.contains("task-ok"));
+
+        let output = TerminalRenderer::new()
+            .with_hide_done(true)
+            .render_many(sessions);
+        assert!(output.contains("task-run"));
+        assert!(output.contains("task-wait"));
+        assert!(!output.contains("task-ok"));
+        assert!(!output.contains("task-bad"));
+        assert!(!output.contains("task-halt"));
+    }
+
+    #[test]
+    fn light_and_dark_use_different_backgrounds() {
+        let mut claude = event();
+        claude.agent = AgentKind::Claude;
+        let dark = TerminalRenderer::new().render_session(&claude);
+        let light = TerminalRenderer::new()
+            .with_theme(Theme::light())
+            .render_session(&claude);
+        assert!(dark.starts_with(Theme::dark().claude_bg));
+        assert!(light.starts_with(Theme::light().claude_bg));
+        assert_ne!(Theme::dark().claude_bg, Theme::light().claude_bg);
+    }
+
+    #[test]
+    fn custom_agents_get_distinct_stable_colors() {
+        let aider = AgentKind::Custom("aider".to_string());
+        let cursor = AgentKind::Custom("cursor-agent".to_string());
+        let theme = Theme::dark();
+        assert_ne!(theme.bg_for_agent(&aider), theme.bg_for_agent(&cursor));
+        assert_eq!(theme.bg_for_agent(&aider), theme.bg_for_agent(&aider));
+        assert_eq!(theme.bg_for_agent(&aider), Theme::dark().bg_for_agent(&aider));
+        assert_eq!(Theme::mono().bg_for_agent(&aider), "");
+
+        let themed = theme.with_custom_agent(
+            "aider",
+            AgentStyle {
+                icon: "A".to_string(),
+                bg: "\x1b[48;5;1m".to_string(),
+            },
+        );
+        assert_eq!(themed.bg_for_agent(&aider), "\x1b[48;5;1m");
+        assert_eq!(themed.icon_for_agent(&aider), "A");
+        assert_eq!(themed.icon_for_agent(&cursor), "◇");
+    }
+
+    #[test]
+    fn mono_theme_has_no_backgrounds() {
+        let output = TerminalRenderer::new()
+            .with_theme(Theme::mono())
+            .render_session(&event());
+        assert!(!output.contains("\x1b[4"));
+        assert!(!output.contains("\x1b[10"));
+        assert!(output.starts_with("[◎ long title]"));
+    }
+
+    #[test]
+    fn home_relative_paths_use_tilde() {
+        let home = Some("/Users/alice");
+        assert_eq!(shorten_path("/Users/alice/repo", home, 40), "~/repo");
+        assert_eq!(shorten_path("/Users/alice", home, 40), "~");
+        assert_eq!(shorten_path("/Users/alicex/repo", home, 40), "/Users/alicex/repo");
+
+        let mut local = event();
+        local.working_dir = "/Users/alice/repo".to_string();
+        let renderer = TerminalRenderer::new().with_home_dir(Some("/Users/alice".to_string()));
+        assert!(renderer.render_session(&local).contains("@ ~/repo"));
+        local.origin = Origin::Remote {
+            peer: "peer-a".to_string(),
+        };
+        assert!(renderer.render_session(&local).contains("@ /Users/alice/repo"));
+    }
+
+    #[test]
+    fn deep_paths_keep_both_ends() {
+        let deep = "/Users/alice/projects/deep/nested/tree/project";
+        assert_eq!(shorten_path(deep, Some("/Users/alice"), 16), "~/pro.../project");
+
+        let short = shorten_path(deep, None, 24);
+        assert_eq!(short.chars().count(), 24);
+        assert!(short.starts_with("/Users/alice"));
+        assert!(short.ends_with("/project"));
+
+        let long_leaf = shorten_path("/srv/a-very-long-final-directory-name", None, 20);
+        assert_eq!(long_leaf.chars().count(), 20);
+        assert!(long_leaf.starts_with("/srv/a-"));
+        assert!(long_leaf.ends_with("name"));
+    }
+
+    #[test]
+    fn renders_branch_next_to_directory() {
+        let mut e = event();
+        assert!(!TerminalRenderer::new().render_session(&e).contains('⎇'));
+        e.branch = Some("main".to_string());
+        assert!(TerminalRenderer::new()
+            .render_session(&e)
+            .contains("@ /tmp/repo ⎇ main"));
+    }
+
+    #[test]
+    fn max_width_caps_lines_below_detected_width() {
+        let mut e = event();
+        e.working_dir = "/srv/projects/a-rather-long-repository-name/sub".to_string();
+        e.last_lines = vec!["x".repeat(80)];
+        let wide = TerminalRenderer::new().with_width(Some(120));
+        assert!(wide.render_session(&e).lines().any(|line| visible_len(line) > 40));
+
+        let capped = wide.with_max_width(Some(40));
+        let output = capped.render_session(&e);
+        assert!(output.lines().all(|line| visible_len(line) <= 40));
+        assert!(output.contains("..."));
+    }
+
+    #[test]
+    fn second_page_shows_next_slice_with_counts() {
+        let sessions = (1..=15)
+            .map(|n| {
+                let mut e = event();
+                e.id = format!("s{n:02}");
+                e
+            })
+            .collect::<Vec<_>>();
+
+        let first = TerminalRenderer::new().with_paging(1, Some(8));
+        let (shown, footer) = first.paginate(sessions.clone());
+        assert_eq!(shown.len(), 8);
+        assert!(footer.expect("footer").contains("(showing 1-8 of 15, use --page 2)"));
+
+        let second = TerminalRenderer::new().with_paging(2, Some(8));
+        let (shown, footer) = second.paginate(sessions.clone());
+        let ids = shown.iter().map(|s| s.id.as_str()).collect::<Vec<_>>();
+        assert_eq!(ids, ["s09", "s10", "s11", "s12", "s13", "s14", "s15"]);
+        assert!(footer.expect("footer").contains("(showing 9-15 of 15)"));
+
+        let (shown, footer) = TerminalRenderer::new().with_paging(1, Some(20)).paginate(sessions);
+        assert_eq!(shown.len(), 15);
+        assert!(footer.is_none());
+    }
+
+    #[test]
+    fn clock_formats_known_instant_with_offset() {
+        // 2023-11-14T22:13:20.999Z
+        let ms = 1_700_000_000_999;
+        assert_eq!(format_clock_with_offset(ms, 0), "22:13:20");
+        assert_eq!(format_clock_with_offset(ms, 2 * 3600), "00:13:20");
+        assert_eq!(format_clock_with_offset(ms, -(5 * 3600 + 30 * 60)), "16:43:20");
+    }
+
+    #[test]
+    fn duration_rolls_over_seconds_minutes_and_hours() {
+        assert_eq!(format_duration(0), "00:00:00");
+        assert_eq!(format_duration(59_999), "00:00:59");
+        assert_eq!(format_duration(60_000), "00:01:00");
+        assert_eq!(format_duration(3_599_000), "00:59:59");
+        assert_eq!(format_duration(3_600_000), "01:00:00");
+        assert_eq!(format_duration((14 * 60 + 32) * 1000), "00:14:32");
+        assert_eq!(format_duration(100 * 3_600_000), "100:00:00");
+    }
+
+    #[test]
+    fn statusline_counts_agents_and_attention_on_one_line() {
+        let with = |agent: AgentKind, status: SessionStatus, pending: Option<&str>| {
+            let mut e = event();
+            e.agent = agent;
+            e.status = status;
+            e.pending_action = pending.map(str::to_string);
+            e
+        };
+        let sessions = vec![
+            with(AgentKind::Claude, SessionStatus::Running, None),
+            with(AgentKind::Claude, SessionStatus::Running, Some("Approve write")),
+            with(AgentKind::Gemini, SessionStatus::WaitingInput, None),
+            with(AgentKind::Codex, SessionStatus::Success, None),
+        ];
+        let theme = Theme::dark();
+        let line = render_statusline(&sessions, &theme);
+        assert_eq!(line, "◆2 ✦1 ⏳2");
+        assert!(!line.contains('\n') && !line.contains('\r'));
+        assert!(!line.contains('\x1b'));
+        assert_eq!(render_statusline(&sessions, &theme), line);
+        assert_eq!(render_statusline(&[], &theme), "");
+    }
+
+    #[test]
+    fn statusline_uses_theme_icons_for_custom_agents() {
+        let custom = |label: &str| {
+            let mut e = event();
+            e.agent = AgentKind::Custom(label.to_string());
+            e.status = SessionStatus::Running;
+            e.pending_action = None;
+            e
+        };
+        let style = |icon: &str| AgentStyle {
+            icon: icon.to_string(),
+            bg: String::new(),
+        };
+        let theme = Theme::dark()
+            .with_custom_agent("aider", style("A"))
+            .with_custom_agent("goose", style("G"));
+        let sessions = vec![custom("goose"), custom("aider"), custom("aider"), custom("other")];
+        assert_eq!(render_statusline(&sessions, &theme), "A2 G1 ◇1");
+    }
+
+    #[test]
+    fn renders_pending_action() {
+        let renderer = TerminalRenderer::new();
+        let output = renderer.render_session(&event());
         assert!(output.contains("Click approve"));
     }
+
+    #[test]
+    fn renders_user_aggregate_line() {
+        let mut remote = event();
+        remote.id = "remote:peer-a:1".to_string();
+        remote.status = SessionStatus::Running;
+        remote.origin = Origin::Remote {
+            peer: "peer-a".to_string(),
+        };
+        let output = TerminalRenderer::new()
+            .render_user_aggregates(&aggregate_by_user(&[event(), remote]));
+        assert!(output.contains("alice"));
+        assert!(output.contains("1 running"));
+        assert!(output.contains("1 waiting"));
+        assert!(output.contains("across 2 hosts"));
+    }
+
+    #[test]
+    fn sectioned_render_puts_local_first_and_groups_remotes_by_peer() {
+        let remote = |title: &str, peer: &str| {
+            let mut e = event();
+            e.title = title.to_string();
+            e.origin = Origin::Remote {
+                peer: peer.to_string(),
+            };
+            e
+        };
+        let mut local = event();
+        local.title = "mine".to_string();
+        let sessions = vec![
+            remote("b-one", "peer-b"),
+            remote("a-one", "peer-a"),
+            local,
+            remote("b-two", "peer-b"),
+        ];
+
+        let output = TerminalRenderer::new()
+            .with_theme(Theme::mono())
+            .render_many_sectioned(sessions, 0);
+        let pos = |needle: &str| output.find(needle).expect(needle);
+        assert!(output.starts_with("\x1b[1m── LOCAL ──"));
+        assert!(pos("── LOCAL ──") < pos("mine"));
+        assert!(pos("mine") < pos("── REMOTE peer-a ──"));
+        assert!(pos("── REMOTE peer-a ──") < pos("a-one"));
+        assert!(pos("a-one") < pos("── REMOTE peer-b ──"));
+        assert!(pos("── REMOTE peer-b ──") < pos("b-one"));
+        assert!(pos("b-one") < pos("b-two"));
+        assert_eq!(output.matches("── REMOTE peer-b ──").count(), 1);
+    }
+
+    #[test]
+    fn peer_order_keeps_remote_sections_stable_across_renders() {
+        let remote = |id: &str, peer: &str| {
+            let mut e = event();
+            e.id = format!("remote:{peer}:{id}");
+            e.title = format!("{peer}-{id}");
+            e.origin = Origin::Remote {
+                peer: peer.to_string(),
+            };
+            e
+        };
+        // `zeta` was seen first, so it keeps the top slot however the tick is ordered.
+        let renderer = TerminalRenderer::new()
+            .with_theme(Theme::mono())
+            .with_peer_order(vec!["zeta".to_string(), "alpha".to_string()]);
+        let first = renderer.render_many_sectioned(
+            vec![remote("1", "alpha"), remote("1", "zeta"), remote("2", "zeta")],
+            0,
+        );
+        let second = renderer.render_many_sectioned(
+            vec![remote("1", "zeta"), remote("2", "zeta"), remote("1", "alpha")],
+            0,
+        );
+        assert_eq!(first, second);
+        let pos = |needle: &str| first.find(needle).expect(needle);
+        assert!(pos("── REMOTE zeta ──") < pos("zeta-1"));
+        assert!(pos("zeta-2") < pos("── REMOTE alpha ──"));
+        assert!(pos("── REMOTE alpha ──") < pos("alpha-1"));
+
+        // Without an order, sections stay alphabetical.
+        let by_name = TerminalRenderer::new()
+            .with_theme(Theme::mono())
+            .render_many_sectioned(vec![remote("1", "zeta"), remote("1", "alpha")], 0);
+        assert!(by_name.find("REMOTE alpha") < by_name.find("REMOTE zeta"));
+    }
+
+    #[test]
+    fn groups_cards_by_project() {
+        let mut api = event();
+        api.id = "api".to_string();
+        api.project = Some("billing".to_string());
+        let mut web = event();
+        web.id = "web".to_string();
+        web.project = Some("admin".to_string());
+        let loose = event();
+
+        let output = TerminalRenderer::new()
+            .with_group_by_project(true)
+            .render_many_by_project(vec![loose, api, web], 0);
+        let admin = output.find("── PROJECT admin ──").expect("admin section");
+        let billing = output.find("── PROJECT billing ──").expect("billing section");
+        let none = output.find("── NO PROJECT ──").expect("no-project section");
+        assert!(admin < billing && billing < none);
+    }
+
+    #[test]
+    fn sectioned_render_skips_empty_sections() {
+        let output = TerminalRenderer::new().render_many_sectioned(vec![event()], 0);
+        assert!(output.contains("── LOCAL ──"));
+        assert!(!output.contains("REMOTE"));
+    }
+
+    #[test]
+    fn renders_peer_badge_only_for_remote_sessions() {
+        let renderer = TerminalRenderer::new();
+        let local = renderer.render_session(&event());
+        assert!(!local.contains('⟿'));
+
+        let mut remote = event();
+        remote.origin = Origin::Remote {
+            peer: "peer-a".to_string(),
+        };
+        let output = renderer.render_session(&remote);
+        assert!(output.contains("⟿ peer-a"));
+    }
 }
 
@@ -1,32 +1,194 @@
+use std::collections::HashMap;
+
+use hmac::{Hmac, Mac};
 use sha1::Sha1;
+use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 
-use crate::model::SessionEvent;
+use crate::model::{Origin, SessionEvent};
+
+/// Trust level granted by a presented key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyClass {
+    /// Full key: may pull sessions and push its own into the peer's view.
+    Full,
+    /// View-only key derived from the full key: may pull, never push.
+    Observer,
+}
+
+/// Markers whose following text `filter_sensitive` masks by default (case-insensitive).
+pub const DEFAULT_REDACT_PATTERNS: &[&str] =
+    &["api_key=", "token=", "password=", "secret=", "bearer "];
+
+/// Which session fields `filter_sensitive` scrubs before they leave this host, and the
+/// markers it looks for. The default scrubs transcript lines and pending actions only.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RedactionPolicy {
+    pub enabled: bool,
+    pub last_lines: bool,
+    pub pending_action: bool,
+    pub title: bool,
+    pub patterns: Vec<String>,
+}
+
+impl Default for RedactionPolicy {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            last_lines: true,
+            pending_action: true,
+            title: false,
+            patterns: DEFAULT_REDACT_PATTERNS.iter().map(|p| p.to_string()).collect(),
+        }
+    }
+}
+
+impl RedactionPolicy {
+    /// Passes every field through untouched, for trusted internal networks.
+    pub fn off() -> Self {
+        Self {
+            enabled: false,
+            ..Self::default()
+        }
+    }
+
+    /// Also scrubs titles, which are built from the agent's command line.
+    pub fn with_titles(mut self, titles: bool) -> Self {
+        self.title = titles;
+        self
+    }
+
+    /// Adds markers on top of the defaults, e.g. `ghp_` or `aws_secret_access_key=`.
+    pub fn with_extra_patterns(mut self, patterns: Vec<String>) -> Self {
+        self.patterns.extend(patterns);
+        self
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct SecurityLayer {
     key_hash: String,
+    observer_hash: String,
+    env_denylist: Vec<String>,
+    redaction: RedactionPolicy,
 }
 
 impl SecurityLayer {
     pub fn new(shared_key: &str) -> Self {
         Self {
             key_hash: hash_key(shared_key),
+            observer_hash: hash_key(&derive_observer_key(shared_key)),
+            env_denylist: Vec::new(),
+            redaction: RedactionPolicy::default(),
         }
     }
 
+    pub fn with_redaction(mut self, policy: RedactionPolicy) -> Self {
+        self.redaction = policy;
+        self
+    }
+
+    /// Variable names (exact, case-sensitive) whose value is masked when it follows the
+    /// name as the next word, e.g. `GITHUB_TOKEN ghp_...`, where no `=` gives it away.
+    pub fn with_env_denylist(mut self, names: Vec<String>) -> Self {
+        self.env_denylist = names;
+        self
+    }
+
     pub fn verify_key(&self, provided_key: &str) -> bool {
         hash_key(provided_key) == self.key_hash
     }
 
+    pub fn verify_observer_key(&self, provided_key: &str) -> bool {
+        hash_key(provided_key) == self.observer_hash
+    }
+
+    /// Short non-secret identifier of the shared key (e.g. `3f2a-9c1d`), safe to print
+    /// or hand to unauthenticated peers so two hosts can compare keys by eye.
+    pub fn fingerprint(&self) -> String {
+        format!("{}-{}", &self.key_hash[..4], &self.key_hash[4..8])
+    }
+
+    pub fn classify_key(&self, provided_key: &str) -> Option<KeyClass> {
+        if self.verify_key(provided_key) {
+            Some(KeyClass::Full)
+        } else if self.verify_observer_key(provided_key) {
+            Some(KeyClass::Observer)
+        } else {
+            None
+        }
+    }
+
+    /// Answers a server challenge with HMAC-SHA256 keyed by the key hash, so the key
+    /// itself never crosses the wire.
+    pub fn respond_to_challenge(&self, challenge: &str) -> String {
+        challenge_proof(&self.key_hash, challenge)
+    }
+
+    /// Server side of `respond_to_challenge`: which key, if any, produced `proof`.
+    pub fn classify_response(&self, challenge: &str, proof: &str) -> Option<KeyClass> {
+        if verify_proof(&self.key_hash, challenge, proof) {
+            Some(KeyClass::Full)
+        } else if verify_proof(&self.observer_hash, challenge, proof) {
+            Some(KeyClass::Observer)
+        } else {
+            None
+        }
+    }
+
     pub fn filter_sensitive(&self, mut event: SessionEvent) -> SessionEvent {
-        event.last_lines = event
-            .last_lines
-            .iter()
-            .map(|line| redact_line(line))
-            .collect();
+        let policy = &self.redaction;
+        if !policy.enabled {
+            return event;
+        }
+        if policy.last_lines {
+            event.last_lines = event
+                .last_lines
+                .iter()
+                .map(|line| self.redact(line))
+                .collect();
+        }
+        if policy.pending_action {
+            event.pending_action = event.pending_action.as_deref().map(|line| self.redact(line));
+        }
+        if policy.title {
+            event.title = self.redact(&event.title);
+        }
         event
     }
+
+    /// Replaces the session's user and peer host with short pseudonyms (`user-a3f`,
+    /// `host-9c1`) that are stable for a given key, for screenshots and public sharing.
+    /// Secrets in the text are not touched; that is `filter_sensitive`'s job.
+    pub fn anonymize(&self, mut event: SessionEvent) -> SessionEvent {
+        event.user = self.pseudonym("user", &event.user);
+        if let Some(peer) = event.origin.peer().map(str::to_string) {
+            let host = self.pseudonym("host", &peer);
+            if let Some(rest) = event.id.strip_prefix(&format!("remote:{peer}:")) {
+                event.id = format!("remote:{host}:{rest}");
+            }
+            event.origin = Origin::Remote { peer: host };
+        }
+        event
+    }
+
+    /// `<kind>-<3 hex chars>` derived from `name` and the key hash.
+    pub fn pseudonym(&self, kind: &str, name: &str) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.key_hash.as_bytes());
+        hasher.update(kind.as_bytes());
+        hasher.update(b":");
+        hasher.update(name.as_bytes());
+        let digest = format!("{:x}", hasher.finalize());
+        format!("{kind}-{}", &digest[..3])
+    }
+
+    fn redact(&self, line: &str) -> String {
+        redact_denied_values(
+            &redact_line(line, &self.redaction.patterns),
+            &self.env_denylist,
+        )
+    }
 }
 
 fn hash_key(key: &str) -> String {
@@ -35,6 +197,85 @@ fn hash_key(key: &str) -> String {
     format!("{:x}", hasher.finalize())
 }
 
+fn challenge_mac(key_hash: &str, challenge: &str) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key_hash.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(challenge.as_bytes());
+    mac
+}
+
+fn challenge_proof(key_hash: &str, challenge: &str) -> String {
+    challenge_mac(key_hash, challenge)
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+fn verify_proof(key_hash: &str, challenge: &str, proof: &str) -> bool {
+    let Some(bytes) = decode_hex(proof) else {
+        return false;
+    };
+    challenge_mac(key_hash, challenge).verify_slice(&bytes).is_ok()
+}
+
+fn decode_hex(input: &str) -> Option<Vec<u8>> {
+    if !input.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..input.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(input.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Rough guess at how hard a passkey is to brute-force; see `passkey_strength`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Strength {
+    Weak,
+    Ok,
+    Strong,
+}
+
+/// Keys shorter than this are weak however random they look.
+const MIN_PASSKEY_CHARS: usize = 8;
+
+/// Classifies `key` by its Shannon entropy (bits per character times length), which
+/// penalizes short keys, small alphabets and repetition alike. A 40-char hex key from
+/// `generate_passkey_sha1` is strong; `test` or `1234` is weak.
+pub fn passkey_strength(key: &str) -> Strength {
+    let chars: Vec<char> = key.chars().collect();
+    if chars.len() < MIN_PASSKEY_CHARS {
+        return Strength::Weak;
+    }
+    let mut counts: HashMap<char, usize> = HashMap::new();
+    for c in &chars {
+        *counts.entry(*c).or_default() += 1;
+    }
+    let len = chars.len() as f64;
+    let bits_per_char: f64 = counts
+        .values()
+        .map(|&n| {
+            let p = n as f64 / len;
+            -p * p.log2()
+        })
+        .sum();
+    match bits_per_char * len {
+        bits if bits < 40.0 => Strength::Weak,
+        bits if bits < 100.0 => Strength::Ok,
+        _ => Strength::Strong,
+    }
+}
+
+/// Derives the view-only passkey that can be shared alongside a full session key.
+pub fn derive_observer_key(shared_key: &str) -> String {
+    let mut hasher = Sha1::new();
+    hasher.update(b"observer:");
+    hasher.update(shared_key.as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
 pub fn generate_passkey_sha1(host_name: &str, session_unix_ms: u64, random_seed: u64) -> String {
     let mut hasher = Sha1::new();
     hasher.update(host_name.as_bytes());
@@ -45,24 +286,85 @@ pub fn generate_passkey_sha1(host_name: &str, session_unix_ms: u64, random_seed:
     format!("{:x}", hasher.finalize())
 }
 
-fn redact_line(line: &str) -> String {
+fn redact_line(line: &str, suspects: &[String]) -> String {
     let mut out = line.to_string();
-    let suspects = ["api_key=", "token=", "password=", "secret="];
     for suspect in suspects {
-        if let Some(idx) = out.to_lowercase().find(suspect) {
-            let prefix_len = idx + suspect.len();
-            let prefix = &out[..prefix_len];
-            out = format!("{prefix}[REDACTED]");
+        if suspect.is_empty() {
+            continue;
+        }
+        if let Some(prefix_len) = find_end_ignore_case(&out, suspect) {
+            out = format!("{}[REDACTED]", &out[..prefix_len]);
         }
     }
     out
 }
 
+/// Byte offset just past the first case-insensitive match of `needle` in `haystack`.
+/// Compares char by char so the offset is valid in `haystack` itself; lowercasing the
+/// whole line first can change its byte length (`İ` grows from 2 to 3 bytes).
+fn find_end_ignore_case(haystack: &str, needle: &str) -> Option<usize> {
+    haystack.char_indices().find_map(|(start, _)| {
+        let mut end = start;
+        let mut rest = haystack[start..].chars();
+        for wanted in needle.chars() {
+            let c = rest.next()?;
+            if !c.to_lowercase().eq(wanted.to_lowercase()) {
+                return None;
+            }
+            end += c.len_utf8();
+        }
+        Some(end)
+    })
+}
+
+/// Masks the word after any whole-word occurrence of a denied name, keeping the rest of
+/// the line (spacing included) as it was.
+fn redact_denied_values(line: &str, denylist: &[String]) -> String {
+    if denylist.is_empty() {
+        return line.to_string();
+    }
+    let mut words = Vec::new();
+    let mut start = None;
+    for (idx, c) in line.char_indices() {
+        match (c.is_whitespace(), start) {
+            (false, None) => start = Some(idx),
+            (true, Some(s)) => {
+                words.push(s..idx);
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        words.push(s..line.len());
+    }
+
+    let mut out = String::new();
+    let mut copied = 0;
+    for pair in words.windows(2) {
+        let name = line[pair[0].clone()].trim_matches(|c: char| !is_word_char(c));
+        if denylist.iter().any(|denied| denied == name) && pair[1].start >= copied {
+            out.push_str(&line[copied..pair[1].start]);
+            out.push_str("[REDACTED]");
+            copied = pair[1].end;
+        }
+    }
+    out.push_str(&line[copied..]);
+    out
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::model::{AgentKind, SessionEvent, SessionStatus};
+    use crate::model::{AgentKind, Origin, SessionEvent, SessionStatus};
 
-    use super::{generate_passkey_sha1, SecurityLayer};
+    use super::{
+        derive_observer_key, generate_passkey_sha1, passkey_strength, KeyClass, RedactionPolicy,
+        SecurityLayer, Strength,
+    };
 
     #[test]
     fn verifies_key() {
@@ -71,6 +373,85 @@ mod tests {
         assert!(!sec.verify_key("abcd"));
     }
 
+    #[test]
+    fn challenge_response_round_trip_rejects_wrong_key() {
+        let server = SecurityLayer::new("abc");
+        let challenge = "0123456789abcdef";
+
+        let proof = SecurityLayer::new("abc").respond_to_challenge(challenge);
+        assert!(!proof.contains("abc"));
+        assert_eq!(server.classify_response(challenge, &proof), Some(KeyClass::Full));
+
+        let observer = SecurityLayer::new(&derive_observer_key("abc"));
+        let observer_proof = observer.respond_to_challenge(challenge);
+        assert_eq!(
+            server.classify_response(challenge, &observer_proof),
+            Some(KeyClass::Observer)
+        );
+
+        let wrong = SecurityLayer::new("abd").respond_to_challenge(challenge);
+        assert_eq!(server.classify_response(challenge, &wrong), None);
+        assert_eq!(server.classify_response("other-nonce", &proof), None);
+        assert_eq!(server.classify_response(challenge, "not-hex"), None);
+    }
+
+    #[test]
+    fn anonymize_maps_users_and_peers_to_stable_pseudonyms() {
+        let sec = SecurityLayer::new("abc");
+        let event = |user: &str, origin: Origin| SessionEvent {
+            id: "remote:laptop:1".to_string(),
+            agent: AgentKind::Claude,
+            title: "t".to_string(),
+            working_dir: "/tmp".to_string(),
+            user: user.to_string(),
+            status: SessionStatus::Running,
+            pending_action: None,
+            started_at_unix_ms: 1,
+            updated_at_unix_ms: 2,
+            last_lines: vec![],
+            idle: false,
+            origin,
+            branch: None,
+            project: None,
+        };
+        let alice = sec.anonymize(event("alice", Origin::Local));
+        let alice_again = sec.anonymize(event("alice", Origin::Local));
+        let bob = sec.anonymize(event("bob", Origin::Local));
+        assert_eq!(alice.user, alice_again.user);
+        assert_ne!(alice.user, bob.user);
+        assert!(alice.user.starts_with("user-") && alice.user.len() == 8);
+
+        let laptop = Origin::Remote {
+            peer: "laptop".to_string(),
+        };
+        let remote = sec.anonymize(event("alice", laptop));
+        let host = sec.pseudonym("host", "laptop");
+        assert_eq!(remote.user, alice.user);
+        assert_eq!(remote.origin.peer(), Some(host.as_str()));
+        assert_eq!(remote.id, format!("remote:{host}:1"));
+        assert!(!format!("{remote:?}").contains("laptop"));
+    }
+
+    #[test]
+    fn fingerprint_is_stable_per_key() {
+        let a = SecurityLayer::new("team-key").fingerprint();
+        assert_eq!(a, SecurityLayer::new("team-key").fingerprint());
+        assert_ne!(a, SecurityLayer::new("other-key").fingerprint());
+        assert_eq!(a.len(), 9);
+        assert!(!a.contains("team-key"));
+    }
+
+    #[test]
+    fn classifies_observer_key_separately() {
+        let sec = SecurityLayer::new("abc");
+        let observer = derive_observer_key("abc");
+        assert_ne!(observer, "abc");
+        assert_eq!(sec.classify_key("abc"), Some(KeyClass::Full));
+        assert_eq!(sec.classify_key(&observer), Some(KeyClass::Observer));
+        assert!(!sec.verify_key(&observer));
+        assert_eq!(sec.classify_key("wrong"), None);
+    }
+
     #[test]
     fn redacts_secret_lines() {
         let sec = SecurityLayer::new("abc");
@@ -85,16 +466,151 @@ mod tests {
             started_at_unix_ms: 1,
             updated_at_unix_ms: 2,
             last_lines: vec!["token=mytoken".to_string()],
+            idle: false,
+            origin: Origin::Local,
+            branch: None,
+            project: None,
         };
         let filtered = sec.filter_sensitive(event);
         assert_eq!(filtered.last_lines[0], "token=[REDACTED]");
     }
 
+    #[test]
+    fn redaction_offsets_survive_case_folding_that_changes_length() {
+        let sec = SecurityLayer::new("abc");
+        let mut event = crate::sample_event("id");
+        event.last_lines = vec![
+            "İİ token=abcdef".to_string(),
+            "İİİİİİİ token=x".to_string(),
+            "İİ TOKEN=abcdef".to_string(),
+        ];
+        let filtered = sec.filter_sensitive(event);
+        assert_eq!(
+            filtered.last_lines,
+            vec!["İİ token=[REDACTED]", "İİİİİİİ token=[REDACTED]", "İİ TOKEN=[REDACTED]"]
+        );
+    }
+
+    #[test]
+    fn redaction_policy_selects_fields_and_patterns() {
+        let event = SessionEvent {
+            id: "id".to_string(),
+            agent: AgentKind::Claude,
+            title: "deploy password=hunter2".to_string(),
+            working_dir: "/tmp".to_string(),
+            user: "u".to_string(),
+            status: SessionStatus::WaitingInput,
+            pending_action: Some("Approve: curl -H 'bearer abc'".to_string()),
+            started_at_unix_ms: 1,
+            updated_at_unix_ms: 2,
+            last_lines: vec!["token=mytoken".to_string(), "ghp_abcdef leaked".to_string()],
+            idle: false,
+            origin: Origin::Local,
+            branch: None,
+            project: None,
+        };
+        let filter = |policy: RedactionPolicy| {
+            SecurityLayer::new("abc")
+                .with_redaction(policy)
+                .filter_sensitive(event.clone())
+        };
+
+        let default = filter(RedactionPolicy::default());
+        assert_eq!(default.title, "deploy password=hunter2");
+        assert_eq!(default.last_lines, vec!["token=[REDACTED]", "ghp_abcdef leaked"]);
+        assert_eq!(
+            default.pending_action.as_deref(),
+            Some("Approve: curl -H 'bearer [REDACTED]")
+        );
+
+        assert_eq!(filter(RedactionPolicy::off()), event);
+
+        let strict = filter(
+            RedactionPolicy::default()
+                .with_titles(true)
+                .with_extra_patterns(vec!["GHP_".to_string()]),
+        );
+        assert_eq!(strict.title, "deploy password=[REDACTED]");
+        assert_eq!(strict.last_lines, vec!["token=[REDACTED]", "ghp_[REDACTED]"]);
+    }
+
+    #[test]
+    fn denylisted_env_name_masks_following_value() {
+        let sec = SecurityLayer::new("abc").with_env_denylist(vec!["GITHUB_TOKEN".to_string()]);
+        let mut event = SessionEvent {
+            id: "id".to_string(),
+            agent: AgentKind::Claude,
+            title: "t".to_string(),
+            working_dir: "/tmp".to_string(),
+            user: "u".to_string(),
+            status: SessionStatus::Running,
+            pending_action: Some("export GITHUB_TOKEN ghp_xxx".to_string()),
+            started_at_unix_ms: 1,
+            updated_at_unix_ms: 2,
+            last_lines: vec![
+                "using GITHUB_TOKEN ghp_xxx for  the push".to_string(),
+                "MY_GITHUB_TOKEN ghp_xxx github_token ghp_yyy".to_string(),
+            ],
+            idle: false,
+            origin: Origin::Local,
+            branch: None,
+            project: None,
+        };
+        event = sec.filter_sensitive(event);
+        assert_eq!(event.last_lines[0], "using GITHUB_TOKEN [REDACTED] for  the push");
+        assert_eq!(event.last_lines[1], "MY_GITHUB_TOKEN ghp_xxx github_token ghp_yyy");
+        assert_eq!(
+            event.pending_action.as_deref(),
+            Some("export GITHUB_TOKEN [REDACTED]")
+        );
+    }
+
+    #[test]
+    fn redacts_secret_pending_action() {
+        let sec = SecurityLayer::new("abc");
+        let event = SessionEvent {
+            id: "id".to_string(),
+            agent: AgentKind::Claude,
+            title: "t".to_string(),
+            working_dir: "/tmp".to_string(),
+            user: "u".to_string(),
+            status: SessionStatus::WaitingInput,
+            pending_action: Some(
+                "Approve: run `curl -H 'authorization: Bearer abc'`".to_string(),
+            ),
+            started_at_unix_ms: 1,
+            updated_at_unix_ms: 2,
+            last_lines: vec![],
+            idle: false,
+            origin: Origin::Local,
+            branch: None,
+            project: None,
+        };
+        let filtered = sec.filter_sensitive(event);
+        let action = filtered.pending_action.expect("action kept");
+        assert_eq!(action, "Approve: run `curl -H 'authorization: Bearer [REDACTED]");
+        assert!(!action.contains("abc"));
+    }
+
     #[test]
     fn generates_sha1_passkey() {
         let key = generate_passkey_sha1("host-a", 100, 200);
         assert_eq!(key.len(), 40);
         assert!(key.chars().all(|c| c.is_ascii_hexdigit()));
     }
+
+    #[test]
+    fn passkey_strength_grades_length_and_variety() {
+        assert_eq!(passkey_strength("1234"), Strength::Weak);
+        assert_eq!(passkey_strength("test"), Strength::Weak);
+        assert_eq!(passkey_strength("aaaaaaaaaaaaaaaaaaaaaaaaaaaaaa"), Strength::Weak);
+        assert_eq!(passkey_strength("Tr0ub4dor&3xyz"), Strength::Ok);
+        let generated = generate_passkey_sha1("host-a", 100, 200);
+        assert_eq!(passkey_strength(&generated), Strength::Strong);
+        assert_eq!(
+            passkey_strength("9f86d081884c7d659a2feaa0c55ad015a3bf4f1b"),
+            Strength::Strong
+        );
+    }
 }
 
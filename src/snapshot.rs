@@ -0,0 +1,251 @@
+//! Versioned snapshot format, decoupled from `SessionEvent` so internal changes
+//! never silently alter the JSON that tools diff across versions.
+
+use std::io::Write;
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::model::{AgentKind, Origin, RuntimeStateStore, SessionEvent, SessionStatus};
+
+pub const SNAPSHOT_V1: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SnapshotV1 {
+    #[serde(rename = "version")]
+    pub version: u32,
+    #[serde(rename = "sessions")]
+    pub sessions: Vec<SessionV1>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SessionV1 {
+    #[serde(rename = "id")]
+    pub id: String,
+    #[serde(rename = "agent")]
+    pub agent: String,
+    #[serde(rename = "title")]
+    pub title: String,
+    #[serde(rename = "working_dir")]
+    pub working_dir: String,
+    #[serde(rename = "user")]
+    pub user: String,
+    #[serde(rename = "status")]
+    pub status: String,
+    #[serde(rename = "pending_action")]
+    pub pending_action: Option<String>,
+    #[serde(rename = "started_at_unix_ms")]
+    pub started_at_unix_ms: u64,
+    #[serde(rename = "updated_at_unix_ms")]
+    pub updated_at_unix_ms: u64,
+    #[serde(rename = "last_lines")]
+    pub last_lines: Vec<String>,
+    /// Additive: omitted when unknown, so older v1 readers and diffs are unaffected.
+    #[serde(rename = "branch", default, skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+    #[serde(rename = "project", default, skip_serializing_if = "Option::is_none")]
+    pub project: Option<String>,
+    /// Peer a remote session came from; omitted for local sessions.
+    #[serde(rename = "peer", default, skip_serializing_if = "Option::is_none")]
+    pub peer: Option<String>,
+}
+
+impl From<&SessionEvent> for SessionV1 {
+    fn from(event: &SessionEvent) -> Self {
+        Self {
+            id: event.id.clone(),
+            agent: event.agent.as_label().to_string(),
+            title: event.title.clone(),
+            working_dir: event.working_dir.clone(),
+            user: event.user.clone(),
+            status: event.status.as_label().to_string(),
+            pending_action: event.pending_action.clone(),
+            started_at_unix_ms: event.started_at_unix_ms,
+            updated_at_unix_ms: event.updated_at_unix_ms,
+            last_lines: event.last_lines.clone(),
+            branch: event.branch.clone(),
+            project: event.project.clone(),
+            peer: event.origin.peer().map(str::to_string),
+        }
+    }
+}
+
+impl SessionV1 {
+    /// Unknown agent labels degrade to `Unknown` and unknown states to `Running`,
+    /// matching how external states are normalized elsewhere.
+    pub fn into_event(self) -> SessionEvent {
+        SessionEvent {
+            id: self.id,
+            agent: self.agent.parse().unwrap_or(AgentKind::Unknown),
+            title: self.title,
+            working_dir: self.working_dir,
+            user: self.user,
+            status: self.status.parse().unwrap_or(SessionStatus::Running),
+            pending_action: self.pending_action,
+            started_at_unix_ms: self.started_at_unix_ms,
+            updated_at_unix_ms: self.updated_at_unix_ms,
+            last_lines: self.last_lines,
+            idle: false,
+            origin: match self.peer {
+                Some(peer) => Origin::Remote { peer },
+                None => Origin::Local,
+            },
+            branch: self.branch,
+            project: self.project,
+        }
+    }
+}
+
+impl SnapshotV1 {
+    pub fn from_events(events: &[SessionEvent]) -> Self {
+        Self {
+            version: SNAPSHOT_V1,
+            sessions: events.iter().map(SessionV1::from).collect(),
+        }
+    }
+
+    pub fn from_store(store: &RuntimeStateStore) -> Self {
+        Self::from_events(&store.all())
+    }
+
+    pub fn into_events(self) -> Vec<SessionEvent> {
+        self.sessions
+            .into_iter()
+            .map(SessionV1::into_event)
+            .collect()
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    pub fn from_json(text: &str) -> Result<Self> {
+        let snapshot: SnapshotV1 = serde_json::from_str(text)?;
+        if snapshot.version != SNAPSHOT_V1 {
+            return Err(anyhow!(
+                "unsupported snapshot version {} (expected {SNAPSHOT_V1})",
+                snapshot.version
+            ));
+        }
+        Ok(snapshot)
+    }
+}
+
+/// One line of `--format jsonl` output: a v1 session stamped with the tick it was seen on.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct JsonlRecordV1 {
+    #[serde(rename = "tick_unix_ms")]
+    pub tick_unix_ms: u64,
+    /// The monitor's `--label`; additive, omitted when none was given.
+    #[serde(rename = "label", default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(flatten)]
+    pub session: SessionV1,
+}
+
+/// Appends one newline-delimited JSON record per session for a single tick.
+pub fn write_jsonl_tick<W: Write>(
+    out: &mut W,
+    events: &[SessionEvent],
+    tick_unix_ms: u64,
+    label: Option<&str>,
+) -> Result<()> {
+    for event in events {
+        let record = JsonlRecordV1 {
+            tick_unix_ms,
+            label: label.map(str::to_string),
+            session: SessionV1::from(event),
+        };
+        serde_json::to_writer(&mut *out, &record)?;
+        out.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::{AgentKind, Origin, RuntimeStateStore, SessionEvent, SessionStatus};
+
+    use super::{write_jsonl_tick, JsonlRecordV1, SnapshotV1};
+
+    fn event() -> SessionEvent {
+        SessionEvent {
+            id: "s-1".to_string(),
+            agent: AgentKind::Gemini,
+            title: "fix tests".to_string(),
+            working_dir: "/repo".to_string(),
+            user: "alice".to_string(),
+            status: SessionStatus::WaitingInput,
+            pending_action: Some("Confirm run".to_string()),
+            started_at_unix_ms: 10,
+            updated_at_unix_ms: 20,
+            last_lines: vec!["line".to_string()],
+            idle: false,
+            origin: Origin::Local,
+            branch: None,
+            project: None,
+        }
+    }
+
+    #[test]
+    fn v1_json_shape_is_stable() {
+        let json = SnapshotV1::from_events(&[event()]).to_json().expect("json");
+        assert_eq!(
+            json,
+            concat!(
+                r#"{"version":1,"sessions":[{"id":"s-1","agent":"gemini","title":"fix tests","#,
+                r#""working_dir":"/repo","user":"alice","status":"waiting_input","#,
+                r#""pending_action":"Confirm run","started_at_unix_ms":10,"#,
+                r#""updated_at_unix_ms":20,"last_lines":["line"]}]}"#
+            )
+        );
+    }
+
+    #[test]
+    fn v1_round_trips_store_events() {
+        let mut store = RuntimeStateStore::default();
+        store.upsert(event());
+        let json = SnapshotV1::from_store(&store).to_json().expect("json");
+        let events = SnapshotV1::from_json(&json).expect("parse").into_events();
+        assert_eq!(events, store.all());
+    }
+
+    #[test]
+    fn v1_round_trips_remote_origin_as_peer() {
+        let mut remote = event();
+        remote.origin = Origin::Remote {
+            peer: "build-box".to_string(),
+        };
+        let json = SnapshotV1::from_events(&[remote.clone()]).to_json().expect("json");
+        assert!(json.contains("\"peer\":\"build-box\""), "{json}");
+        let events = SnapshotV1::from_json(&json).expect("parse").into_events();
+        assert_eq!(events, vec![remote]);
+    }
+
+    #[test]
+    fn jsonl_ticks_emit_one_parseable_record_per_line() {
+        let mut second = event();
+        second.id = "s-2".to_string();
+        let mut out = Vec::new();
+        write_jsonl_tick(&mut out, &[event(), second], 100, None).expect("tick 1");
+        write_jsonl_tick(&mut out, &[event()], 200, Some("prod")).expect("tick 2");
+
+        let text = String::from_utf8(out).expect("utf8");
+        let records = text
+            .lines()
+            .map(|line| serde_json::from_str::<JsonlRecordV1>(line).expect("valid record"))
+            .collect::<Vec<_>>();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0].tick_unix_ms, 100);
+        assert_eq!(records[1].session.id, "s-2");
+        assert_eq!(records[2].tick_unix_ms, 200);
+        assert!(!text.lines().next().unwrap_or_default().contains("\"label\""));
+        assert_eq!(records[2].label.as_deref(), Some("prod"));
+        assert_eq!(records[2].session.clone().into_event(), event());
+    }
+
+    #[test]
+    fn rejects_unknown_version() {
+        assert!(SnapshotV1::from_json(r#"{"version":2,"sessions":[]}"#).is_err());
+    }
+}
@@ -1,12 +1,16 @@
-use std::io::{Read, Write};
-use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
-use std::time::Duration;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use anyhow::{anyhow, Result};
 use serde::{Deserialize, Serialize};
 
-use crate::model::SessionEvent;
-use crate::security::SecurityLayer;
+use crate::model::{Origin, SessionEvent, MAX_LAST_LINES, MAX_LINE_CHARS};
+use crate::security::{KeyClass, RedactionPolicy, SecurityLayer};
+use crate::transport::{Connection, TcpTransport, Transport};
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 pub enum TransportProtocol {
@@ -21,18 +25,132 @@ pub struct SyncEnvelope {
     pub nonce: u64,
     pub protocol: TransportProtocol,
     pub payload: Vec<SessionEvent>,
+    /// Set when the puller authenticated with an observer key; its own sessions were not accepted.
+    #[serde(default)]
+    pub read_only: bool,
+    /// Address the serving host accepted the pull on; filled by the server, not the caller.
+    #[serde(default)]
+    pub source_ip: String,
+    /// Self-reported host name of the serving host; empty when it did not set one.
+    #[serde(default)]
+    pub source_host: String,
+    /// Answer to a heartbeat: the peer is reachable, and `payload` is deliberately empty.
+    #[serde(default)]
+    pub heartbeat: bool,
+}
+
+impl SyncEnvelope {
+    /// Name to namespace this envelope's sessions under: the server's host name, then
+    /// its address, then its `peer` label, then `fallback` (usually the dialed target).
+    pub fn source_name<'a>(&'a self, fallback: &'a str) -> &'a str {
+        [
+            self.source_host.as_str(),
+            self.source_ip.as_str(),
+            self.peer.as_str(),
+        ]
+        .into_iter()
+        .map(str::trim)
+        .find(|name| !name.is_empty())
+        .unwrap_or(fallback)
+    }
+}
+
+/// Wire protocol version. v2 replaced the plaintext `auth_key` with a challenge-response:
+/// the server opens with a `Challenge` line and the client answers with an HMAC proof.
+pub const PROTOCOL_VERSION: u32 = 2;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Challenge {
+    version: u32,
+    nonce: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct PullRequest {
-    auth_key: String,
+    #[serde(default)]
+    version: u32,
+    /// Fingerprint lookup only; needs no proof and receives no sessions.
+    #[serde(default)]
+    discover: bool,
+    /// Liveness probe: authenticated like a pull, but no sessions travel either way.
+    #[serde(default)]
+    heartbeat: bool,
+    #[serde(default)]
+    proof: String,
     peer: String,
     payload: Vec<SessionEvent>,
 }
 
+impl PullRequest {
+    fn new(peer: &str, heartbeat: bool, payload: Vec<SessionEvent>) -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            discover: false,
+            heartbeat,
+            proof: String::new(),
+            peer: peer.to_string(),
+            payload,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct DiscoveryResponse {
-    auth_key: String,
+    fingerprint: String,
+}
+
+/// How much of each session this host shares with peers, on top of `RedactionPolicy`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ShareProfile {
+    /// Every field, after redaction.
+    #[default]
+    Full,
+    /// Agent kind, status, user and timestamps only: title, dir, branch, project,
+    /// transcript lines and pending action are blanked.
+    StatusOnly,
+    /// Like `Full`, but title, dir, branch, project and pending action are replaced by
+    /// pseudonyms stable for the session key, and transcript lines are dropped. Peers can
+    /// still tell sessions in the same dir apart from the rest.
+    Redacted,
+}
+
+impl ShareProfile {
+    pub fn apply(self, security: &SecurityLayer, mut event: SessionEvent) -> SessionEvent {
+        match self {
+            ShareProfile::Full => return event,
+            ShareProfile::StatusOnly => {
+                event.title.clear();
+                event.working_dir.clear();
+                event.branch = None;
+                event.project = None;
+                event.pending_action = None;
+            }
+            ShareProfile::Redacted => {
+                let hide = |kind: &str, text: &str| match text {
+                    "" => String::new(),
+                    text => security.pseudonym(kind, text),
+                };
+                event.title = hide("title", &event.title);
+                event.working_dir = hide("dir", &event.working_dir);
+                event.branch = event.branch.map(|branch| hide("branch", &branch));
+                event.project = event.project.map(|project| hide("project", &project));
+                event.pending_action = event.pending_action.map(|action| hide("action", &action));
+            }
+        }
+        event.last_lines.clear();
+        event
+    }
+}
+
+/// How much randomness to mix into a backoff delay so reconnecting peers spread out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JitterMode {
+    /// Exactly the exponential bound; deterministic.
+    None,
+    /// Uniform in `[0, bound]`.
+    Full,
+    /// Uniform in `[bound / 2, bound]`.
+    Equal,
 }
 
 #[derive(Debug, Clone)]
@@ -40,6 +158,7 @@ pub struct RetryPolicy {
     pub max_attempts: u32,
     pub base_delay_ms: u64,
     pub max_delay_ms: u64,
+    pub jitter: JitterMode,
 }
 
 impl Default for RetryPolicy {
@@ -48,37 +167,125 @@ impl Default for RetryPolicy {
             max_attempts: 5,
             base_delay_ms: 200,
             max_delay_ms: 2_000,
+            jitter: JitterMode::None,
         }
     }
 }
 
 impl RetryPolicy {
     pub fn delay_for_attempt(&self, attempt: u32) -> Duration {
+        self.delay_for_attempt_with(attempt, &mut jitter_random)
+    }
+
+    /// Same as `delay_for_attempt` with an injected random source for the jitter.
+    pub fn delay_for_attempt_with<R: FnMut() -> u64>(
+        &self,
+        attempt: u32,
+        random: &mut R,
+    ) -> Duration {
         let exp = 2_u64.saturating_pow(attempt.saturating_sub(1));
-        let raw = self.base_delay_ms.saturating_mul(exp);
-        Duration::from_millis(raw.min(self.max_delay_ms))
+        let bound = self.base_delay_ms.saturating_mul(exp).min(self.max_delay_ms);
+        let delay = match self.jitter {
+            JitterMode::None => bound,
+            JitterMode::Full => random() % (bound + 1),
+            JitterMode::Equal => bound / 2 + random() % (bound - bound / 2 + 1),
+        };
+        Duration::from_millis(delay)
     }
 }
 
-#[derive(Debug, Clone)]
+// splitmix64 over a shared counter, seeded by the clock; good enough to de-synchronize peers.
+fn jitter_random() -> u64 {
+    static STATE: AtomicU64 = AtomicU64::new(0);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0);
+    let mut z = STATE
+        .fetch_add(0x9E37_79B9_7F4A_7C15, Ordering::Relaxed)
+        .wrapping_add(nanos);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// `peer` of an envelope prepared with an empty name by a client with no name of its own.
+pub const FALLBACK_PEER_NAME: &str = "agent-box";
+
+#[derive(Clone)]
 pub struct SyncClient {
     security: SecurityLayer,
+    transport: Arc<dyn Transport>,
+    strict_peer: bool,
+    name: String,
+    max_response_bytes: usize,
+    share: ShareProfile,
+}
+
+impl std::fmt::Debug for SyncClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SyncClient")
+            .field("security", &self.security)
+            .field("strict_peer", &self.strict_peer)
+            .field("name", &self.name)
+            .field("max_response_bytes", &self.max_response_bytes)
+            .field("share", &self.share)
+            .finish()
+    }
 }
 
 impl SyncClient {
     pub fn new(shared_key: &str) -> Self {
         Self {
             security: SecurityLayer::new(shared_key),
+            transport: Arc::new(TcpTransport::new()),
+            strict_peer: false,
+            name: String::new(),
+            max_response_bytes: DEFAULT_MAX_RESPONSE_BYTES,
+            share: ShareProfile::Full,
         }
     }
 
-    pub fn handshake(&self, provided_key: &str) -> Result<()> {
-        if !self.security.verify_key(provided_key) {
-            return Err(anyhow!("invalid auth key"));
-        }
-        Ok(())
+    /// Name stamped as `peer` on envelopes prepared with an empty one, usually the host
+    /// name; without it `FALLBACK_PEER_NAME` is used.
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = name.trim().to_string();
+        self
+    }
+
+    /// Dials peers over `transport` instead of TCP.
+    pub fn with_transport(mut self, transport: Arc<dyn Transport>) -> Self {
+        self.transport = transport;
+        self
     }
 
+    /// Which fields of pushed sessions are scrubbed; see `RedactionPolicy`.
+    pub fn with_redaction(mut self, policy: RedactionPolicy) -> Self {
+        self.security = self.security.with_redaction(policy);
+        self
+    }
+
+    /// Rejects replies whose `source_host`, `source_ip` and `peer` all name some host
+    /// other than the one dialed, instead of trusting the claim for namespacing.
+    pub fn with_strict_peer(mut self, strict: bool) -> Self {
+        self.strict_peer = strict;
+        self
+    }
+
+    /// How much of each outgoing session is shared; see `ShareProfile`.
+    pub fn with_share_profile(mut self, share: ShareProfile) -> Self {
+        self.share = share;
+        self
+    }
+
+    /// Largest reply accepted from a peer; a longer one fails the pull.
+    pub fn with_max_response_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_response_bytes = max_bytes;
+        self
+    }
+
+    /// Redacts and caps `events`, sorted by id so the payload does not depend on the
+    /// order the caller collected them in. A blank `peer` becomes this client's name.
     pub fn prepare_envelope(
         &self,
         peer: String,
@@ -86,16 +293,82 @@ impl SyncClient {
         protocol: TransportProtocol,
         events: Vec<SessionEvent>,
     ) -> SyncEnvelope {
-        let filtered = events
+        let mut filtered = events
             .into_iter()
-            .map(|event| self.security.filter_sensitive(event))
-            .collect();
+            .map(|event| self.outbound_event(event))
+            .collect::<Vec<_>>();
+        filtered.sort_by(|a, b| a.id.cmp(&b.id));
+        let peer = match peer.trim() {
+            "" if self.name.is_empty() => FALLBACK_PEER_NAME.to_string(),
+            "" => self.name.clone(),
+            _ => peer,
+        };
         SyncEnvelope {
             peer,
             nonce,
             protocol,
             payload: filtered,
+            read_only: false,
+            source_ip: String::new(),
+            source_host: String::new(),
+            heartbeat: false,
+        }
+    }
+
+    /// Like `prepare_envelope`, but drops events until the encoded envelope fits in
+    /// `max_bytes`: finished sessions go first, then the least recently updated ones.
+    pub fn prepare_envelope_capped(
+        &self,
+        peer: String,
+        nonce: u64,
+        protocol: TransportProtocol,
+        events: Vec<SessionEvent>,
+        max_bytes: usize,
+    ) -> SyncEnvelope {
+        let mut envelope = self.prepare_envelope(peer, nonce, protocol, events);
+        let mut size = self.encoded_size(&envelope);
+        if size <= max_bytes {
+            return envelope;
+        }
+        let payload = &envelope.payload;
+        let mut order = (0..payload.len()).collect::<Vec<_>>();
+        order.sort_by(|&a, &b| {
+            let (a, b) = (&payload[a], &payload[b]);
+            b.status
+                .is_terminal()
+                .cmp(&a.status.is_terminal())
+                .then_with(|| a.updated_at_unix_ms.cmp(&b.updated_at_unix_ms))
+                .then_with(|| a.id.cmp(&b.id))
+        });
+        let mut dropped = vec![false; payload.len()];
+        for idx in order {
+            if size <= max_bytes {
+                break;
+            }
+            // An event costs its own JSON plus the comma separating it from a neighbour.
+            let event_bytes = serde_json::to_vec(&payload[idx]).map_or(0, |json| json.len());
+            size = size.saturating_sub(event_bytes + 1);
+            dropped[idx] = true;
         }
+        let mut dropped = dropped.into_iter();
+        envelope
+            .payload
+            .retain(|_| !dropped.next().unwrap_or(false));
+        envelope
+    }
+
+    /// Bytes `encode_envelope` would put on the wire for `envelope`.
+    pub fn encoded_size(&self, envelope: &SyncEnvelope) -> usize {
+        // The transport transform is byte-for-byte, so the JSON length is the wire length.
+        serde_json::to_vec(envelope).map_or(0, |json| json.len())
+    }
+
+    /// Caps, redacts and JSON-sanitizes one event before it leaves this host.
+    fn outbound_event(&self, event: SessionEvent) -> SessionEvent {
+        let event = self
+            .security
+            .filter_sensitive(event.truncated(MAX_LAST_LINES, MAX_LINE_CHARS));
+        sanitize_wire_event(self.share.apply(&self.security, event))
     }
 
     pub fn encode_envelope(&self, envelope: &SyncEnvelope) -> Result<Vec<u8>> {
@@ -105,10 +378,13 @@ impl SyncClient {
 
     pub fn decode_envelope(&self, bytes: &[u8]) -> Result<SyncEnvelope> {
         let plain = decrypt_like_transport(bytes);
-        let envelope: SyncEnvelope = serde_json::from_slice(&plain)?;
-        Ok(envelope)
+        serde_json::from_slice(&plain)
+            .map_err(|e| anyhow!("malformed sync envelope from peer: {e}"))
     }
 
+    /// Pulls the peer's sessions and pushes `local_events` to it. `auth_key` must be a
+    /// well-formed key matching this client (checked before dialing); whether the peer
+    /// accepts it is decided by the server, which answers a bad proof with no envelope.
     pub fn pull_once(
         &self,
         peer_host: &str,
@@ -118,67 +394,212 @@ impl SyncClient {
         local_events: Vec<SessionEvent>,
         timeout: Duration,
     ) -> Result<SyncEnvelope> {
-        self.handshake(auth_key)?;
-        let addr = resolve_addr(peer_host, port)?;
-        let mut stream = TcpStream::connect_timeout(&addr, timeout)
-            .map_err(|e| anyhow!("connect failed to {peer_host}:{port}: {e}"))?;
-        stream.set_read_timeout(Some(timeout)).ok();
-        stream.set_write_timeout(Some(timeout)).ok();
-
         let outbound = local_events
             .into_iter()
-            .map(|event| self.security.filter_sensitive(event))
+            .map(|event| self.outbound_event(event))
             .collect::<Vec<_>>();
-        let request = PullRequest {
-            auth_key: auth_key.to_string(),
-            peer: local_peer.to_string(),
-            payload: outbound,
-        };
-        let request_bytes = serde_json::to_vec(&request)?;
-        stream.write_all(&request_bytes)?;
-        stream.shutdown(Shutdown::Write).ok();
+        let request = PullRequest::new(local_peer, false, outbound);
+        self.request(peer_host, port, auth_key, request, timeout)
+    }
 
-        let mut bytes = Vec::new();
-        stream.read_to_end(&mut bytes)?;
+    /// Checks that the peer is up and accepts our key without moving any sessions. The
+    /// answer is an envelope with `heartbeat` set and an empty payload.
+    pub fn heartbeat(
+        &self,
+        peer_host: &str,
+        port: u16,
+        auth_key: &str,
+        local_peer: &str,
+        timeout: Duration,
+    ) -> Result<SyncEnvelope> {
+        let request = PullRequest::new(local_peer, true, Vec::new());
+        self.request(peer_host, port, auth_key, request, timeout)
+    }
+
+    /// Answers the peer's challenge with `request`, proof filled in, and decodes the reply.
+    fn request(
+        &self,
+        peer_host: &str,
+        port: u16,
+        auth_key: &str,
+        mut request: PullRequest,
+        timeout: Duration,
+    ) -> Result<SyncEnvelope> {
+        validate_auth_key(auth_key)?;
+        let stream = self.transport.connect(peer_host, port, timeout)?;
+        let mut reader = BufReader::new(stream);
+        let challenge = read_challenge(&mut reader)?;
+
+        request.proof = self.security.respond_to_challenge(&challenge.nonce);
+        let deadline = Instant::now() + timeout;
+        let bytes = exchange(&mut reader, &request, deadline, self.max_response_bytes)?;
         if bytes.is_empty() {
             return Err(anyhow!("empty sync response from peer"));
         }
-        self.decode_envelope(&bytes)
+        let envelope = self.decode_envelope(&bytes)?;
+        if self.strict_peer && !envelope_matches_target(&envelope, peer_host) {
+            return Err(anyhow!(
+                "peer identity mismatch: dialed {peer_host} but it answered as {}",
+                envelope.source_name(peer_host)
+            ));
+        }
+        Ok(envelope)
+    }
+}
+
+/// Whether any name the envelope reports for its server is `target`. An envelope that
+/// names nothing is namespaced under `target` anyway, so it matches too.
+fn envelope_matches_target(envelope: &SyncEnvelope, target: &str) -> bool {
+    let target = target.trim();
+    let mut names = [
+        envelope.source_host.as_str(),
+        envelope.source_ip.as_str(),
+        envelope.peer.as_str(),
+    ]
+    .into_iter()
+    .map(str::trim)
+    .filter(|name| !name.is_empty())
+    .peekable();
+    names.peek().is_none() || names.any(|name| name.eq_ignore_ascii_case(target))
+}
+
+/// Rejects keys that can never authenticate: empty, or containing whitespace/control chars.
+pub fn validate_auth_key(key: &str) -> Result<()> {
+    if key.is_empty() {
+        return Err(anyhow!("auth key must be non-empty"));
+    }
+    if key.chars().any(|c| c.is_whitespace() || c.is_control()) {
+        return Err(anyhow!("auth key must not contain whitespace or control characters"));
     }
+    Ok(())
 }
 
-pub fn discover_join_key(peer_host: &str, port: u16, timeout: Duration) -> Result<String> {
-    let addr = resolve_addr(peer_host, port)?;
-    let mut stream = TcpStream::connect_timeout(&addr, timeout)
-        .map_err(|e| anyhow!("connect failed to {peer_host}:{port}: {e}"))?;
-    stream.set_read_timeout(Some(timeout)).ok();
-    stream.set_write_timeout(Some(timeout)).ok();
+/// Asks a peer for the fingerprint of its session key. The key itself never leaves the
+/// server; joiners compare this against `SecurityLayer::fingerprint` of the key they
+/// were given out-of-band.
+pub fn fetch_key_fingerprint(peer_host: &str, port: u16, timeout: Duration) -> Result<String> {
+    fetch_key_fingerprint_via(&TcpTransport::new(), peer_host, port, timeout)
+}
+
+pub fn fetch_key_fingerprint_via(
+    transport: &dyn Transport,
+    peer_host: &str,
+    port: u16,
+    timeout: Duration,
+) -> Result<String> {
+    let stream = transport.connect(peer_host, port, timeout)?;
+    let mut reader = BufReader::new(stream);
+    read_challenge(&mut reader)?;
 
     let request = PullRequest {
-        auth_key: "__discover__".to_string(),
+        version: PROTOCOL_VERSION,
+        discover: true,
+        heartbeat: false,
+        proof: String::new(),
         peer: "discover".to_string(),
         payload: vec![],
     };
-    let request_bytes = serde_json::to_vec(&request)?;
-    stream.write_all(&request_bytes)?;
-    stream.shutdown(Shutdown::Write).ok();
-
-    let mut bytes = Vec::new();
-    stream.read_to_end(&mut bytes)?;
+    let deadline = Instant::now() + timeout;
+    let bytes = exchange(&mut reader, &request, deadline, DEFAULT_MAX_RESPONSE_BYTES)?;
     if bytes.is_empty() {
         return Err(anyhow!("empty discovery response from peer"));
     }
     let response: DiscoveryResponse = serde_json::from_slice(&bytes)?;
-    if response.auth_key.trim().is_empty() {
-        return Err(anyhow!("peer returned empty discovery key"));
+    if response.fingerprint.trim().is_empty() {
+        return Err(anyhow!("peer returned empty key fingerprint"));
     }
-    Ok(response.auth_key)
+    Ok(response.fingerprint)
 }
 
+/// Largest reply a client reads from a peer by default.
+pub const DEFAULT_MAX_RESPONSE_BYTES: usize = 16 * 1024 * 1024;
+/// How much of a reply the client asks the connection for per read.
+const RESPONSE_CHUNK_BYTES: usize = 16 * 1024;
+/// Most connections `serve_once` handles concurrently by default; further ones wait in
+/// the backlog.
+pub const DEFAULT_MAX_SERVE_WORKERS: usize = 8;
+/// Largest pull request a server reads by default; bigger ones are dropped unanswered.
+pub const DEFAULT_MAX_REQUEST_BYTES: usize = 4 * 1024 * 1024;
+/// How long `serve_once` keeps accepting new connections within one tick, and how long
+/// any connection it accepted may take to deliver its request.
+const SERVE_DEADLINE: Duration = Duration::from_millis(1_000);
+
 pub struct SyncServer {
-    listener: TcpListener,
+    transport: Arc<dyn Transport>,
     security: SecurityLayer,
-    shared_key: String,
+    source_host: String,
+    max_request_bytes: usize,
+    max_workers: usize,
+    share: ShareProfile,
+}
+
+/// Collects `SyncServer` options; finish with `bind` (TCP) or `build` (any transport).
+#[derive(Debug, Clone)]
+pub struct SyncServerBuilder {
+    security: SecurityLayer,
+    source_host: String,
+    max_request_bytes: usize,
+    max_workers: usize,
+    share: ShareProfile,
+}
+
+impl SyncServerBuilder {
+    pub fn new(shared_key: &str) -> Self {
+        Self {
+            security: SecurityLayer::new(shared_key),
+            source_host: String::new(),
+            max_request_bytes: DEFAULT_MAX_REQUEST_BYTES,
+            max_workers: DEFAULT_MAX_SERVE_WORKERS,
+            share: ShareProfile::Full,
+        }
+    }
+
+    /// How much of each served session peers get to see; see `ShareProfile`.
+    pub fn with_share_profile(mut self, share: ShareProfile) -> Self {
+        self.share = share;
+        self
+    }
+
+    /// Host name stamped into every served envelope as `source_host`.
+    pub fn with_source_host(mut self, host: &str) -> Self {
+        self.source_host = host.trim().to_string();
+        self
+    }
+
+    /// Which fields of served sessions are scrubbed; see `RedactionPolicy`.
+    pub fn with_redaction(mut self, policy: RedactionPolicy) -> Self {
+        self.security = self.security.with_redaction(policy);
+        self
+    }
+
+    /// Requests larger than this many bytes are dropped without an answer.
+    pub fn with_max_request_bytes(mut self, bytes: usize) -> Self {
+        self.max_request_bytes = bytes;
+        self
+    }
+
+    /// Connections handled at once by `serve_once` (at least one).
+    pub fn with_max_workers(mut self, workers: usize) -> Self {
+        self.max_workers = workers.max(1);
+        self
+    }
+
+    pub fn bind(self, ip: &str, port: u16) -> Result<SyncServer> {
+        let transport = TcpTransport::bind(ip, port)?;
+        Ok(self.build(Arc::new(transport)))
+    }
+
+    /// Serves connections accepted from `transport` instead of a TCP listener.
+    pub fn build(self, transport: Arc<dyn Transport>) -> SyncServer {
+        SyncServer {
+            transport,
+            security: self.security,
+            source_host: self.source_host,
+            max_request_bytes: self.max_request_bytes,
+            max_workers: self.max_workers,
+            share: self.share,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -188,17 +609,25 @@ pub struct IncomingPeerUpdate {
 }
 
 impl SyncServer {
+    /// Shortcut for `SyncServer::builder(shared_key).bind(ip, port)`.
     pub fn bind(ip: &str, port: u16, shared_key: &str) -> Result<Self> {
-        let listener =
-            TcpListener::bind(format!("{ip}:{port}")).map_err(|e| anyhow!("bind failed: {e}"))?;
-        listener.set_nonblocking(true)?;
-        Ok(Self {
-            listener,
-            security: SecurityLayer::new(shared_key),
-            shared_key: shared_key.to_string(),
-        })
+        Self::builder(shared_key).bind(ip, port)
     }
 
+    pub fn builder(shared_key: &str) -> SyncServerBuilder {
+        SyncServerBuilder::new(shared_key)
+    }
+
+    /// Serves connections accepted from `transport` instead of a TCP listener.
+    pub fn with_transport(transport: Arc<dyn Transport>, shared_key: &str) -> Self {
+        Self::builder(shared_key).build(transport)
+    }
+
+    /// Serves every pending connection, each on its own short-lived worker thread, so a
+    /// slow client cannot hold up the others. Keeps accepting while workers are busy, up
+    /// to the configured worker count at once and until `SERVE_DEADLINE` has passed, which
+    /// also cuts off requests still trickling in; returns the sessions pushed by full-key
+    /// peers.
     pub fn serve_once(
         &self,
         local_events: Vec<SessionEvent>,
@@ -206,69 +635,385 @@ impl SyncServer {
         nonce: u64,
         protocol: TransportProtocol,
     ) -> Result<Vec<IncomingPeerUpdate>> {
+        let deadline = Instant::now() + SERVE_DEADLINE;
+        let local_events = &local_events;
+        let (outcomes, accept_error) = thread::scope(|scope| {
+            let mut workers: Vec<thread::ScopedJoinHandle<ServeOutcome>> = Vec::new();
+            let mut accept_error = None;
+            loop {
+                let live = workers.iter().filter(|w| !w.is_finished()).count();
+                if Instant::now() < deadline && live < self.max_workers {
+                    match self.transport.accept() {
+                        Ok(Some(stream)) => {
+                            workers.push(scope.spawn(move || {
+                                self.handle_connection(
+                                    stream,
+                                    local_events,
+                                    peer_name,
+                                    nonce,
+                                    protocol,
+                                    deadline,
+                                )
+                            }));
+                            continue;
+                        }
+                        Ok(None) => {}
+                        Err(err) => {
+                            accept_error = Some(err);
+                            break;
+                        }
+                    }
+                }
+                if workers.iter().all(|w| w.is_finished()) || Instant::now() >= deadline {
+                    break;
+                }
+                thread::sleep(Duration::from_millis(5));
+            }
+            let outcomes = workers
+                .into_iter()
+                .map(|w| w.join().unwrap_or(ServeOutcome::Dropped))
+                .collect::<Vec<_>>();
+            (outcomes, accept_error)
+        });
+
         let mut served = 0usize;
         let mut incoming_updates = Vec::new();
-        loop {
-            let (mut stream, _) = match self.listener.accept() {
-                Ok(v) => v,
-                Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => break,
-                Err(err) => return Err(anyhow!("accept failed: {err}")),
-            };
-            stream.set_nonblocking(false).ok();
-            stream.set_read_timeout(Some(Duration::from_millis(300))).ok();
-            stream
-                .set_write_timeout(Some(Duration::from_millis(300)))
-                .ok();
-
-            let mut bytes = Vec::new();
-            stream.read_to_end(&mut bytes)?;
-            if bytes.is_empty() {
-                continue;
+        for outcome in outcomes {
+            match outcome {
+                ServeOutcome::Dropped => {}
+                ServeOutcome::Served(update) => {
+                    served += 1;
+                    incoming_updates.extend(update);
+                }
             }
-            let req: PullRequest = match serde_json::from_slice(&bytes) {
-                Ok(v) => v,
-                Err(_) => continue,
+        }
+        let _ = served;
+        if let Some(err) = accept_error {
+            return Err(anyhow!("accept failed: {err}"));
+        }
+        Ok(incoming_updates)
+    }
+
+    fn handle_connection(
+        &self,
+        mut stream: Box<dyn Connection>,
+        local_events: &[SessionEvent],
+        peer_name: &str,
+        nonce: u64,
+        protocol: TransportProtocol,
+        deadline: Instant,
+    ) -> ServeOutcome {
+        let challenge = Challenge {
+            version: PROTOCOL_VERSION,
+            nonce: challenge_nonce(),
+        };
+        let Ok(mut line) = serde_json::to_vec(&challenge) else {
+            return ServeOutcome::Dropped;
+        };
+        line.push(b'\n');
+        if stream.write_all(&line).is_err() {
+            return ServeOutcome::Dropped;
+        }
+
+        let bytes = match read_bounded(&mut stream, deadline, self.max_request_bytes) {
+            Ok(bytes) if !bytes.is_empty() => bytes,
+            _ => return ServeOutcome::Dropped,
+        };
+        let req: PullRequest = match serde_json::from_slice(&bytes) {
+            Ok(v) => v,
+            Err(_) => return ServeOutcome::Dropped,
+        };
+        if req.version != PROTOCOL_VERSION {
+            return ServeOutcome::Dropped;
+        }
+        if req.discover {
+            let resp = DiscoveryResponse {
+                fingerprint: self.security.fingerprint(),
             };
-            if req.auth_key == "__discover__" {
-                let resp = DiscoveryResponse {
-                    auth_key: self.shared_key.clone(),
-                };
-                let serialized = serde_json::to_vec(&resp)?;
-                stream.write_all(&serialized)?;
-                served += 1;
-                continue;
+            let written = serde_json::to_vec(&resp)
+                .map_err(anyhow::Error::from)
+                .and_then(|bytes| Ok(stream.write_all(&bytes)?));
+            return match written {
+                Ok(()) => ServeOutcome::Served(None),
+                Err(_) => ServeOutcome::Dropped,
+            };
+        }
+        let Some(key_class) = self.security.classify_response(&challenge.nonce, &req.proof)
+        else {
+            return ServeOutcome::Dropped;
+        };
+
+        let client = SyncClient {
+            security: self.security.clone(),
+            transport: Arc::clone(&self.transport),
+            strict_peer: false,
+            name: self.source_host.clone(),
+            max_response_bytes: DEFAULT_MAX_RESPONSE_BYTES,
+            share: self.share,
+        };
+        let shared = if req.heartbeat {
+            Vec::new()
+        } else {
+            local_events.to_vec()
+        };
+        let mut envelope = client.prepare_envelope(peer_name.to_string(), nonce, protocol, shared);
+        envelope.heartbeat = req.heartbeat;
+        envelope.read_only = key_class == KeyClass::Observer;
+        envelope.source_ip = stream
+            .local_ip()
+            .map(|ip| ip.to_string())
+            .unwrap_or_default();
+        envelope.source_host = self.source_host.clone();
+        let written = client
+            .encode_envelope(&envelope)
+            .and_then(|encoded| Ok(stream.write_all(&encoded)?));
+        if written.is_err() {
+            return ServeOutcome::Dropped;
+        }
+        // Observers only ever pull; anything they push is dropped. A heartbeat still
+        // reports the peer, with no sessions, so it counts as alive on this side too.
+        let payload = if req.heartbeat { Vec::new() } else { req.payload };
+        let update = (key_class == KeyClass::Full).then_some(IncomingPeerUpdate {
+            peer: req.peer,
+            payload,
+        });
+        ServeOutcome::Served(update)
+    }
+}
+
+/// Result of one worker's connection: served (with the peer's push, if accepted) or not.
+enum ServeOutcome {
+    Dropped,
+    Served(Option<IncomingPeerUpdate>),
+}
+
+/// Reads the server's opening challenge line and checks the protocol version.
+fn read_challenge(reader: &mut BufReader<Box<dyn Connection>>) -> Result<Challenge> {
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    if line.trim().is_empty() {
+        return Err(anyhow!("peer closed before sending a challenge"));
+    }
+    let challenge: Challenge =
+        serde_json::from_str(&line).map_err(|e| anyhow!("malformed challenge from peer: {e}"))?;
+    if challenge.version != PROTOCOL_VERSION {
+        return Err(anyhow!(
+            "peer speaks sync protocol v{}, this build speaks v{PROTOCOL_VERSION}",
+            challenge.version
+        ));
+    }
+    Ok(challenge)
+}
+
+/// Sends `request`, half-closes the connection, and returns everything the peer replies
+/// by `deadline`, up to `max_bytes`.
+fn exchange(
+    reader: &mut BufReader<Box<dyn Connection>>,
+    request: &PullRequest,
+    deadline: Instant,
+    max_bytes: usize,
+) -> Result<Vec<u8>> {
+    let request_bytes = serde_json::to_vec(request)?;
+    reader.get_mut().write_all(&request_bytes)?;
+    reader.get_mut().close_write().ok();
+    read_bounded(reader, deadline, max_bytes)
+}
+
+/// Reads until end-of-stream, which frames every sync message: the sender writes one
+/// message and closes its write half. Each chunk has to arrive within the connection's
+/// read timeout and the whole message by `deadline`, so a peer that stalls or trickles
+/// bytes is cut off rather than holding the reader, and a message over `max_bytes` is
+/// refused instead of buffered.
+fn read_bounded(reader: &mut impl Read, deadline: Instant, max_bytes: usize) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let mut chunk = vec![0; RESPONSE_CHUNK_BYTES];
+    loop {
+        let read = match reader.read(&mut chunk) {
+            Ok(0) => return Ok(bytes),
+            Ok(read) => read,
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(err)
+                if matches!(
+                    err.kind(),
+                    std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                ) =>
+            {
+                return Err(anyhow!(
+                    "peer stalled after {} bytes of its sync message",
+                    bytes.len()
+                ));
             }
-            if !self.security.verify_key(&req.auth_key) {
-                continue;
+            Err(err) => return Err(err.into()),
+        };
+        if bytes.len() + read > max_bytes {
+            return Err(anyhow!("sync message from peer exceeds {max_bytes} bytes"));
+        }
+        bytes.extend_from_slice(&chunk[..read]);
+        if Instant::now() >= deadline {
+            return Err(anyhow!(
+                "peer too slow: sync message incomplete after {} bytes",
+                bytes.len()
+            ));
+        }
+    }
+}
+
+fn challenge_nonce() -> String {
+    format!("{:016x}{:016x}", jitter_random(), jitter_random())
+}
+
+/// Rewrites a peer's event so it can live next to local sessions without id clashes.
+/// `user` is left as the peer reported it; the peer itself is recorded in `origin`.
+/// The peer's `updated_at_unix_ms` is kept so ordering and transition guards still hold.
+pub fn namespace_remote_event(mut event: SessionEvent, peer: &str) -> SessionEvent {
+    event.id = format!("remote:{peer}:{}", event.id);
+    event.origin = Origin::Remote {
+        peer: peer.to_string(),
+    };
+    event
+}
+
+/// How far ahead of local time a peer's timestamps may run before they are clamped.
+pub const DEFAULT_MAX_CLOCK_SKEW_MS: u64 = 5_000;
+
+/// Remote sessions keyed by namespaced id, kept across ticks to avoid flicker and
+/// evicted once they have not been refreshed within `ttl_ms`. Peers are tracked on their
+/// own, so one that answers with no sessions is still known to be alive.
+#[derive(Debug, Clone)]
+pub struct RemoteCache {
+    ttl_ms: u64,
+    max_skew_ms: u64,
+    entries: HashMap<String, (SessionEvent, u64)>,
+    // peer name -> local time it last answered a pull or heartbeat
+    peers: HashMap<String, u64>,
+}
+
+impl RemoteCache {
+    pub fn new(ttl_ms: u64) -> Self {
+        Self {
+            ttl_ms,
+            max_skew_ms: DEFAULT_MAX_CLOCK_SKEW_MS,
+            entries: HashMap::new(),
+            peers: HashMap::new(),
+        }
+    }
+
+    pub fn with_max_skew_ms(mut self, max_skew_ms: u64) -> Self {
+        self.max_skew_ms = max_skew_ms;
+        self
+    }
+
+    pub fn ttl_ms(&self) -> u64 {
+        self.ttl_ms
+    }
+
+    /// Stores `events` from `peer`, seen at local time `now`. An update that is older than
+    /// the cached event or an illegal transition (e.g. reviving a finished session) is
+    /// ignored, though the session still counts as seen. Timestamps more than the allowed
+    /// skew ahead of `now` are clamped to `now`, so a peer with a fast clock cannot pin
+    /// its sessions against later updates.
+    pub fn ingest(&mut self, peer: &str, events: Vec<SessionEvent>, now: u64) {
+        self.mark_alive(peer, now);
+        let latest = now.saturating_add(self.max_skew_ms);
+        for event in events {
+            let mut event = namespace_remote_event(event, peer);
+            if event.updated_at_unix_ms > latest {
+                event.updated_at_unix_ms = now;
+            }
+            if event.started_at_unix_ms > latest {
+                event.started_at_unix_ms = now.min(event.updated_at_unix_ms);
             }
+            match self.entries.get_mut(&event.id) {
+                Some((existing, seen_at)) => {
+                    *seen_at = now;
+                    if existing.accepts_update(&event) {
+                        *existing = event;
+                    }
+                }
+                None => {
+                    self.entries.insert(event.id.clone(), (event, now));
+                }
+            }
+        }
+    }
+
+    /// Records that `peer` answered at `now`, e.g. a heartbeat with no sessions.
+    pub fn mark_alive(&mut self, peer: &str, now: u64) {
+        self.peers.insert(peer.to_string(), now);
+    }
 
-            let client = SyncClient::new(&req.auth_key);
-            let envelope = client.prepare_envelope(
-                peer_name.to_string(),
-                nonce,
-                protocol,
-                local_events.clone(),
-            );
-            let encoded = client.encode_envelope(&envelope)?;
-            stream.write_all(&encoded)?;
-            incoming_updates.push(IncomingPeerUpdate {
-                peer: req.peer.clone(),
-                payload: req.payload.clone(),
-            });
-            served += 1;
+    pub fn is_alive(&self, peer: &str, now: u64) -> bool {
+        self.peers
+            .get(peer)
+            .is_some_and(|seen_at| now.saturating_sub(*seen_at) <= self.ttl_ms)
+    }
+
+    /// Peers that are still answering but have no cached sessions, sorted by name.
+    pub fn idle_peers(&self, now: u64) -> Vec<String> {
+        let mut idle = self
+            .peers
+            .keys()
+            .filter(|peer| self.is_alive(peer, now))
+            .filter(|peer| {
+                !self
+                    .entries
+                    .values()
+                    .any(|(event, _)| event.origin.peer() == Some(peer.as_str()))
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        idle.sort();
+        idle
+    }
+
+    pub fn prune(&mut self, now: u64) {
+        let ttl_ms = self.ttl_ms;
+        self.entries
+            .retain(|_, (_, seen_at)| now.saturating_sub(*seen_at) <= ttl_ms);
+        self.peers
+            .retain(|_, seen_at| now.saturating_sub(*seen_at) <= ttl_ms);
+    }
+
+    pub fn events(&self) -> Vec<SessionEvent> {
+        let mut items: Vec<_> = self.entries.values().map(|(e, _)| e.clone()).collect();
+        items.sort_by(|a, b| a.id.cmp(&b.id));
+        items
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// Replaces characters some JSON readers choke on (control characters other than tab,
+/// and Unicode noncharacters such as U+FFFF) with U+FFFD in every text field.
+fn sanitize_wire_event(mut event: SessionEvent) -> SessionEvent {
+    fn clean(text: &mut String) {
+        if text.chars().any(is_unsafe_wire_char) {
+            *text = text
+                .chars()
+                .map(|c| if is_unsafe_wire_char(c) { '\u{FFFD}' } else { c })
+                .collect();
         }
-        let _ = served;
-        Ok(incoming_updates)
     }
+    clean(&mut event.id);
+    clean(&mut event.title);
+    clean(&mut event.working_dir);
+    clean(&mut event.user);
+    event.pending_action.iter_mut().for_each(clean);
+    event.last_lines.iter_mut().for_each(clean);
+    event.branch.iter_mut().for_each(clean);
+    event.project.iter_mut().for_each(clean);
+    event
 }
 
-fn resolve_addr(host: &str, port: u16) -> Result<SocketAddr> {
-    let mut resolved = (host, port)
-        .to_socket_addrs()
-        .map_err(|e| anyhow!("resolve failed for {host}:{port}: {e}"))?;
-    resolved
-        .next()
-        .ok_or_else(|| anyhow!("no socket addresses for {host}:{port}"))
+fn is_unsafe_wire_char(c: char) -> bool {
+    let code = c as u32;
+    (c.is_control() && c != '\t') || (0xFDD0..=0xFDEF).contains(&code) || code & 0xFFFE == 0xFFFE
 }
 
 // Placeholder transport transform to model encrypted transport boundaries.
@@ -282,18 +1027,77 @@ fn decrypt_like_transport(input: &[u8]) -> Vec<u8> {
 
 #[cfg(test)]
 mod tests {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{mpsc, Arc};
     use std::thread;
-    use std::time::Duration;
+    use std::time::{Duration, Instant};
 
-    use crate::model::{AgentKind, SessionEvent, SessionStatus};
+    use crate::model::{
+        AgentKind, Origin, RuntimeStateStore, SessionEvent, SessionStatus, MAX_LAST_LINES,
+        MAX_LINE_CHARS,
+    };
+    use crate::security::{derive_observer_key, SecurityLayer};
+    use crate::transport::memory::MemoryTransport;
+    use crate::transport::Transport;
 
-    use super::{discover_join_key, RetryPolicy, SyncClient, SyncServer, TransportProtocol};
+    use super::{
+        fetch_key_fingerprint, namespace_remote_event, read_bounded, validate_auth_key, Challenge,
+        JitterMode, RemoteCache, RetryPolicy, ShareProfile, SyncClient, SyncServer,
+        TransportProtocol, FALLBACK_PEER_NAME, PROTOCOL_VERSION,
+    };
+
+    fn remote_event(id: &str) -> SessionEvent {
+        SessionEvent {
+            id: id.to_string(),
+            agent: AgentKind::Codex,
+            title: "t".to_string(),
+            working_dir: "/tmp".to_string(),
+            user: "bob".to_string(),
+            status: SessionStatus::Running,
+            pending_action: None,
+            started_at_unix_ms: 1,
+            updated_at_unix_ms: 2,
+            last_lines: vec![],
+            idle: false,
+            origin: Origin::Local,
+            branch: None,
+            project: None,
+        }
+    }
 
     #[test]
-    fn handshake_rejects_invalid_key() {
-        let client = SyncClient::new("abc");
-        assert!(client.handshake("wrong").is_err());
-        assert!(client.handshake("abc").is_ok());
+    fn status_only_profile_strips_titles_and_dirs_but_keeps_status() {
+        let mut event = remote_event("s1");
+        event.title = "rotate prod credentials".to_string();
+        event.working_dir = "/srv/secret-project".to_string();
+        event.status = SessionStatus::WaitingInput;
+        event.pending_action = Some("Approve: rm -rf build".to_string());
+        event.branch = Some("hotfix".to_string());
+        event.last_lines = vec!["deploying".to_string()];
+
+        let client = SyncClient::new("abc").with_share_profile(ShareProfile::StatusOnly);
+        let events = vec![event.clone()];
+        let envelope = client.prepare_envelope("p".to_string(), 1, TransportProtocol::Http, events);
+        let shared = &envelope.payload[0];
+        assert!(shared.title.is_empty());
+        assert!(shared.working_dir.is_empty());
+        assert_eq!(shared.branch, None);
+        assert_eq!(shared.pending_action, None);
+        assert!(shared.last_lines.is_empty());
+        assert_eq!(shared.status, SessionStatus::WaitingInput);
+        assert_eq!(shared.agent, AgentKind::Codex);
+        assert_eq!(shared.id, "s1");
+
+        let redacted = SyncClient::new("abc")
+            .with_share_profile(ShareProfile::Redacted)
+            .prepare_envelope("p".to_string(), 1, TransportProtocol::Http, vec![event]);
+        let shared = &redacted.payload[0];
+        assert!(shared.title.starts_with("title-"), "{}", shared.title);
+        assert!(shared.working_dir.starts_with("dir-"));
+        assert!(!shared.working_dir.contains("secret"));
+        assert!(shared.pending_action.as_deref().is_some_and(|a| a.starts_with("action-")));
     }
 
     #[test]
@@ -310,6 +1114,10 @@ mod tests {
             started_at_unix_ms: 1,
             updated_at_unix_ms: 2,
             last_lines: vec!["api_key=123".to_string()],
+            idle: false,
+            origin: Origin::Local,
+            branch: None,
+            project: None,
         };
         let env = client.prepare_envelope(
             "peer-a".to_string(),
@@ -322,6 +1130,214 @@ mod tests {
         assert_eq!(decoded.payload[0].last_lines[0], "api_key=[REDACTED]");
     }
 
+    #[test]
+    fn empty_peer_name_defaults_to_the_client_name() {
+        let named = SyncClient::new("abc").with_name(" laptop ");
+        let env = named.prepare_envelope(String::new(), 1, TransportProtocol::Http, vec![]);
+        assert_eq!(env.peer, "laptop");
+        let env = named.prepare_envelope("  ".to_string(), 1, TransportProtocol::Http, vec![]);
+        assert_eq!(env.peer, "laptop");
+        let ip = "10.0.0.2".to_string();
+        let env = named.prepare_envelope(ip, 1, TransportProtocol::Http, vec![]);
+        assert_eq!(env.peer, "10.0.0.2");
+
+        let anonymous = SyncClient::new("abc");
+        let env = anonymous.prepare_envelope(String::new(), 1, TransportProtocol::Http, vec![]);
+        assert_eq!(env.peer, FALLBACK_PEER_NAME);
+
+        let decoded = anonymous
+            .decode_envelope(&anonymous.encode_envelope(&env).expect("encode"))
+            .expect("decode");
+        assert_eq!(decoded.peer, FALLBACK_PEER_NAME);
+        assert_eq!(decoded.source_name("10.0.0.9"), FALLBACK_PEER_NAME);
+    }
+
+    #[test]
+    fn envelope_payload_order_ignores_input_order() {
+        let client = SyncClient::new("abc");
+        let events = vec![remote_event("b"), remote_event("c"), remote_event("a")];
+        let mut reversed = events.clone();
+        reversed.reverse();
+
+        let first =
+            client.prepare_envelope("p".to_string(), 1, TransportProtocol::Http, events);
+        let second =
+            client.prepare_envelope("p".to_string(), 1, TransportProtocol::Http, reversed);
+        let ids = first.payload.iter().map(|e| e.id.as_str()).collect::<Vec<_>>();
+        assert_eq!(ids, ["a", "b", "c"]);
+        assert_eq!(
+            client.encode_envelope(&first).expect("encode"),
+            client.encode_envelope(&second).expect("encode")
+        );
+    }
+
+    #[test]
+    fn capped_envelope_drops_finished_then_oldest_sessions_until_it_fits() {
+        let client = SyncClient::new("abc");
+        let events = (0..10u64)
+            .map(|i| {
+                let mut event = remote_event(&format!("s{i}"));
+                event.updated_at_unix_ms = 100 + i;
+                event.last_lines = vec!["x".repeat(200); 4];
+                if i == 8 {
+                    event.status = SessionStatus::Success;
+                }
+                event
+            })
+            .collect::<Vec<_>>();
+        let full = client.prepare_envelope(
+            "p".to_string(),
+            1,
+            TransportProtocol::Http,
+            events.clone(),
+        );
+        let full_size = client.encoded_size(&full);
+        assert_eq!(full_size, client.encode_envelope(&full).expect("encode").len());
+
+        let cap = full_size / 2;
+        let capped = client.prepare_envelope_capped(
+            "p".to_string(),
+            1,
+            TransportProtocol::Http,
+            events,
+            cap,
+        );
+        assert!(client.encoded_size(&capped) <= cap);
+        let ids = capped.payload.iter().map(|e| e.id.as_str()).collect::<Vec<_>>();
+        assert_eq!(ids, ["s5", "s6", "s7", "s9"]);
+
+        let roomy = client.prepare_envelope_capped(
+            "p".to_string(),
+            1,
+            TransportProtocol::Http,
+            vec![remote_event("a")],
+            full_size,
+        );
+        assert_eq!(roomy.payload.len(), 1);
+    }
+
+    #[test]
+    fn envelope_strings_are_sanitized_and_bad_json_is_an_error() {
+        let client = SyncClient::new("abc");
+        let mut event = remote_event("s\u{0}1");
+        event.title = "fix \u{1b}[31mparser\u{FFFF}".to_string();
+        event.last_lines = vec!["col\tumn\u{7}".to_string()];
+        let envelope =
+            client.prepare_envelope("p".to_string(), 1, TransportProtocol::Http, vec![event]);
+        let sent = &envelope.payload[0];
+        assert_eq!(sent.id, "s\u{FFFD}1");
+        assert_eq!(sent.title, "fix \u{FFFD}[31mparser\u{FFFD}");
+        assert_eq!(sent.last_lines, vec!["col\tumn\u{FFFD}"]);
+
+        let bytes = client.encode_envelope(&envelope).expect("encode");
+        let decoded = client.decode_envelope(&bytes).expect("round trip");
+        assert_eq!(decoded.payload[0].title, sent.title);
+
+        // A lone surrogate escape from a non-Rust peer is rejected with an error, not a panic.
+        let json = serde_json::to_string(&envelope)
+            .expect("json")
+            .replace('\u{FFFD}', "\\ud800");
+        let hostile = super::encrypt_like_transport(json.as_bytes());
+        let err = client.decode_envelope(&hostile).expect_err("lone surrogate");
+        assert!(err.to_string().starts_with("malformed sync envelope from peer"));
+    }
+
+    #[test]
+    fn namespaces_remote_event_by_peer() {
+        let event = namespace_remote_event(remote_event("proc-7"), "10.0.0.2");
+        assert_eq!(event.id, "remote:10.0.0.2:proc-7");
+        assert_eq!(event.user, "bob");
+        assert_eq!(event.updated_at_unix_ms, 2);
+        assert_eq!(event.origin.peer(), Some("10.0.0.2"));
+    }
+
+    #[test]
+    fn remote_cache_refuses_to_revive_finished_sessions() {
+        let at = |status: SessionStatus, ts: u64| {
+            let mut e = remote_event("job");
+            e.status = status;
+            e.updated_at_unix_ms = ts;
+            e
+        };
+        let mut cache = RemoteCache::new(1_000);
+        cache.ingest("peer-a", vec![at(SessionStatus::Success, 10)], 100);
+        cache.ingest("peer-a", vec![at(SessionStatus::Running, 5)], 200);
+        cache.ingest("peer-a", vec![at(SessionStatus::Running, 20)], 300);
+
+        let events = cache.events();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].status, SessionStatus::Success);
+        assert_eq!(events[0].updated_at_unix_ms, 10);
+        // Still reported by the peer, so it is not evicted early.
+        cache.prune(1_250);
+        assert_eq!(cache.len(), 1);
+
+        let mut store = RuntimeStateStore::default();
+        assert!(store.upsert(events[0].clone()));
+        let revived = namespace_remote_event(at(SessionStatus::Running, 5), "peer-a");
+        assert!(!store.upsert(revived));
+        assert_eq!(
+            store.get("remote:peer-a:job").map(|e| e.status),
+            Some(SessionStatus::Success)
+        );
+    }
+
+    #[test]
+    fn remote_cache_clamps_far_future_timestamps() {
+        let day_ms = 24 * 60 * 60 * 1000;
+        let mut future = remote_event("skewed");
+        future.started_at_unix_ms = 10_000 + day_ms;
+        future.updated_at_unix_ms = 10_000 + day_ms;
+        let mut cache = RemoteCache::new(1_000).with_max_skew_ms(2_000);
+        cache.ingest("peer-a", vec![future], 10_000);
+
+        let cached = &cache.events()[0];
+        assert_eq!(cached.updated_at_unix_ms, 10_000);
+        assert_eq!(cached.started_at_unix_ms, 10_000);
+
+        // Within the allowed skew: kept as is, and it replaces the clamped update.
+        let mut next = remote_event("skewed");
+        next.status = SessionStatus::WaitingInput;
+        next.updated_at_unix_ms = 11_500;
+        cache.ingest("peer-a", vec![next], 10_500);
+        let cached = &cache.events()[0];
+        assert_eq!(cached.status, SessionStatus::WaitingInput);
+        assert_eq!(cached.updated_at_unix_ms, 11_500);
+
+        cache.prune(12_000);
+        assert!(cache.is_empty(), "clamped session must still expire");
+    }
+
+    #[test]
+    fn remote_cache_evicts_after_ttl() {
+        let mut cache = RemoteCache::new(1_000);
+        cache.ingest("peer-a", vec![remote_event("a")], 100);
+        cache.ingest("peer-b", vec![remote_event("b")], 900);
+
+        cache.prune(1_100);
+        assert_eq!(cache.len(), 2);
+
+        cache.prune(1_500);
+        let ids: Vec<_> = cache.events().into_iter().map(|e| e.id).collect();
+        assert_eq!(ids, vec!["remote:peer-b:b".to_string()]);
+    }
+
+    #[test]
+    fn envelope_caps_oversized_last_lines() {
+        let client = SyncClient::new("abc");
+        let mut event = remote_event("big");
+        event.last_lines = vec!["y".repeat(10_000); 100];
+        let env = client.prepare_envelope(
+            "peer-a".to_string(),
+            1,
+            TransportProtocol::Http,
+            vec![event],
+        );
+        let lines = &env.payload[0].last_lines;
+        assert_eq!(lines.len(), MAX_LAST_LINES);
+        assert!(lines.iter().all(|l| l.chars().count() <= MAX_LINE_CHARS));
+    }
+
     #[test]
     fn retry_policy_is_bounded() {
         let policy = RetryPolicy::default();
@@ -330,6 +1346,64 @@ mod tests {
         assert_eq!(policy.delay_for_attempt(10).as_millis(), 2_000);
     }
 
+    #[test]
+    fn jittered_delays_stay_within_bound_and_vary() {
+        let full = RetryPolicy {
+            jitter: JitterMode::Full,
+            ..RetryPolicy::default()
+        };
+        let delays = (0..32)
+            .map(|_| full.delay_for_attempt(3).as_millis())
+            .collect::<Vec<_>>();
+        assert!(delays.iter().all(|d| *d <= 800));
+        assert!(delays.iter().any(|d| *d != delays[0]));
+
+        let equal = RetryPolicy {
+            jitter: JitterMode::Equal,
+            ..RetryPolicy::default()
+        };
+        for _ in 0..32 {
+            let d = equal.delay_for_attempt(3).as_millis();
+            assert!((400..=800).contains(&d));
+        }
+
+        let mut fixed = || 7_u64;
+        assert_eq!(full.delay_for_attempt_with(1, &mut fixed).as_millis(), 7);
+    }
+
+    #[test]
+    fn pull_fails_on_read_timeout_when_the_peer_stalls_mid_reply() {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind");
+        let port = listener.local_addr().expect("addr").port();
+        let handle = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().expect("accept");
+            let challenge = Challenge {
+                version: PROTOCOL_VERSION,
+                nonce: "n".to_string(),
+            };
+            let mut line = serde_json::to_vec(&challenge).expect("challenge");
+            line.push(b'\n');
+            stream.write_all(&line).expect("send challenge");
+            let mut request = Vec::new();
+            stream.read_to_end(&mut request).expect("read request");
+            stream.write_all(b"{\"peer\":\"slow\",\"pay").expect("partial reply");
+            // Hold the connection open well past the client's timeout.
+            thread::sleep(Duration::from_secs(1));
+        });
+
+        let started = Instant::now();
+        let err = SyncClient::new("abc")
+            .pull_once("127.0.0.1", port, "abc", "client", vec![], Duration::from_millis(300))
+            .expect_err("a stalled reply must fail");
+        assert!(started.elapsed() < Duration::from_millis(900), "{:?}", started.elapsed());
+        assert!(err.to_string().contains("stalled"), "{err}");
+        handle.join().expect("server thread joins");
+
+        let far = Instant::now() + Duration::from_secs(5);
+        assert!(read_bounded(&mut &b"0123456789"[..], far, 4).is_err());
+        assert_eq!(read_bounded(&mut &b"0123"[..], far, 4).expect("fits"), b"0123");
+    }
+
     #[test]
     fn pull_once_gets_remote_payload() {
         let server =
@@ -345,15 +1419,18 @@ mod tests {
             started_at_unix_ms: 1,
             updated_at_unix_ms: 2,
             last_lines: vec!["token=123".to_string()],
+            idle: false,
+            origin: Origin::Local,
+            branch: None,
+            project: None,
         };
 
         let handle = thread::spawn(move || {
             for _ in 0..20 {
-                if server
+                if !server
                     .serve_once(vec![event.clone()], "peer-a", 10, TransportProtocol::Http)
                     .expect("serve ok")
-                    .len()
-                    > 0
+                    .is_empty()
                 {
                     return;
                 }
@@ -380,7 +1457,397 @@ mod tests {
     }
 
     #[test]
-    fn discover_join_key_returns_server_key() {
+    fn pull_over_memory_transport_needs_no_socket() {
+        let transport = Arc::new(MemoryTransport::default());
+        let server = SyncServer::builder("abc")
+            .with_source_host("mem")
+            .build(transport.clone());
+        let client = SyncClient::new("abc").with_transport(transport);
+
+        let handle = thread::spawn(move || {
+            for _ in 0..200 {
+                let local = vec![remote_event("s1")];
+                let incoming = server
+                    .serve_once(local, "peer-a", 10, TransportProtocol::Http)
+                    .expect("serve ok");
+                if !incoming.is_empty() {
+                    return incoming;
+                }
+                thread::sleep(Duration::from_millis(1));
+            }
+            panic!("server did not serve request");
+        });
+
+        let response = client
+            .pull_once(
+                "nowhere.invalid",
+                0,
+                "abc",
+                "client-a",
+                vec![remote_event("pushed")],
+                Duration::from_millis(300),
+            )
+            .expect("pull works");
+        assert_eq!(response.source_host, "mem");
+        assert!(response.source_ip.is_empty());
+        assert_eq!(response.payload.len(), 1);
+        assert_eq!(response.payload[0].id, "s1");
+
+        let incoming = handle.join().expect("server thread joins");
+        assert_eq!(incoming[0].peer, "client-a");
+        assert_eq!(incoming[0].payload[0].id, "pushed");
+    }
+
+    #[test]
+    fn builder_max_request_bytes_drops_oversized_pushes() {
+        let transport = Arc::new(MemoryTransport::default());
+        let server = SyncServer::builder("abc")
+            .with_source_host("mem")
+            .with_max_request_bytes(2_048)
+            .build(transport.clone());
+        let client = SyncClient::new("abc").with_transport(transport);
+
+        let handle = thread::spawn(move || {
+            let mut pushed = Vec::new();
+            for _ in 0..400 {
+                pushed.extend(
+                    server
+                        .serve_once(vec![remote_event("s1")], "peer-a", 10, TransportProtocol::Http)
+                        .expect("serve ok"),
+                );
+                if !pushed.is_empty() {
+                    return pushed;
+                }
+                thread::sleep(Duration::from_millis(1));
+            }
+            panic!("server did not accept the small push");
+        });
+
+        let big = (0..20)
+            .map(|i| {
+                let mut event = remote_event(&format!("big-{i}"));
+                event.title = "t".repeat(200);
+                event
+            })
+            .collect::<Vec<_>>();
+        let pull = |events| {
+            client.pull_once("mem", 0, "abc", "client-a", events, Duration::from_millis(300))
+        };
+        let err = pull(big).expect_err("oversized request is dropped");
+        assert!(err.to_string().contains("empty sync response"), "{err}");
+        let response = pull(vec![remote_event("small")]).expect("small request is served");
+        assert_eq!(response.payload.len(), 1);
+
+        let pushed = handle.join().expect("server thread joins");
+        assert_eq!(pushed.len(), 1);
+        assert_eq!(pushed[0].payload[0].id, "small");
+    }
+
+    #[test]
+    fn strict_client_rejects_spoofed_peer_name() {
+        let transport = Arc::new(MemoryTransport::default());
+        let server = SyncServer::builder("abc")
+            .with_source_host("trusted-b")
+            .build(transport.clone());
+        let lenient = SyncClient::new("abc").with_transport(transport.clone());
+        let strict = lenient.clone().with_strict_peer(true);
+
+        let handle = thread::spawn(move || {
+            let mut served = 0;
+            for _ in 0..400 {
+                let local = vec![remote_event("s1")];
+                served += server
+                    .serve_once(local, "trusted-b", 10, TransportProtocol::Http)
+                    .expect("serve ok")
+                    .len();
+                if served == 3 {
+                    return;
+                }
+                thread::sleep(Duration::from_millis(1));
+            }
+            panic!("server did not serve three requests");
+        });
+
+        let pull = |client: &SyncClient, target: &str| {
+            client.pull_once(target, 0, "abc", "client-a", vec![], Duration::from_millis(300))
+        };
+        let spoofed = pull(&lenient, "host-a").expect("lenient client trusts the claim");
+        assert_eq!(spoofed.source_name("host-a"), "trusted-b");
+
+        let err = pull(&strict, "host-a").expect_err("strict client rejects the claim");
+        assert!(err.to_string().contains("dialed host-a"), "{err}");
+        assert!(err.to_string().contains("trusted-b"), "{err}");
+
+        let genuine = pull(&strict, "Trusted-B").expect("matching name is accepted");
+        assert_eq!(genuine.payload.len(), 1);
+        handle.join().expect("server thread joins");
+    }
+
+    #[test]
+    fn heartbeat_with_empty_payload_keeps_peer_alive() {
+        let transport = Arc::new(MemoryTransport::default());
+        let server = SyncServer::builder("abc")
+            .with_source_host("mem")
+            .build(transport.clone());
+        let client = SyncClient::new("abc").with_transport(transport);
+
+        let handle = thread::spawn(move || {
+            for _ in 0..200 {
+                let local = vec![remote_event("s1")];
+                let incoming = server
+                    .serve_once(local, "peer-a", 10, TransportProtocol::Http)
+                    .expect("serve ok");
+                if !incoming.is_empty() {
+                    return incoming;
+                }
+                thread::sleep(Duration::from_millis(1));
+            }
+            panic!("server did not serve heartbeat");
+        });
+
+        let response = client
+            .heartbeat("nowhere.invalid", 0, "abc", "client-a", Duration::from_millis(300))
+            .expect("heartbeat works");
+        assert!(response.heartbeat);
+        assert!(response.payload.is_empty(), "heartbeats carry no sessions");
+        let incoming = handle.join().expect("server thread joins");
+        assert_eq!(incoming[0].peer, "client-a");
+        assert!(incoming[0].payload.is_empty());
+
+        let mut cache = RemoteCache::new(100);
+        cache.ingest("mem", vec![remote_event("old")], 0);
+        cache.prune(150);
+        assert!(cache.is_empty());
+        assert!(!cache.is_alive("mem", 150));
+
+        cache.ingest(response.source_name("nowhere.invalid"), response.payload.clone(), 150);
+        cache.prune(240);
+        assert!(cache.is_alive("mem", 240));
+        assert_eq!(cache.idle_peers(240), vec!["mem".to_string()]);
+        cache.prune(251);
+        assert!(!cache.is_alive("mem", 251));
+        assert!(cache.idle_peers(251).is_empty());
+    }
+
+    #[test]
+    fn observer_key_pulls_read_only_and_cannot_push() {
+        let server =
+            SyncServer::bind("127.0.0.1", 38468, "abc").expect("server should bind localhost");
+        let handle = thread::spawn(move || {
+            let mut pushed = Vec::new();
+            for _ in 0..30 {
+                let local = vec![remote_event("shared")];
+                let incoming = server
+                    .serve_once(local, "peer-a", 10, TransportProtocol::Http)
+                    .expect("serve ok");
+                pushed.extend(incoming);
+                thread::sleep(Duration::from_millis(10));
+            }
+            pushed
+        });
+
+        thread::sleep(Duration::from_millis(20));
+        let observer_key = derive_observer_key("abc");
+        let client = SyncClient::new(&observer_key);
+        let response = client
+            .pull_once(
+                "127.0.0.1",
+                38468,
+                &observer_key,
+                "observer-a",
+                vec![remote_event("gossip")],
+                Duration::from_millis(300),
+            )
+            .expect("observer can pull");
+        assert!(response.read_only);
+        assert_eq!(response.payload.len(), 1);
+
+        let pushed = handle.join().expect("server thread joins");
+        assert!(pushed.is_empty(), "observer payload must not be accepted");
+    }
+
+    #[test]
+    fn envelope_source_fields_drive_remote_namespace() {
+        let server = SyncServer::builder("abc")
+            .with_source_host("host-a")
+            .bind("127.0.0.1", 38469)
+            .expect("server should bind localhost");
+        let handle = thread::spawn(move || {
+            for _ in 0..30 {
+                let local = vec![remote_event("s1")];
+                if !server
+                    .serve_once(local, "127.0.0.1", 10, TransportProtocol::Http)
+                    .expect("serve ok")
+                    .is_empty()
+                {
+                    return;
+                }
+                thread::sleep(Duration::from_millis(10));
+            }
+            panic!("server did not serve request");
+        });
+
+        thread::sleep(Duration::from_millis(20));
+        let response = SyncClient::new("abc")
+            .pull_once(
+                "127.0.0.1",
+                38469,
+                "abc",
+                "client-a",
+                vec![],
+                Duration::from_millis(300),
+            )
+            .expect("pull works");
+        handle.join().expect("server thread joins");
+
+        assert_eq!(response.source_ip, "127.0.0.1");
+        assert_eq!(response.source_host, "host-a");
+        let source = response.source_name("fallback");
+        assert_eq!(source, "host-a");
+        let event = namespace_remote_event(response.payload[0].clone(), source);
+        assert_eq!(event.id, "remote:host-a:s1");
+    }
+
+    #[test]
+    fn wrong_key_proof_gets_no_sessions() {
+        let server =
+            SyncServer::bind("127.0.0.1", 38470, "abc").expect("server should bind localhost");
+        let handle = thread::spawn(move || {
+            let mut pushed = Vec::new();
+            for _ in 0..30 {
+                let local = vec![remote_event("secret-session")];
+                let incoming = server
+                    .serve_once(local, "peer-a", 10, TransportProtocol::Http)
+                    .expect("serve ok");
+                pushed.extend(incoming);
+                thread::sleep(Duration::from_millis(10));
+            }
+            pushed
+        });
+
+        thread::sleep(Duration::from_millis(20));
+        let result = SyncClient::new("wrong").pull_once(
+            "127.0.0.1",
+            38470,
+            "wrong",
+            "intruder",
+            vec![remote_event("gossip")],
+            Duration::from_millis(300),
+        );
+        assert!(result.is_err(), "wrong-key proof must not receive an envelope");
+        let pushed = handle.join().expect("server thread joins");
+        assert!(pushed.is_empty());
+    }
+
+    #[test]
+    fn empty_key_is_rejected_before_connecting() {
+        assert!(validate_auth_key("").is_err());
+        assert!(validate_auth_key("two words").is_err());
+        assert!(validate_auth_key("abc").is_ok());
+
+        // Nothing listens on this port, so only a call that got past the precondition
+        // reports a connect failure.
+        let pull = |key: &str| {
+            SyncClient::new(key)
+                .pull_once(
+                    "127.0.0.1",
+                    38471,
+                    key,
+                    "client-a",
+                    vec![],
+                    Duration::from_millis(200),
+                )
+                .expect_err("no server is listening")
+                .to_string()
+        };
+        let empty = pull("");
+        assert!(empty.contains("non-empty"), "{empty}");
+        let valid = pull("abc");
+        assert!(valid.contains("connect failed"), "{valid}");
+    }
+
+    #[test]
+    fn slow_client_does_not_starve_fast_client() {
+        let transport = Arc::new(MemoryTransport::default());
+        let server = SyncServer::builder("abc").build(transport.clone());
+        let done = Arc::new(AtomicBool::new(false));
+        let serving = Arc::clone(&done);
+        let handle = thread::spawn(move || {
+            while !serving.load(Ordering::Relaxed) {
+                let local = vec![remote_event("s1")];
+                let _ = server
+                    .serve_once(local, "peer-a", 10, TransportProtocol::Http)
+                    .expect("serve ok");
+                thread::sleep(Duration::from_millis(1));
+            }
+        });
+
+        // Connects first, reads the challenge, then stalls until released.
+        let mut slow = transport
+            .connect("nowhere.invalid", 0, Duration::from_millis(300))
+            .expect("slow connects");
+        let (challenged_tx, challenged_rx) = mpsc::channel();
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+        let slow = thread::spawn(move || {
+            let mut line = String::new();
+            BufReader::new(&mut slow).read_line(&mut line).expect("challenge");
+            challenged_tx.send(()).expect("signal challenge");
+            let _ = release_rx.recv();
+        });
+        challenged_rx.recv().expect("slow client got its challenge");
+
+        let response = SyncClient::new("abc")
+            .with_transport(transport)
+            .pull_once(
+                "nowhere.invalid",
+                0,
+                "abc",
+                "fast",
+                vec![],
+                Duration::from_millis(300),
+            )
+            .expect("fast client is served");
+        assert_eq!(response.payload.len(), 1);
+        assert!(!slow.is_finished(), "fast pull finished while the slow client stalled");
+
+        release_tx.send(()).expect("release slow client");
+        slow.join().expect("slow client joins");
+        done.store(true, Ordering::Relaxed);
+        handle.join().expect("server thread joins");
+    }
+
+    #[test]
+    fn trickling_client_is_cut_off_at_the_serve_deadline() {
+        const BYTES: usize = 20;
+        let transport = Arc::new(MemoryTransport::default());
+        let server = SyncServer::builder("abc").build(transport.clone());
+        let mut conn = transport
+            .connect("nowhere.invalid", 0, Duration::from_millis(300))
+            .expect("client connects");
+        let client = thread::spawn(move || {
+            let mut line = String::new();
+            BufReader::new(&mut conn).read_line(&mut line).expect("challenge");
+            // One byte at a time, each well inside the per-read timeout, for far longer
+            // than the serve deadline. Returns how many went out before the server hung up.
+            for sent in 0..BYTES {
+                if conn.write_all(b" ").is_err() {
+                    return sent;
+                }
+                thread::sleep(Duration::from_millis(200));
+            }
+            BYTES
+        });
+
+        let incoming = server
+            .serve_once(vec![], "peer-a", 10, TransportProtocol::Http)
+            .expect("serve ok");
+        assert!(incoming.is_empty());
+        let sent = client.join().expect("client joins");
+        assert!(sent < BYTES, "server kept reading all {sent} bytes");
+    }
+
+    #[test]
+    fn discovery_returns_fingerprint_not_key() {
         let server =
             SyncServer::bind("127.0.0.1", 38467, "abc").expect("server should bind localhost");
         let handle = thread::spawn(move || {
@@ -393,9 +1860,11 @@ mod tests {
         });
 
         thread::sleep(Duration::from_millis(20));
-        let key =
-            discover_join_key("127.0.0.1", 38467, Duration::from_millis(300)).expect("discover");
-        assert_eq!(key, "abc");
+        let fingerprint = fetch_key_fingerprint("127.0.0.1", 38467, Duration::from_millis(300))
+            .expect("discover");
+        assert_ne!(fingerprint, "abc");
+        assert!(!fingerprint.contains("abc"));
+        assert_eq!(fingerprint, SecurityLayer::new("abc").fingerprint());
         handle.join().expect("server thread joins");
     }
 }
@@ -0,0 +1,128 @@
+//! Forwards session lifecycle transitions to the local syslog daemon.
+
+use anyhow::{anyhow, Result};
+use syslog::{Facility, Formatter3164, Logger, LoggerBackend, Severity};
+
+use crate::model::{Lifecycle, SessionStatus, Transition};
+
+/// How loudly a session's new status is logged: input waits warn, failures are errors.
+pub fn severity_for(status: SessionStatus) -> Severity {
+    match status {
+        SessionStatus::Running => Severity::LOG_INFO,
+        SessionStatus::WaitingInput => Severity::LOG_WARNING,
+        SessionStatus::Success | SessionStatus::Stopped => Severity::LOG_NOTICE,
+        SessionStatus::Failed => Severity::LOG_ERR,
+    }
+}
+
+/// One line per transition, e.g. `claude session local:7 "fix tests" ended: failed (was
+/// running)`.
+pub fn syslog_message(transition: &Transition) -> String {
+    let session = &transition.session;
+    let verb = match transition.lifecycle {
+        Lifecycle::Started => "started",
+        Lifecycle::StatusChanged => "changed",
+        Lifecycle::Ended => "ended",
+    };
+    let mut message = format!(
+        "{} session {} {:?} {verb}: {}",
+        session.agent.as_label(),
+        session.id,
+        session.title,
+        session.status.as_label()
+    );
+    if let Some(from) = transition.from {
+        message.push_str(&format!(" (was {})", from.as_label()));
+    }
+    if let Some(peer) = session.origin.peer() {
+        message.push_str(&format!(" on {peer}"));
+    }
+    message
+}
+
+/// Writes transitions to `/dev/log` (or `/var/run/syslog`) under the user facility.
+pub struct SyslogSink {
+    logger: Logger<LoggerBackend, Formatter3164>,
+}
+
+impl SyslogSink {
+    pub fn connect() -> Result<Self> {
+        let formatter = Formatter3164 {
+            facility: Facility::LOG_USER,
+            hostname: None,
+            process: "agent-box".to_string(),
+            pid: std::process::id(),
+        };
+        let logger =
+            syslog::unix(formatter).map_err(|e| anyhow!("cannot connect to syslog: {e}"))?;
+        Ok(Self { logger })
+    }
+
+    /// Logs every transition; the first failure is returned after the rest were tried.
+    pub fn send(&mut self, transitions: &[Transition]) -> Result<()> {
+        let mut first_error = None;
+        for transition in transitions {
+            let message = syslog_message(transition);
+            let sent = match severity_for(transition.session.status) {
+                Severity::LOG_ERR => self.logger.err(message),
+                Severity::LOG_WARNING => self.logger.warning(message),
+                Severity::LOG_NOTICE => self.logger.notice(message),
+                _ => self.logger.info(message),
+            };
+            if let Err(err) = sent {
+                first_error.get_or_insert(anyhow!("syslog write failed: {err}"));
+            }
+        }
+        first_error.map_or(Ok(()), Err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use syslog::Severity;
+
+    use crate::model::{AgentKind, Lifecycle, Origin, SessionEvent, SessionStatus, Transition};
+
+    use super::{severity_for, syslog_message};
+
+    #[test]
+    fn severity_follows_the_new_status() {
+        for (status, expected) in [
+            (SessionStatus::Running, Severity::LOG_INFO),
+            (SessionStatus::WaitingInput, Severity::LOG_WARNING),
+            (SessionStatus::Success, Severity::LOG_NOTICE),
+            (SessionStatus::Failed, Severity::LOG_ERR),
+            (SessionStatus::Stopped, Severity::LOG_NOTICE),
+        ] {
+            assert_eq!(severity_for(status) as u8, expected as u8, "{status:?}");
+        }
+    }
+
+    #[test]
+    fn message_names_the_session_and_both_statuses() {
+        let transition = Transition {
+            lifecycle: Lifecycle::Ended,
+            from: Some(SessionStatus::Running),
+            session: SessionEvent {
+                id: "local:7".to_string(),
+                agent: AgentKind::Claude,
+                title: "fix tests".to_string(),
+                working_dir: "/tmp/repo".to_string(),
+                user: "alice".to_string(),
+                status: SessionStatus::Failed,
+                pending_action: None,
+                started_at_unix_ms: 1,
+                updated_at_unix_ms: 2,
+                last_lines: Vec::new(),
+                idle: false,
+                origin: Origin::Local,
+                branch: None,
+                project: None,
+            },
+        };
+        assert_eq!(
+            syslog_message(&transition),
+            "claude session local:7 \"fix tests\" ended: failed (was running)"
+        );
+    }
+}
@@ -0,0 +1,199 @@
+use std::io::{self, Read, Write};
+use std::net::{IpAddr, Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+
+/// How long a served connection may stall on a read or write before it is dropped.
+const SERVER_IO_TIMEOUT: Duration = Duration::from_millis(300);
+
+/// A bidirectional byte stream between a sync client and server.
+pub trait Connection: Read + Write + Send {
+    /// Signals end-of-request to the other side while keeping the read half open.
+    fn close_write(&mut self) -> io::Result<()>;
+
+    /// Address this end is bound to, if the transport has one.
+    fn local_ip(&self) -> Option<IpAddr>;
+}
+
+/// How sync connections are made (client side) and received (server side).
+pub trait Transport: Send + Sync {
+    fn connect(&self, host: &str, port: u16, timeout: Duration) -> Result<Box<dyn Connection>>;
+
+    /// Non-blocking: `Ok(None)` when no connection is waiting.
+    fn accept(&self) -> io::Result<Option<Box<dyn Connection>>>;
+}
+
+impl Connection for TcpStream {
+    fn close_write(&mut self) -> io::Result<()> {
+        self.shutdown(Shutdown::Write)
+    }
+
+    fn local_ip(&self) -> Option<IpAddr> {
+        self.local_addr().ok().map(|addr| addr.ip())
+    }
+}
+
+/// The production transport. Client-only unless built with `bind`.
+#[derive(Debug, Default)]
+pub struct TcpTransport {
+    listener: Option<TcpListener>,
+}
+
+impl TcpTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn bind(ip: &str, port: u16) -> Result<Self> {
+        // A tuple rather than `ip:port`, so IPv6 literals need no brackets.
+        let listener = TcpListener::bind((ip, port)).map_err(|e| anyhow!("bind failed: {e}"))?;
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            listener: Some(listener),
+        })
+    }
+}
+
+impl Transport for TcpTransport {
+    fn connect(&self, host: &str, port: u16, timeout: Duration) -> Result<Box<dyn Connection>> {
+        let addr = resolve_addr(host, port)?;
+        let stream = TcpStream::connect_timeout(&addr, timeout)
+            .map_err(|e| anyhow!("connect failed to {host}:{port}: {e}"))?;
+        stream.set_read_timeout(Some(timeout)).ok();
+        stream.set_write_timeout(Some(timeout)).ok();
+        Ok(Box::new(stream))
+    }
+
+    fn accept(&self) -> io::Result<Option<Box<dyn Connection>>> {
+        let Some(listener) = &self.listener else {
+            return Ok(None);
+        };
+        match listener.accept() {
+            Ok((stream, _)) => {
+                stream.set_nonblocking(false).ok();
+                stream.set_read_timeout(Some(SERVER_IO_TIMEOUT)).ok();
+                stream.set_write_timeout(Some(SERVER_IO_TIMEOUT)).ok();
+                Ok(Some(Box::new(stream)))
+            }
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+fn resolve_addr(host: &str, port: u16) -> Result<SocketAddr> {
+    let mut resolved = (host, port)
+        .to_socket_addrs()
+        .map_err(|e| anyhow!("resolve failed for {host}:{port}: {e}"))?;
+    resolved
+        .next()
+        .ok_or_else(|| anyhow!("no socket addresses for {host}:{port}"))
+}
+
+/// Socket-free transport for tests: `connect` hands the server end of an in-process
+/// pipe to the next `accept`, whatever host and port were asked for.
+#[cfg(test)]
+pub(crate) mod memory {
+    use std::collections::VecDeque;
+    use std::io::{self, Read, Write};
+    use std::net::IpAddr;
+    use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use anyhow::Result;
+
+    use super::{Connection, Transport};
+
+    const READ_TIMEOUT: Duration = Duration::from_secs(2);
+
+    #[derive(Clone, Default)]
+    pub(crate) struct MemoryTransport {
+        pending: Arc<Mutex<VecDeque<MemoryConnection>>>,
+    }
+
+    pub(crate) struct MemoryConnection {
+        tx: Option<Sender<Vec<u8>>>,
+        rx: Receiver<Vec<u8>>,
+        buffered: Vec<u8>,
+    }
+
+    fn pipe() -> (MemoryConnection, MemoryConnection) {
+        let (a_tx, b_rx) = mpsc::channel();
+        let (b_tx, a_rx) = mpsc::channel();
+        let end = |tx, rx| MemoryConnection {
+            tx: Some(tx),
+            rx,
+            buffered: Vec::new(),
+        };
+        (end(a_tx, a_rx), end(b_tx, b_rx))
+    }
+
+    impl Read for MemoryConnection {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.buffered.is_empty() {
+                match self.rx.recv_timeout(READ_TIMEOUT) {
+                    Ok(chunk) => self.buffered = chunk,
+                    Err(RecvTimeoutError::Disconnected) => return Ok(0),
+                    Err(RecvTimeoutError::Timeout) => {
+                        return Err(io::ErrorKind::TimedOut.into());
+                    }
+                }
+            }
+            let n = buf.len().min(self.buffered.len());
+            buf[..n].copy_from_slice(&self.buffered[..n]);
+            self.buffered.drain(..n);
+            Ok(n)
+        }
+    }
+
+    impl Write for MemoryConnection {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let tx = self.tx.as_ref().ok_or(io::ErrorKind::BrokenPipe)?;
+            tx.send(buf.to_vec())
+                .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))?;
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Connection for MemoryConnection {
+        fn close_write(&mut self) -> io::Result<()> {
+            self.tx = None;
+            Ok(())
+        }
+
+        fn local_ip(&self) -> Option<IpAddr> {
+            None
+        }
+    }
+
+    impl Transport for MemoryTransport {
+        fn connect(
+            &self,
+            _host: &str,
+            _port: u16,
+            _timeout: Duration,
+        ) -> Result<Box<dyn Connection>> {
+            let (client, server) = pipe();
+            self.pending
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .push_back(server);
+            Ok(Box::new(client))
+        }
+
+        fn accept(&self) -> io::Result<Option<Box<dyn Connection>>> {
+            let next = self
+                .pending
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .pop_front();
+            Ok(next.map(|conn| Box::new(conn) as Box<dyn Connection>))
+        }
+    }
+}
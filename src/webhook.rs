@@ -0,0 +1,432 @@
+//! Posts session lifecycle transitions to an HTTP endpoint as JSON batches.
+
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+
+use crate::model::{Lifecycle, Transition};
+use crate::unix_ms_now;
+
+/// Minimum gap between two POSTs; transitions in between are sent as one batch.
+pub const DEFAULT_WEBHOOK_DEBOUNCE_MS: u64 = 2_000;
+/// Oldest events are dropped past this many while the endpoint is failing.
+const MAX_PENDING_EVENTS: usize = 256;
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(2);
+/// How often the delivery thread checks whether a debounced batch is due.
+const DELIVERY_TICK: Duration = Duration::from_millis(250);
+
+/// One transition as it appears in the POST body.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct WebhookEvent {
+    pub event: Lifecycle,
+    pub id: String,
+    pub agent: String,
+    pub status: String,
+    pub previous_status: Option<String>,
+    pub title: String,
+    pub user: String,
+    pub working_dir: String,
+    pub peer: Option<String>,
+    pub at_unix_ms: u64,
+}
+
+impl WebhookEvent {
+    pub fn from_transition(transition: &Transition, at_unix_ms: u64) -> Self {
+        let session = &transition.session;
+        Self {
+            event: transition.lifecycle,
+            id: session.id.clone(),
+            agent: session.agent.as_label().to_string(),
+            status: session.status.as_label().to_string(),
+            previous_status: transition.from.map(|status| status.as_label().to_string()),
+            title: session.title.clone(),
+            user: session.user.clone(),
+            working_dir: session.working_dir.clone(),
+            peer: session.origin.peer().map(str::to_string),
+            at_unix_ms,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct WebhookBody<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    label: Option<&'a str>,
+    events: &'a [WebhookEvent],
+}
+
+/// Queues transitions and POSTs them as `{"events": [...]}` to a plain `http://` URL, at
+/// most once per debounce window.
+#[derive(Debug)]
+pub struct WebhookSink {
+    host: String,
+    port: u16,
+    path: String,
+    debounce_ms: u64,
+    label: Option<String>,
+    last_post_ms: Option<u64>,
+    pending: Vec<WebhookEvent>,
+}
+
+impl WebhookSink {
+    pub fn new(url: &str) -> Result<Self> {
+        let rest = url
+            .strip_prefix("http://")
+            .ok_or_else(|| anyhow!("webhook URL must start with http://: {url}"))?;
+        let (authority, path) = match rest.find('/') {
+            Some(idx) => rest.split_at(idx),
+            None => (rest, "/"),
+        };
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => {
+                let port = port
+                    .parse()
+                    .map_err(|_| anyhow!("invalid port in webhook URL: {url}"))?;
+                (host, port)
+            }
+            None => (authority, 80),
+        };
+        if host.is_empty() {
+            return Err(anyhow!("webhook URL has no host: {url}"));
+        }
+        Ok(Self {
+            host: host.to_string(),
+            port,
+            path: path.to_string(),
+            debounce_ms: DEFAULT_WEBHOOK_DEBOUNCE_MS,
+            label: None,
+            last_post_ms: None,
+            pending: Vec::new(),
+        })
+    }
+
+    pub fn with_debounce_ms(mut self, debounce_ms: u64) -> Self {
+        self.debounce_ms = debounce_ms;
+        self
+    }
+
+    /// Names the monitor instance in every body, as a top-level `label`.
+    pub fn with_label(mut self, label: Option<String>) -> Self {
+        self.label = label;
+        self
+    }
+
+    pub fn push(&mut self, transitions: &[Transition], now_ms: u64) {
+        self.push_events(
+            transitions
+                .iter()
+                .map(|t| WebhookEvent::from_transition(t, now_ms))
+                .collect(),
+        );
+    }
+
+    fn push_events(&mut self, events: Vec<WebhookEvent>) {
+        self.pending.extend(events);
+        let overflow = self.pending.len().saturating_sub(MAX_PENDING_EVENTS);
+        self.pending.drain(..overflow);
+    }
+
+    /// Posts the pending batch unless one went out less than the debounce window ago.
+    /// Returns how many events were delivered; on failure they stay queued.
+    pub fn flush(&mut self, now_ms: u64) -> Result<usize> {
+        let due = self
+            .last_post_ms
+            .is_none_or(|last| now_ms.saturating_sub(last) >= self.debounce_ms);
+        if self.pending.is_empty() || !due {
+            return Ok(0);
+        }
+        self.last_post_ms = Some(now_ms);
+        self.post_pending()
+    }
+
+    /// Posts whatever is pending regardless of the debounce window, e.g. on exit.
+    pub fn flush_all(&mut self) -> Result<usize> {
+        if self.pending.is_empty() {
+            return Ok(0);
+        }
+        self.post_pending()
+    }
+
+    fn post_pending(&mut self) -> Result<usize> {
+        let body = serde_json::to_string(&WebhookBody {
+            label: self.label.as_deref(),
+            events: &self.pending,
+        })?;
+        self.post(&body)?;
+        Ok(std::mem::take(&mut self.pending).len())
+    }
+
+    fn post(&self, body: &str) -> Result<()> {
+        let addr = format!("{}:{}", self.host, self.port);
+        let mut stream = connect(&addr)?;
+        stream.set_read_timeout(Some(WEBHOOK_TIMEOUT)).ok();
+        stream.set_write_timeout(Some(WEBHOOK_TIMEOUT)).ok();
+        let request = format!(
+            "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\n\
+             Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            self.path,
+            self.host,
+            body.len()
+        );
+        stream
+            .write_all(request.as_bytes())
+            .map_err(|e| anyhow!("webhook POST to {addr}: {e}"))?;
+        let mut response = String::new();
+        stream
+            .read_to_string(&mut response)
+            .map_err(|e| anyhow!("webhook response from {addr}: {e}"))?;
+        let status = response.lines().next().unwrap_or_default();
+        match status.split_whitespace().nth(1) {
+            Some(code) if code.starts_with('2') => Ok(()),
+            _ => Err(anyhow!("webhook {addr} answered {status:?}")),
+        }
+    }
+}
+
+/// Owns a `WebhookSink` on a background thread so the render loop only enqueues events;
+/// a slow or unreachable endpoint delays delivery, never the next frame.
+#[derive(Debug)]
+pub struct WebhookDelivery {
+    events: Option<Sender<Vec<WebhookEvent>>>,
+    errors: Receiver<anyhow::Error>,
+    worker: Option<JoinHandle<Result<usize>>>,
+}
+
+impl WebhookDelivery {
+    pub fn spawn(mut sink: WebhookSink) -> Self {
+        let (events_tx, events_rx) = mpsc::channel::<Vec<WebhookEvent>>();
+        let (errors_tx, errors_rx) = mpsc::channel();
+        let worker = thread::spawn(move || loop {
+            match events_rx.recv_timeout(DELIVERY_TICK) {
+                Ok(events) => sink.push_events(events),
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return sink.flush_all(),
+            }
+            if let Err(err) = sink.flush(unix_ms_now()) {
+                let _ = errors_tx.send(err);
+            }
+        });
+        Self {
+            events: Some(events_tx),
+            errors: errors_rx,
+            worker: Some(worker),
+        }
+    }
+
+    /// Hands `transitions` to the delivery thread without waiting on the network and
+    /// returns the latest delivery failure reported since the previous call, if any.
+    pub fn push(&mut self, transitions: &[Transition], now_ms: u64) -> Option<anyhow::Error> {
+        if !transitions.is_empty() {
+            let events = transitions
+                .iter()
+                .map(|t| WebhookEvent::from_transition(t, now_ms))
+                .collect();
+            if let Some(sender) = &self.events {
+                let _ = sender.send(events);
+            }
+        }
+        self.errors.try_iter().last()
+    }
+
+    /// Stops the delivery thread after it posts whatever is still queued, e.g. on exit.
+    pub fn finish(mut self) -> Result<usize> {
+        self.events.take();
+        match self.worker.take() {
+            Some(worker) => worker
+                .join()
+                .map_err(|_| anyhow!("webhook delivery thread panicked"))?,
+            None => Ok(0),
+        }
+    }
+}
+
+/// Connects within `WEBHOOK_TIMEOUT`, trying each resolved address in turn, so an
+/// unreachable host fails a delivery promptly instead of after the OS connect timeout.
+fn connect(addr: &str) -> Result<TcpStream> {
+    let resolved = addr
+        .to_socket_addrs()
+        .map_err(|e| anyhow!("webhook connect to {addr}: {e}"))?;
+    let mut last_error = None;
+    for socket_addr in resolved {
+        match TcpStream::connect_timeout(&socket_addr, WEBHOOK_TIMEOUT) {
+            Ok(stream) => return Ok(stream),
+            Err(err) => last_error = Some(err),
+        }
+    }
+    Err(match last_error {
+        Some(err) => anyhow!("webhook connect to {addr}: {err}"),
+        None => anyhow!("webhook connect to {addr}: no addresses resolved"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use crate::model::{
+        AgentKind, Origin, RuntimeStateStore, SessionEvent, SessionStatus, TransitionObserver,
+    };
+
+    use super::{WebhookDelivery, WebhookSink};
+
+    fn session(status: SessionStatus) -> SessionEvent {
+        SessionEvent {
+            id: "local:7".to_string(),
+            agent: AgentKind::Claude,
+            title: "fix tests".to_string(),
+            working_dir: "/tmp/repo".to_string(),
+            user: "alice".to_string(),
+            status,
+            pending_action: None,
+            started_at_unix_ms: 1,
+            updated_at_unix_ms: 2,
+            last_lines: vec!["secret line".to_string()],
+            idle: false,
+            origin: Origin::Local,
+            branch: None,
+            project: None,
+        }
+    }
+
+    /// Accepts `posts` requests, answers 204, and returns their request lines and bodies.
+    fn mock_server(posts: usize) -> (u16, thread::JoinHandle<Vec<(String, String)>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind mock server");
+        let port = listener.local_addr().expect("local addr").port();
+        let handle = thread::spawn(move || {
+            let mut received = Vec::new();
+            for stream in listener.incoming().take(posts) {
+                let mut reader = BufReader::new(stream.expect("accept"));
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).expect("request line");
+                let mut length = 0;
+                loop {
+                    let mut header = String::new();
+                    reader.read_line(&mut header).expect("header");
+                    if header.trim().is_empty() {
+                        break;
+                    }
+                    if let Some(value) = header.to_lowercase().strip_prefix("content-length:") {
+                        length = value.trim().parse().expect("content length");
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).expect("body");
+                let mut stream = reader.into_inner();
+                stream
+                    .write_all(b"HTTP/1.1 204 No Content\r\nConnection: close\r\n\r\n")
+                    .expect("respond");
+                received.push((
+                    request_line.trim().to_string(),
+                    String::from_utf8(body).expect("utf8 body"),
+                ));
+            }
+            received
+        });
+        (port, handle)
+    }
+
+    #[test]
+    fn posts_debounced_batches_matching_transitions() {
+        let (port, server) = mock_server(2);
+        let mut sink = WebhookSink::new(&format!("http://127.0.0.1:{port}/hooks/agents"))
+            .expect("valid url")
+            .with_debounce_ms(1_000);
+        let mut observer = TransitionObserver::default();
+        let mut store = RuntimeStateStore::default();
+
+        store.upsert(session(SessionStatus::Running));
+        sink.push(&observer.observe(&store), 100);
+        assert_eq!(sink.flush(100).expect("first post"), 1);
+
+        store.clear();
+        store.upsert(session(SessionStatus::WaitingInput));
+        sink.push(&observer.observe(&store), 600);
+        assert_eq!(sink.flush(600).expect("debounced"), 0);
+        assert_eq!(sink.flush(1_100).expect("second post"), 1);
+        assert_eq!(sink.flush_all().expect("nothing left"), 0);
+
+        let received = server.join().expect("server thread");
+        assert_eq!(received[0].0, "POST /hooks/agents HTTP/1.1");
+        let body: serde_json::Value = serde_json::from_str(&received[1].1).expect("json body");
+        assert_eq!(
+            body,
+            serde_json::json!({"events": [{
+                "event": "status_changed",
+                "id": "local:7",
+                "agent": "claude",
+                "status": "waiting_input",
+                "previous_status": "running",
+                "title": "fix tests",
+                "user": "alice",
+                "working_dir": "/tmp/repo",
+                "peer": null,
+                "at_unix_ms": 600,
+            }]})
+        );
+    }
+
+    #[test]
+    fn delivery_enqueues_without_waiting_on_a_stalled_endpoint() {
+        // Accepts the connection but never answers, so every POST waits out its timeouts.
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind stalled server");
+        let port = listener.local_addr().expect("local addr").port();
+        let stalled = thread::spawn(move || {
+            let (stream, _) = listener.accept().expect("accept");
+            thread::sleep(Duration::from_secs(3));
+            drop(stream);
+        });
+        let sink = WebhookSink::new(&format!("http://127.0.0.1:{port}/hook")).expect("valid url");
+        let mut delivery = WebhookDelivery::spawn(sink);
+        let mut observer = TransitionObserver::default();
+        let mut store = RuntimeStateStore::default();
+
+        store.upsert(session(SessionStatus::Running));
+        let transitions = observer.observe(&store);
+        let started = Instant::now();
+        assert!(delivery.push(&transitions, 100).is_none());
+        thread::sleep(Duration::from_millis(300));
+        store.clear();
+        store.upsert(session(SessionStatus::WaitingInput));
+        assert!(delivery.push(&observer.observe(&store), 400).is_none());
+        assert!(started.elapsed() < Duration::from_secs(1), "push waited on the network");
+
+        assert!(delivery.finish().is_err());
+        stalled.join().expect("stalled server");
+    }
+
+    #[test]
+    fn delivery_posts_what_is_left_on_finish() {
+        let (port, server) = mock_server(1);
+        let sink = WebhookSink::new(&format!("http://127.0.0.1:{port}/hook"))
+            .expect("valid url")
+            .with_debounce_ms(60_000);
+        let mut delivery = WebhookDelivery::spawn(sink);
+        let mut observer = TransitionObserver::default();
+        let mut store = RuntimeStateStore::default();
+
+        store.upsert(session(SessionStatus::Running));
+        assert!(delivery.push(&observer.observe(&store), 100).is_none());
+        delivery.finish().expect("final post");
+
+        let received = server.join().expect("server thread");
+        let body: serde_json::Value = serde_json::from_str(&received[0].1).expect("json body");
+        assert_eq!(body["events"][0]["status"], "running");
+    }
+
+    #[test]
+    fn rejects_non_http_urls() {
+        assert!(WebhookSink::new("https://example.com/hook").is_err());
+        assert!(WebhookSink::new("http://:80/hook").is_err());
+        assert!(WebhookSink::new("http://example.com:x/hook").is_err());
+        let sink = WebhookSink::new("http://example.com").expect("bare host");
+        assert_eq!((sink.port, sink.path.as_str()), (80, "/"));
+    }
+}
@@ -1,5 +1,13 @@
+use std::process::Command;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+
 use agent_box::cli::validate_bind;
+use agent_box::collector::Collector;
+use agent_box::model::{RuntimeStateStore, SessionEvent};
 use agent_box::sync::{RetryPolicy, SyncClient};
+use agent_box::{check_collected_no_waiting, try_run_once_with_stats};
 
 #[test]
 fn invalid_ip_is_rejected() {
@@ -7,9 +15,11 @@ fn invalid_ip_is_rejected() {
 }
 
 #[test]
-fn invalid_auth_key_fails_handshake() {
-    let client = SyncClient::new("expected-key");
-    assert!(client.handshake("wrong-key").is_err());
+fn blank_auth_key_fails_before_connecting() {
+    let err = SyncClient::new("expected-key")
+        .pull_once("127.0.0.1", 1, "  ", "me", Vec::new(), Duration::from_millis(100))
+        .expect_err("blank key is refused");
+    assert!(err.to_string().contains("whitespace"), "{err}");
 }
 
 #[test]
@@ -19,3 +29,66 @@ fn reconnect_backoff_caps_max_delay() {
     assert!(last.as_millis() <= policy.max_delay_ms as u128);
 }
 
+/// Stands in for a host where `ps` cannot be run.
+struct BrokenCollector;
+
+impl Collector for BrokenCollector {
+    fn collect(&self) -> Vec<SessionEvent> {
+        Vec::new()
+    }
+
+    fn try_collect(&self) -> Result<Vec<SessionEvent>> {
+        Err(anyhow!("ps: command not found"))
+    }
+}
+
+#[test]
+fn fail_on_waiting_gate_fails_when_collection_fails() {
+    let mut store = RuntimeStateStore::default();
+    let err = try_run_once_with_stats(&BrokenCollector, &mut store).expect_err("ps is missing");
+    assert!(store.all().is_empty());
+    let gate = check_collected_no_waiting(&store, Some(&err)).expect_err("nothing was checked");
+    assert!(gate.to_string().contains("ps: command not found"));
+    assert!(check_collected_no_waiting(&store, None).is_ok());
+}
+
+#[test]
+fn json_error_format_reports_kind_and_message() {
+    let output = Command::new(env!("CARGO_BIN_EXE_agent-box"))
+        .args(["--no-expose", "--ip", "not_an_ip", "--error-format", "json"])
+        .output()
+        .expect("binary runs");
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+
+    let stderr = String::from_utf8(output.stderr).expect("utf8 stderr");
+    let value: serde_json::Value = serde_json::from_str(stderr.trim()).expect("one JSON object");
+    assert_eq!(value["kind"], "invalid_bind");
+    assert_eq!(value["error"], "invalid IP address: not_an_ip");
+    assert_eq!(value.as_object().map(|o| o.len()), Some(2));
+}
+
+#[test]
+fn fail_on_waiting_exits_nonzero_listing_waiting_demo_sessions() {
+    let output = Command::new(env!("CARGO_BIN_EXE_agent-box"))
+        .args(["--demo", "--fail-on-waiting", "--no-expose"])
+        .output()
+        .expect("binary runs");
+    assert!(!output.status.success());
+
+    let stderr = String::from_utf8(output.stderr).expect("utf8 stderr");
+    assert!(stderr.contains("waiting for input"), "stderr: {stderr}");
+    assert!(stderr.contains("demo-codex-1"), "stderr: {stderr}");
+}
+
+#[test]
+fn fail_on_waiting_exits_zero_without_waiting_sessions() {
+    // Every local session is excluded, so whatever runs on this machine cannot be waiting.
+    let output = Command::new(env!("CARGO_BIN_EXE_agent-box"))
+        .args(["--fail-on-waiting", "--no-expose", "--exclude-user", "*"])
+        .output()
+        .expect("binary runs");
+    let stderr = String::from_utf8(output.stderr).expect("utf8 stderr");
+    assert!(output.status.success(), "stderr: {stderr}");
+    assert!(!stderr.contains("waiting for input"), "stderr: {stderr}");
+}
@@ -1,7 +1,16 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
 use agent_box::collector::MockCollector;
 use agent_box::model::{RuntimeStateStore, SessionStatus};
+use agent_box::renderer::TerminalRenderer;
 use agent_box::run_once_with_collector;
-use agent_box::{render_snapshot, sample_event};
+use agent_box::sync::RemoteCache;
+use agent_box::{
+    check_no_waiting, render_snapshot, render_snapshot_session, render_snapshot_with_renderer,
+    sample_event, wait_for_next_tick, RefreshTrigger,
+};
 
 #[test]
 fn local_collect_store_render_flow() {
@@ -25,3 +34,123 @@ fn store_blocks_invalid_terminal_regression() {
     assert!(!store.upsert(invalid));
 }
 
+#[test]
+fn fail_on_waiting_gate_lists_blocked_sessions() {
+    let mut store = RuntimeStateStore::default();
+    store.upsert(sample_event("busy"));
+    assert!(check_no_waiting(&store).is_ok());
+
+    let mut blocked = sample_event("blocked");
+    blocked.status = SessionStatus::WaitingInput;
+    blocked.pending_action = Some("Approve write".to_string());
+    store.upsert(blocked);
+    assert_eq!(store.summary().waiting_input, 1);
+    let err = check_no_waiting(&store).expect_err("waiting session fails the gate");
+    let message = err.to_string();
+    assert!(message.starts_with("1 session is waiting for input"));
+    assert!(message.contains("blocked [unknown] sample @ /tmp/demo: Approve write"));
+    assert!(!message.contains("busy"));
+}
+
+#[test]
+fn renders_single_session_by_id() {
+    let mut store = RuntimeStateStore::default();
+    let mut focus = sample_event("focus");
+    focus.title = "focused task".to_string();
+    store.upsert(focus);
+    store.upsert(sample_event("other"));
+
+    let block = render_snapshot_session(&store, "focus", 0).expect("known id renders");
+    assert!(block.contains("focused task"));
+    assert!(!block.contains("\n\n"));
+    assert!(render_snapshot_session(&store, "missing", 0).is_none());
+}
+
+#[test]
+fn demo_flag_renders_sample_sessions_without_scanning() {
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_agent-box"))
+        .args(["--demo", "--once", "--theme", "mono"])
+        .output()
+        .expect("run agent-box --demo");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    for title in ["refactor checkout flow", "fix flaky payment tests", "write API docs"] {
+        assert!(stdout.contains(title), "missing {title:?} in:\n{stdout}");
+    }
+    assert!(stdout.contains("Approve: cargo test --workspace"));
+    assert!(!stdout.contains("Join by:"), "demo mode must not offer a sync server");
+}
+
+#[test]
+fn quiet_flag_keeps_sessions_but_drops_the_banner() {
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_agent-box"))
+        .args(["--demo", "--once", "--quiet", "--theme", "mono"])
+        .output()
+        .expect("run agent-box --quiet");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("refactor checkout flow"), "missing sessions in:\n{stdout}");
+    assert!(!stdout.contains("Agent-box live monitor"));
+    assert!(!stdout.contains("--- refresh @"));
+    assert!(output.stderr.is_empty());
+}
+
+#[test]
+fn label_heads_the_text_banner_and_tags_jsonl_records() {
+    let run = |extra: &[&str]| {
+        let output = std::process::Command::new(env!("CARGO_BIN_EXE_agent-box"))
+            .args(["--demo", "--once", "--theme", "mono", "--label", "PROD"])
+            .args(extra)
+            .output()
+            .expect("run agent-box --label");
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    };
+
+    let text = run(&[]);
+    assert!(text.contains("=== PROD ===\nAgent-box live monitor"), "{text}");
+
+    let jsonl = run(&["--format", "jsonl"]);
+    let records = jsonl
+        .lines()
+        .map(|line| serde_json::from_str::<serde_json::Value>(line).expect("json record"))
+        .collect::<Vec<_>>();
+    assert!(!records.is_empty());
+    assert!(records.iter().all(|record| record["label"] == "PROD"), "{jsonl}");
+}
+
+#[test]
+fn empty_view_still_lists_connected_peers() {
+    let mut cache = RemoteCache::new(1_000);
+    cache.ingest("build-box", Vec::new(), 100);
+    let renderer = TerminalRenderer::new().with_idle_peers(cache.idle_peers(200));
+    let store = RuntimeStateStore::default();
+
+    let output = render_snapshot_with_renderer(&store, &renderer, 0);
+    assert!(output.starts_with("No active Claude/Codex/Gemini local sessions detected."));
+    assert!(output.contains("⟿ build-box · connected, no sessions"), "{output}");
+    assert_eq!(render_snapshot(&store), "No active Claude/Codex/Gemini local sessions detected.");
+}
+
+#[test]
+fn refresh_request_cuts_the_tick_wait_short() {
+    let stop = AtomicBool::new(false);
+    let refresh = RefreshTrigger::default();
+    assert!(!wait_for_next_tick(Duration::from_millis(20), &stop, &refresh));
+
+    // What the SIGUSR1 handler does: set the shared flag from another thread.
+    let flag = refresh.flag();
+    let signal = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(30));
+        flag.store(true, Ordering::Relaxed);
+    });
+    let started = Instant::now();
+    assert!(wait_for_next_tick(Duration::from_secs(10), &stop, &refresh));
+    assert!(started.elapsed() < Duration::from_secs(5));
+    signal.join().unwrap();
+    assert!(!refresh.take(), "the request is consumed by the wait");
+
+    refresh.request();
+    stop.store(true, Ordering::Relaxed);
+    assert!(!wait_for_next_tick(Duration::from_secs(10), &stop, &refresh));
+}